@@ -35,6 +35,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         content_id: media_url.to_string(),
         stream_type: "BUFFERED".to_string(),
         content_type: "video/mp4".to_string(),
+        duration: None,
         metadata: None,
         tracks: None,
     };
@@ -1,6 +1,6 @@
 use castru::controllers::media::MediaController;
 use castru::protocol::media::MediaInformation;
-use castru::{CastClient, CastError};
+use castru::CastClient;
 use std::error::Error;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -39,6 +39,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         content_id: media_url.to_string(),
         stream_type: "BUFFERED".to_string(),
         content_type: "video/mp4".to_string(),
+        duration: None,
         metadata: None,
         tracks: None,
     };
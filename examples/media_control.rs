@@ -1,7 +1,5 @@
-use castru::{CastClient, CastError};
+use castru::CastClient;
 use std::error::Error;
-use std::time::Duration;
-use tokio::time::sleep;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -1,22 +1,35 @@
+use crate::client::{CastClientConfig, ConnectionEvent};
 use crate::config::Config;
+use crate::controllers::application::Application;
 use crate::controllers::default_media_receiver::DefaultMediaReceiver;
+use crate::controllers::group::{CastGroup, DEFAULT_MAX_DRIFT};
 use crate::controllers::media::{MediaSource, PlaybackStatus};
 use crate::controllers::receiver::ReceiverController;
 use crate::controllers::tui::{TuiCommand, TuiController, TuiState};
-use crate::discovery::{CastDevice, discover_devices_async};
-use crate::protocol::media::{MediaInformation, MediaResponse, NAMESPACE as MEDIA_NAMESPACE, MediaTrack};
-use crate::protocol::receiver::{ReceiverResponse, NAMESPACE as RECEIVER_NAMESPACE};
-use crate::server::{get_mime_type, StreamServer, StreamSource};
+use crate::discovery::{CastDevice, DeviceBrowser, DiscoveryEvent, discover_devices_async};
+use crate::error::CastError;
+use crate::events::DeviceEvent;
+use crate::protocol::media::{Image, MediaInformation, MediaMetadata, MediaTrack, QueueItem, RepeatMode};
+use crate::server::{
+    get_mime_type, HlsOutput, Resource, StreamServer, StreamSource, TranscodeOutput,
+};
 use crate::torrent::{TorrentConfig, TorrentManager, TorrentStreamInfo};
-use crate::transcode::{needs_transcoding, probe_media, spawn_ffmpeg, MediaProbeResult, TranscodeConfig};
+use crate::transcode::cache::{CacheConfig, TranscodeCache};
+use crate::transcode::{
+    needs_transcoding, probe_media, spawn_ffmpeg, spawn_hls, MediaProbeResult, TranscodeConfig,
+    HLS_PLAYLIST,
+};
+use crate::utils::metadata::{find_artwork, guess_metadata};
+use crate::tls::{CertificatePolicy, FingerprintStore};
 use crate::CastClient;
 
 use std::collections::VecDeque;
 use std::error::Error;
 use std::net::IpAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
+use futures_util::future::join_all;
 use librqbit::ManagedTorrent;
 
 enum InternalEvent {
@@ -30,8 +43,6 @@ enum InternalEvent {
 use tokio::sync::mpsc;
 
 struct AppState {
-    is_transcoding: bool,
-    seek_offset: f64,
     current_time: f64,
     last_known_time: f64,
     last_update_instant: std::time::Instant,
@@ -50,11 +61,35 @@ struct AppState {
     torrent_file_name: Option<String>,
     torrent_handle: Option<Arc<ManagedTorrent>>,
     subtitles: Option<String>,
+    /// Tokens of what the server serves for the current item.
+    served: Vec<String>,
+    /// IDs the receiver gave the queued items, in playlist order.
+    queue_item_ids: Vec<i32>,
+    /// Transcode to HLS rather than a single stream.
+    use_hls: bool,
+    /// The transcode the receiver is playing, if the current item is transcoded.
+    transcode: Option<Transcode>,
+    /// The transcode of the current item's whole file. It keeps running while the receiver
+    /// plays a transcode from later on, so it gets cached and can be served once it is there.
+    whole_transcode: Option<Transcode>,
+    transcode_cache: Arc<TranscodeCache>,
+}
+
+impl AppState {
+    /// Where the stream the receiver plays starts in the source; receiver times are
+    /// relative to it.
+    fn stream_offset(&self) -> f64 {
+        self.transcode.as_ref().map_or(0.0, Transcode::start_time)
+    }
 }
 
 const TORRENT_BUFFER_PCT_THRESHOLD: f32 = 3.0;
 const TORRENT_BUFFER_SIZE_THRESHOLD: u64 = 10 * 1024 * 1024; // 10MB
 const WATCHDOG_TIMEOUT_SEC: u64 = 30;
+/// UUID given to devices targeted by `--ip` rather than discovered.
+const UNKNOWN_UUID: &str = "Unknown";
+/// How long ffmpeg may take to write the first HLS segment.
+const HLS_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 const BUFFER_UNDERRUN_THRESHOLD: f32 = 0.5; // percent
 const BUFFER_RESUME_THRESHOLD: f32 = 2.0; // percent
 
@@ -94,6 +129,10 @@ impl CastNowCore {
             return Err("No valid media sources found".into());
         }
 
+        // URLs are fetched by the receiver itself, so it can queue them: no gap between items,
+        // and playback continues after we exit. Anything we serve ourselves goes one at a time.
+        let use_queue = playlist.iter().all(|s| matches!(s, MediaSource::Url(_)));
+
         // 1. Setup Server (lazy init)
         let mut server = StreamServer::new();
         let bind_ip = if let Some(ip) = &self.config.myip {
@@ -107,54 +146,25 @@ impl CastNowCore {
         // Setup Torrent Manager
         let torrent_manager = Arc::new(TorrentManager::new(TorrentConfig::default()).await?);
 
-        // 2. Discover or Target device
-        let device = if let Some(ip_str) = &self.config.target_ip {
-            if !self.config.quiet { println!("Targeting specific IP: {}", ip_str); }
-            CastDevice {
-                ip: ip_str.parse().map_err(|_| "Invalid IP address provided")?,
-                port: 8009,
-                friendly_name: "Direct Connect".to_string(),
-                model_name: "Unknown".to_string(),
-                uuid: "Unknown".to_string(),
-            }
-        } else {
-            if !self.config.quiet { println!("Searching for Cast devices..."); }
-            let mut rx = discover_devices_async()?;
-            let matching_device;
-
-            let timeout = tokio::time::sleep(Duration::from_secs(10));
-            tokio::pin!(timeout);
+        let pins = self.fingerprint_store()?;
 
-            loop {
-                tokio::select! {
-                    Some(d) = rx.recv() => {
-                        if let Some(ref name) = self.config.target_name {
-                            if d.friendly_name == *name {
-                                println!("Found matching device: {}", d.friendly_name);
-                                matching_device = Some(d);
-                                break;
-                            }
-                        } else {
-                            // First found
-                            println!("Found device: {}", d.friendly_name);
-                            matching_device = Some(d);
-                            break;
-                        }
-                    }
-                    _ = &mut timeout => {
-                        matching_device = None;
-                        break;
-                    }
-                }
-            }
-            matching_device.ok_or("No matching device found")?
-        };
+        // 2. Discover or Target device(s)
+        let mut devices = self.resolve_devices().await?;
+        if self.config.is_group() {
+            return self
+                .run_group(devices, playlist, server, torrent_manager, pins)
+                .await;
+        }
+        let device = devices.remove(0);
         
         if !self.config.quiet { println!("Found {}", device.friendly_name); }
 
         // 3. Connect and Launch
         if !self.config.quiet { println!("Connecting to {}...", device.ip); }
-        let mut client = CastClient::connect(&device.ip.to_string(), device.port).await?;
+        let client_config = client_config(&device, pins.as_ref());
+        let mut client =
+            CastClient::connect_with_config(&device.ip.to_string(), device.port, client_config.clone())
+                .await?;
         let mut receiver_ctrl = ReceiverController::new(&client);
         client.connect_receiver().await?;
 
@@ -175,8 +185,6 @@ impl CastNowCore {
         let mut current_status = PlaybackStatus::Idle;
 
         let mut app_state = AppState {
-            is_transcoding: false,
-            seek_offset: 0.0,
             current_time: 0.0,
             last_known_time: 0.0,
             last_update_instant: std::time::Instant::now(),
@@ -195,20 +203,31 @@ impl CastNowCore {
             torrent_file_name: None,
             torrent_handle: None,
             subtitles: self.config.subtitles.clone(),
+            served: Vec::new(),
+            queue_item_ids: Vec::new(),
+            use_hls: !self.config.no_hls,
+            transcode: None,
+            whole_transcode: None,
+            transcode_cache: transcode_cache(&self.config),
         };
 
-        let mut events = client.events();
+        let mut events = client.device_events();
+        let mut connection_events = client.connection_events();
+        let mut status_before_disconnect: Option<PlaybackStatus> = None;
         let (probe_tx, mut probe_rx) = mpsc::channel(16);
 
         // Load first item
-        if let Some(source) = playlist.front() {
+        if use_queue {
+            if let Err(e) = load_queue(&app, &server, &playlist, 0, 0.0, self.config.loop_playlist, &mut app_state).await {
+                log::error!("Failed to load queue: {}", e);
+            }
+        } else if let Some(source) = playlist.front() {
             app_state.current_media_idx = 0;
             app_state.source = Some(source.clone());
             match load_media(
                 &app,
                 &server,
                 source,
-                &server_url_base,
                 0.0,
                 &torrent_manager,
                 &tui,
@@ -217,11 +236,9 @@ impl CastNowCore {
             )
             .await
             {
-                Ok((is_tx, probe, offset)) => {
-                    app_state.is_transcoding = is_tx;
-                    app_state.seek_offset = offset;
-                    app_state.current_time = offset;
-                    app_state.last_known_time = offset;
+                Ok(probe) => {
+                    app_state.current_time = 0.0;
+                    app_state.last_known_time = 0.0;
                     app_state.last_update_instant = std::time::Instant::now();
                     app_state.total_duration = probe.duration;
                     app_state.video_codec = probe.video_codec;
@@ -258,22 +275,25 @@ impl CastNowCore {
                         if let Some(pause_start) = app_state.pause_start_time {
                             if pause_start.elapsed() > Duration::from_secs(10) {
                                 // Attempt full reload to recover from potential transcoding crashes or idle states
-                                if let Some(source) = app_state.source.clone() {
+                                if use_queue {
+                                    log::info!("Auto-recovery: 10s wait elapsed. Reloading queue...");
+                                    let (idx, curr_time) = (app_state.current_media_idx, app_state.current_time);
+                                    if let Err(e) = load_queue(&app, &server, &playlist, idx, curr_time, self.config.loop_playlist, &mut app_state).await {
+                                        log::error!("Failed to reload queue: {}", e);
+                                    }
+                                } else if let Some(source) = app_state.source.clone() {
                                     log::info!("Auto-recovery: 10s wait elapsed. Attempting reload...");
                                     let curr_time = app_state.current_time;
-                                    if let Ok((is_tx, probe, offset)) = load_media(
+                                    if let Ok(probe) = load_media(
                                         &app,
                                         &server,
                                         &source,
-                                        &server_url_base,
                                         curr_time,
                                         &torrent_manager,
                                         &tui,
                                         &mut app_state,
                                         Some(probe_tx.clone()),
                                     ).await {
-                                         app_state.is_transcoding = is_tx;
-                                         app_state.seek_offset = offset;
                                          app_state.last_known_time = app_state.current_time;
                                          app_state.last_update_instant = std::time::Instant::now();
                                          app_state.total_duration = probe.duration;
@@ -291,12 +311,15 @@ impl CastNowCore {
                     // Watchdog: If playing but time hasn't advanced for X seconds, resume.
                     if matches!(current_status, PlaybackStatus::Playing)
                         && app_state.last_update_instant.elapsed() > Duration::from_secs(WATCHDOG_TIMEOUT_SEC) {
-                            if let Some(source) = app_state.source.clone() {
+                            if use_queue {
+                                let (idx, curr_time) = (app_state.current_media_idx, app_state.current_time);
+                                if let Err(e) = load_queue(&app, &server, &playlist, idx, curr_time, self.config.loop_playlist, &mut app_state).await {
+                                    eprintln!("Watchdog resume failed: {}", e);
+                                }
+                            } else if let Some(source) = app_state.source.clone() {
                                 let curr_time = app_state.current_time;
-                                match load_media(&app, &server, &source, &server_url_base, curr_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
-                                    Ok((is_tx, probe, offset)) => {
-                                        app_state.is_transcoding = is_tx;
-                                        app_state.seek_offset = offset;
+                                match load_media(&app, &server, &source, curr_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
+                                    Ok(probe) => {
                                         app_state.last_known_time = app_state.current_time;
                                         app_state.last_update_instant = std::time::Instant::now();
                                         app_state.total_duration = probe.duration;
@@ -342,15 +365,24 @@ impl CastNowCore {
                              current_status = PlaybackStatus::Playing;
                              app_state.pause_start_time = None;
                         },
+                        // Until the receiver reports a session there is no queue to move through.
+                        TuiCommand::Next if use_queue => {
+                            if let Some(sid) = app_state.media_session_id {
+                                let _ = app.queue_next(sid).await;
+                            }
+                        },
+                        TuiCommand::Previous if use_queue => {
+                            if let Some(sid) = app_state.media_session_id {
+                                let _ = app.queue_prev(sid).await;
+                            }
+                        },
                         TuiCommand::Next => {
                             app_state.current_media_idx += 1;
                              if let Some(source) = playlist.get(app_state.current_media_idx) {
                                  app_state.source = Some(source.clone());
-                                if let Ok((is_tx, probe, offset)) = load_media(&app, &server, source, &server_url_base, 0.0, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
-                                     app_state.is_transcoding = is_tx;
-                                     app_state.seek_offset = offset;
-                                     app_state.current_time = offset;
-                                     app_state.last_known_time = offset;
+                                if let Ok(probe) = load_media(&app, &server, source, 0.0, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
+                                     app_state.current_time = 0.0;
+                                     app_state.last_known_time = 0.0;
                                      app_state.last_update_instant = std::time::Instant::now();
                                      app_state.total_duration = probe.duration;
                                      app_state.video_codec = probe.video_codec;
@@ -365,11 +397,9 @@ impl CastNowCore {
                                 app_state.current_media_idx -= 1;
                                  if let Some(source) = playlist.get(app_state.current_media_idx) {
                                     app_state.source = Some(source.clone());
-                                    if let Ok((is_tx, probe, offset)) = load_media(&app, &server, source, &server_url_base, 0.0, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
-                                         app_state.is_transcoding = is_tx;
-                                         app_state.seek_offset = offset;
-                                         app_state.current_time = offset;
-                                         app_state.last_known_time = offset;
+                                    if let Ok(probe) = load_media(&app, &server, source, 0.0, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
+                                         app_state.current_time = 0.0;
+                                         app_state.last_known_time = 0.0;
                                          app_state.last_update_instant = std::time::Instant::now();
                                          app_state.total_duration = probe.duration;
                                          app_state.video_codec = probe.video_codec;
@@ -380,12 +410,11 @@ impl CastNowCore {
                         },
                         TuiCommand::SeekForward(s) => {
                              let new_time = app_state.current_time + s as f64;
-                             if app_state.is_transcoding {
+                             match receiver_seek_time(&app_state, new_time) {
+                             None => {
                                  if let Some(src) = app_state.source.clone() {
-                                     match load_media(&app, &server, &src, &server_url_base, new_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
-                                          Ok((is_tx, probe, offset)) => {
-                                             app_state.is_transcoding = is_tx;
-                                             app_state.seek_offset = offset;
+                                     match load_media(&app, &server, &src, new_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
+                                          Ok(probe) => {
                                              app_state.current_time = new_time;
                                              app_state.last_known_time = new_time;
                                              app_state.last_update_instant = std::time::Instant::now();
@@ -396,21 +425,22 @@ impl CastNowCore {
                                          Err(e) => eprintln!("SeekForward load error: {}", e),
                                      }
                                  }
-                             } else {
-                                 let _ = app.seek(app_state.media_session_id.unwrap_or(1), new_time as f32).await;
+                             }
+                             Some(target) => {
+                                 let _ = app.seek(app_state.media_session_id.unwrap_or(1), target).await;
                                  app_state.current_time = new_time;
                                  app_state.last_known_time = new_time;
                                  app_state.last_update_instant = std::time::Instant::now();
                              }
+                             }
                         },
                         TuiCommand::SeekBackward(s) => {
                              let new_time = (app_state.current_time - s as f64).max(0.0);
-                             if app_state.is_transcoding {
+                             match receiver_seek_time(&app_state, new_time) {
+                             None => {
                                  if let Some(src) = app_state.source.clone() {
-                                     match load_media(&app, &server, &src, &server_url_base, new_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
-                                          Ok((is_tx, probe, offset)) => {
-                                             app_state.is_transcoding = is_tx;
-                                             app_state.seek_offset = offset;
+                                     match load_media(&app, &server, &src, new_time, &torrent_manager, &tui, &mut app_state, Some(probe_tx.clone())).await {
+                                          Ok(probe) => {
                                              app_state.current_time = new_time;
                                              app_state.last_known_time = new_time;
                                              app_state.last_update_instant = std::time::Instant::now();
@@ -421,12 +451,14 @@ impl CastNowCore {
                                          Err(e) => eprintln!("SeekBackward load error: {}", e),
                                      }
                                  }
-                             } else {
-                                 let _ = app.seek(app_state.media_session_id.unwrap_or(1), new_time as f32).await;
+                             }
+                             Some(target) => {
+                                 let _ = app.seek(app_state.media_session_id.unwrap_or(1), target).await;
                                  app_state.current_time = new_time;
                                  app_state.last_known_time = new_time;
                                  app_state.last_update_instant = std::time::Instant::now();
                              }
+                             }
                         },
                         TuiCommand::VolumeUp => {
                             let new_vol = (app_state.volume_level.unwrap_or(0.0) + 0.05).min(1.0);
@@ -467,17 +499,20 @@ impl CastNowCore {
                                 device_name: app_state.device_name.clone(),
                                 animation_frame: app_state.animation_frame,
                                 torrent_progress: app_state.torrent_progress,
+                                members: Vec::new(),
                             };
                             let _ = tui.draw(&tui_state);
 
-                            match CastClient::connect(&device.ip.to_string(), device.port).await {
+                            let _ = client.close().await;
+                            match CastClient::connect_with_config(&device.ip.to_string(), device.port, client_config.clone()).await {
                                 Ok(new_client) => {
                                     client = new_client;
                                     receiver_ctrl = ReceiverController::new(&client);
                                     let _ = client.connect_receiver().await;
                                     app = DefaultMediaReceiver::new(&client);
                                     let _ = app.launch().await;
-                                    events = client.events();
+                                    events = client.device_events();
+                                    connection_events = client.connection_events();
                                 }
                                 Err(e) => {
                                     eprintln!("Reconnect failed: {}", e);
@@ -499,20 +534,16 @@ impl CastNowCore {
                         device_name: app_state.device_name.clone(),
                         animation_frame: app_state.animation_frame,
                         torrent_progress: app_state.torrent_progress,
+                        members: Vec::new(),
                     };
                     let _ = tui.draw(&tui_state);
                 }
                 Ok(event) = events.recv() => {
-                    // Handle events (same logic as main.rs)
-                     if event.namespace == MEDIA_NAMESPACE {
-                         if let Ok(MediaResponse::MediaStatus { status, .. }) = serde_json::from_str::<MediaResponse>(&event.payload) {
+                     match event {
+                         DeviceEvent::MediaStatus { status, .. } => {
                               if let Some(s) = status.first() {
                                   let reported_time = s.current_time as f64;
-                                  if app_state.is_transcoding {
-                                      app_state.current_time = reported_time + app_state.seek_offset;
-                                  } else {
-                                      app_state.current_time = reported_time;
-                                  }
+                                  app_state.current_time = reported_time + app_state.stream_offset();
 
                                   if (app_state.current_time - app_state.last_known_time).abs() > 0.1 {
                                       app_state.last_known_time = app_state.current_time;
@@ -520,6 +551,23 @@ impl CastNowCore {
                                   }
 
                                   app_state.media_session_id = Some(s.media_session_id);
+                                  // The receiver advances its queue on its own; follow along.
+                                  if let Some(item_id) = s.current_item_id.filter(|_| use_queue) {
+                                      let idx = app_state.queue_item_ids.iter().position(|id| *id == item_id);
+                                      if let Some(idx) = idx.filter(|idx| *idx != app_state.current_media_idx) {
+                                          log::info!("Receiver moved to queue item {}", idx);
+                                          app_state.current_media_idx = idx;
+                                          app_state.source = playlist.get(idx).cloned();
+                                          app_state.total_duration = None;
+                                      }
+                                  }
+                                  // A transcoded stream starts at the seek offset, so its length
+                                  // is not the file's; keep the probed duration for those.
+                                  if let Some(d) = s.duration().filter(|d| *d > 0.0) {
+                                      if app_state.transcode.is_none() {
+                                          app_state.total_duration = Some(d as f64);
+                                      }
+                                  }
                                   if let Some(vol) = &s.volume {
                                       app_state.volume_level = vol.level;
                                       if let Some(muted) = vol.muted {
@@ -568,6 +616,9 @@ impl CastNowCore {
                                                                                                                                  if app_state.pause_start_time.is_none() {
                                                                                                                                      app_state.pause_start_time = Some(std::time::Instant::now());
                                                                                                                                  }
+                                                                                                                            } else if use_queue && s.idle_reason.as_deref() == Some("FINISHED") {
+                                                                                                                                 // The receiver plays the next queue item, if any, by itself.
+                                                                                                                                 log::info!("Receiver finished queue item {}.", app_state.current_media_idx);
                                                                                                                             } else if s.idle_reason.as_deref() == Some("FINISHED") {                                                                                                                                            // Check if we really finished or if it was a drop
                                                                                                                                             let total = app_state.total_duration.unwrap_or(0.0);
                                                                                                                                             // Use a threshold (e.g. within 10s of end)
@@ -588,18 +639,15 @@ impl CastNowCore {
                                                                                                                                                             &app,
                                                                                                                                                             &server,
                                                                                                                                                             source,
-                                                                                                                                                            &server_url_base,
                                                                                                                                                             0.0,
                                                                                                                                                             &torrent_manager,
                                                                                                                                                             &tui,
                                                                                                                                                             &mut app_state,
                                                                                                                                                             Some(probe_tx.clone()),
                                                                                                                                                         ).await {
-                                                                                                                                                            Ok((is_tx, probe, offset)) => {
-                                                                                                                                                                 app_state.is_transcoding = is_tx;
-                                                                                                                                                                 app_state.seek_offset = offset;
-                                                                                                                                                                 app_state.current_time = offset;
-                                                                                                                                                                 app_state.last_known_time = offset;
+                                                                                                                                                            Ok(probe) => {
+                                                                                                                                                                 app_state.current_time = 0.0;
+                                                                                                                                                                 app_state.last_known_time = 0.0;
                                                                                                                                                                  app_state.last_update_instant = std::time::Instant::now();
                                                                                                                                                                                                                               app_state.total_duration = probe.duration;
                                                                                                                                                                                                                               app_state.video_codec = probe.video_codec;
@@ -627,12 +675,12 @@ impl CastNowCore {
                                         device_name: app_state.device_name.clone(),
                                         animation_frame: app_state.animation_frame,
                                         torrent_progress: app_state.torrent_progress,
+                                        members: Vec::new(),
                                     };
                                     let _ = tui.draw(&tui_state);
                               }
                          }
-                     } else if event.namespace == RECEIVER_NAMESPACE {
-                          if let Ok(ReceiverResponse::ReceiverStatus { status, .. }) = serde_json::from_str::<ReceiverResponse>(&event.payload) {
+                         DeviceEvent::ReceiverStatus { status, .. } => {
                               if let Some(vol) = status.volume {
                                   app_state.volume_level = vol.level;
                                   if let Some(muted) = vol.muted {
@@ -650,12 +698,49 @@ impl CastNowCore {
                                         device_name: app_state.device_name.clone(),
                                         animation_frame: app_state.animation_frame,
                                         torrent_progress: app_state.torrent_progress,
+                                        members: Vec::new(),
                                     };
                                     let _ = tui.draw(&tui_state);
                               }
-                          }
+                         }
+                         _ => {}
                      }
                 }
+                Ok(event) = connection_events.recv() => {
+                    match event {
+                        ConnectionEvent::Disconnected { reason } => {
+                            log::warn!("Lost connection to device ({}). Status: {:?} -> Reconnecting", reason, current_status);
+                            if current_status != PlaybackStatus::Reconnecting {
+                                status_before_disconnect = Some(current_status.clone());
+                            }
+                            current_status = PlaybackStatus::Reconnecting;
+                        }
+                        ConnectionEvent::Reconnected => {
+                            // Virtual connections are re-opened by the client; refresh the media state.
+                            let restored = status_before_disconnect.take().unwrap_or(PlaybackStatus::Idle);
+                            log::info!("Reconnected to device. Status: {:?} -> {:?}", current_status, restored);
+                            current_status = restored;
+                            app_state.last_update_instant = std::time::Instant::now();
+                            let _ = app.get_status(app_state.media_session_id).await;
+                        }
+                        _ => {}
+                    }
+                    let tui_state = TuiState {
+                        status: format!("{:?}", current_status),
+                        current_time: app_state.current_time as f32,
+                        total_duration: app_state.total_duration.map(|d| d as f32),
+                        volume_level: app_state.volume_level,
+                        is_muted: app_state.is_muted,
+                        media_title: None,
+                        video_codec: app_state.video_codec.clone(),
+                        audio_codec: app_state.audio_codec.clone(),
+                        device_name: app_state.device_name.clone(),
+                        animation_frame: app_state.animation_frame,
+                        torrent_progress: app_state.torrent_progress,
+                        members: Vec::new(),
+                    };
+                    let _ = tui.draw(&tui_state);
+                }
                 _ = animation_interval.tick() => {
                      app_state.animation_frame = app_state.animation_frame.wrapping_add(1);
                      if matches!(current_status, PlaybackStatus::Playing) {
@@ -706,6 +791,7 @@ impl CastNowCore {
                          device_name: app_state.device_name.clone(),
                          animation_frame: app_state.animation_frame,
                          torrent_progress: app_state.torrent_progress,
+                         members: Vec::new(),
                      };
                      let _ = tui.draw(&tui_state);
                 }
@@ -713,6 +799,269 @@ impl CastNowCore {
         }
 
         tui.stop();
+        let _ = client.close().await;
+        Ok(())
+    }
+
+    /// The store of pinned certificates, if `--pin-certificates` was given.
+    fn fingerprint_store(&self) -> Result<Option<Arc<FingerprintStore>>, Box<dyn Error>> {
+        if !self.config.pin_certificates {
+            return Ok(None);
+        }
+        let path = FingerprintStore::default_path().ok_or("Cannot locate the config directory")?;
+        Ok(Some(Arc::new(FingerprintStore::open(path)?)))
+    }
+
+    /// Finds every targeted device: `--ip` ones directly, `--name` ones over mDNS.
+    /// Without targets, the first device found is used.
+    async fn resolve_devices(&self) -> Result<Vec<CastDevice>, Box<dyn Error>> {
+        let mut devices = Vec::new();
+        for ip_str in &self.config.target_ips {
+            if !self.config.quiet { println!("Targeting specific IP: {}", ip_str); }
+            let ip: IpAddr = ip_str.parse().map_err(|_| "Invalid IP address provided")?;
+            devices.push(CastDevice {
+                ip,
+                addresses: vec![ip],
+                port: 8009,
+                // In a group the name has to tell the members apart.
+                friendly_name: if self.config.is_group() { ip_str.clone() } else { "Direct Connect".to_string() },
+                model_name: "Unknown".to_string(),
+                uuid: UNKNOWN_UUID.to_string(),
+                capabilities: Default::default(),
+                status_text: None,
+                icon_path: None,
+                version: None,
+                bs: None,
+            });
+        }
+        let mut wanted = self.config.target_names.clone();
+        if !devices.is_empty() && wanted.is_empty() {
+            return Ok(devices);
+        }
+
+        if !self.config.quiet { println!("Searching for Cast devices..."); }
+        let mut rx = discover_devices_async()?;
+        let timeout = tokio::time::sleep(Duration::from_secs(10));
+        tokio::pin!(timeout);
+
+        loop {
+            tokio::select! {
+                Some(d) = rx.recv() => {
+                    if wanted.is_empty() {
+                        // First found
+                        println!("Found device: {}", d.friendly_name);
+                        devices.push(d);
+                        break;
+                    }
+                    if let Some(pos) = wanted.iter().position(|name| *name == d.friendly_name) {
+                        println!("Found matching device: {}", d.friendly_name);
+                        wanted.remove(pos);
+                        devices.push(d);
+                        if wanted.is_empty() {
+                            break;
+                        }
+                    }
+                }
+                _ = &mut timeout => {
+                    if devices.is_empty() || !wanted.is_empty() {
+                        return Err(format!("No matching device found for: {}", wanted.join(", ")).into());
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Casts the playlist to several devices at once, keeping them in step.
+    ///
+    /// Playlist items are loaded on every member one at a time; the first device leads.
+    async fn run_group(
+        &self,
+        devices: Vec<CastDevice>,
+        playlist: VecDeque<MediaSource>,
+        server: StreamServer,
+        torrent_manager: Arc<TorrentManager>,
+        pins: Option<Arc<FingerprintStore>>,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.config.quiet {
+            println!("Connecting to {} devices...", devices.len());
+        }
+        let pins = pins.as_ref();
+        let connections = join_all(devices.iter().map(|d| async move {
+            let client =
+                CastClient::connect_with_config(&d.ip.to_string(), d.port, client_config(d, pins))
+                    .await?;
+            client.connect_receiver().await?;
+            Ok::<_, CastError>((d.friendly_name.clone(), client))
+        }))
+        .await;
+        let clients = connections.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        let mut group = CastGroup::new(clients.clone());
+        group.launch().await?;
+        log::info!("Default Media Receiver launched on {} devices.", group.len());
+        if let Some(vol) = self.config.volume {
+            let _ = group.set_volume(vol).await;
+        }
+
+        let tui = TuiController::new();
+        let mut tui_rx = tui.start()?;
+
+        let mut app_state = AppState {
+            current_time: 0.0,
+            last_known_time: 0.0,
+            last_update_instant: std::time::Instant::now(),
+            pause_start_time: None,
+            total_duration: None,
+            volume_level: Some(1.0),
+            is_muted: false,
+            source: None,
+            current_media_idx: 0,
+            video_codec: None,
+            audio_codec: None,
+            device_name: format!(
+                "Group ({})",
+                devices.iter().map(|d| d.friendly_name.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+            animation_frame: 0,
+            media_session_id: None,
+            torrent_progress: None,
+            torrent_file_name: None,
+            torrent_handle: None,
+            subtitles: self.config.subtitles.clone(),
+            served: Vec::new(),
+            queue_item_ids: Vec::new(),
+            use_hls: !self.config.no_hls,
+            transcode: None,
+            whole_transcode: None,
+            transcode_cache: transcode_cache(&self.config),
+        };
+
+        let mut states = group.watch_states();
+        // Set when the leader starts playing an item, so FINISHED from an earlier one is ignored.
+        let mut item_started = false;
+        if let Err(e) = load_group_item(&group, &server, &playlist, 0, 0.0, &torrent_manager, &tui, &mut app_state).await {
+            log::error!("Failed to load media: {}", e);
+        }
+
+        let mut animation_interval = tokio::time::interval(Duration::from_millis(150));
+        let mut resync_interval = tokio::time::interval(Duration::from_secs(5));
+
+        loop {
+            tokio::select! {
+                Some(cmd) = tui_rx.recv() => {
+                    let leader = group.leader();
+                    let position = leader.as_ref().map(|l| l.position() as f64 + app_state.stream_offset()).unwrap_or(0.0);
+                    let result = match cmd {
+                        TuiCommand::Quit => break,
+                        TuiCommand::TogglePlay => {
+                            if leader.is_some_and(|l| l.is_playing() || l.player_state == "BUFFERING") {
+                                group.pause().await
+                            } else {
+                                group.play().await
+                            }
+                        }
+                        TuiCommand::Play => group.play().await,
+                        TuiCommand::Pause | TuiCommand::Stop => group.pause().await,
+                        TuiCommand::Next | TuiCommand::Previous => {
+                            let idx = if cmd == TuiCommand::Next {
+                                app_state.current_media_idx + 1
+                            } else {
+                                app_state.current_media_idx.saturating_sub(1)
+                            };
+                            if idx < playlist.len() && idx != app_state.current_media_idx {
+                                item_started = false;
+                                if let Err(e) = load_group_item(&group, &server, &playlist, idx, 0.0, &torrent_manager, &tui, &mut app_state).await {
+                                    log::error!("Failed to load media: {}", e);
+                                }
+                            }
+                            Ok(())
+                        }
+                        TuiCommand::SeekForward(_) | TuiCommand::SeekBackward(_) => {
+                            let new_time = match cmd {
+                                TuiCommand::SeekForward(s) => position + s as f64,
+                                TuiCommand::SeekBackward(s) => (position - s as f64).max(0.0),
+                                _ => unreachable!(),
+                            };
+                            match receiver_seek_time(&app_state, new_time) {
+                                Some(target) => group.seek(target).await,
+                                None => {
+                                    // Out of the transcode's reach; restart it at the new time everywhere.
+                                    let idx = app_state.current_media_idx;
+                                    if let Err(e) = load_group_item(&group, &server, &playlist, idx, new_time, &torrent_manager, &tui, &mut app_state).await {
+                                        log::error!("Seek reload failed: {}", e);
+                                    }
+                                    Ok(())
+                                }
+                            }
+                        }
+                        TuiCommand::VolumeUp | TuiCommand::VolumeDown => {
+                            let step = if cmd == TuiCommand::VolumeUp { 0.05 } else { -0.05 };
+                            let new_vol = (app_state.volume_level.unwrap_or(0.0) + step).clamp(0.0, 1.0);
+                            app_state.volume_level = Some(new_vol);
+                            group.set_volume(new_vol).await
+                        }
+                        TuiCommand::ToggleMute => {
+                            app_state.is_muted = !app_state.is_muted;
+                            group.set_mute(app_state.is_muted).await
+                        }
+                        // Members reconnect by themselves.
+                        TuiCommand::Reconnect => Ok(()),
+                    };
+                    if let Err(e) = result {
+                        log::warn!("Group command failed: {}", e);
+                    }
+                    draw_group(&tui, &group, &app_state);
+                }
+                Ok(()) = states.changed() => {
+                    let leader = states.borrow_and_update().first().cloned();
+                    let Some(leader) = leader else { continue };
+                    if leader.is_playing() {
+                        item_started = true;
+                    }
+                    if let Some(volume) = leader.volume_level {
+                        app_state.volume_level = Some(volume);
+                        app_state.is_muted = leader.is_muted;
+                    }
+                    if item_started && leader.player_state == "IDLE" && leader.idle_reason.as_deref() == Some("FINISHED") {
+                        let next_idx = app_state.current_media_idx + 1;
+                        if next_idx < playlist.len() || self.config.loop_playlist {
+                            let idx = if next_idx < playlist.len() { next_idx } else { 0 };
+                            log::info!("Group finished item {}. Loading {}...", app_state.current_media_idx, idx);
+                            item_started = false;
+                            if let Err(e) = load_group_item(&group, &server, &playlist, idx, 0.0, &torrent_manager, &tui, &mut app_state).await {
+                                log::error!("Failed to load next media: {}", e);
+                            }
+                        }
+                    }
+                    draw_group(&tui, &group, &app_state);
+                }
+                _ = resync_interval.tick() => {
+                    match group.resync(DEFAULT_MAX_DRIFT).await {
+                        Ok(0) => {}
+                        Ok(n) => log::info!("Resynced {} group members.", n),
+                        Err(e) => log::warn!("Resync failed: {}", e),
+                    }
+                }
+                _ = animation_interval.tick() => {
+                    app_state.animation_frame = app_state.animation_frame.wrapping_add(1);
+                    if let Some(handle) = &app_state.torrent_handle {
+                        let stats = handle.stats();
+                        if stats.total_bytes > 0 {
+                            app_state.torrent_progress = Some((stats.progress_bytes as f32 / stats.total_bytes as f32) * 100.0);
+                        }
+                    }
+                    draw_group(&tui, &group, &app_state);
+                }
+            }
+        }
+
+        tui.stop();
+        drop(group);
+        for (_, client) in clients {
+            let _ = client.close().await;
+        }
         Ok(())
     }
 }
@@ -731,10 +1080,7 @@ pub async fn scan_devices() -> Result<(), Box<dyn Error>> {
         tokio::select! {
             Some(device) = rx.recv() => {
                 println!("Found Device:");
-                println!("  Name: {}", device.friendly_name);
-                println!("  Model: {}", device.model_name);
-                println!("  IP: {}:{}", device.ip, device.port);
-                println!("  UUID: {}", device.uuid);
+                print_device(&device);
                 println!("--------------------------------");
             }
             _ = &mut timeout => {
@@ -746,6 +1092,66 @@ pub async fn scan_devices() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Keeps scanning until Ctrl+C, printing devices as they come, change and go.
+pub async fn watch_devices() -> Result<(), Box<dyn Error>> {
+    println!("Watching for Google Cast devices (Ctrl+C to exit)...");
+    let mut browser = DeviceBrowser::start()?;
+    let mut events = browser.events();
+    for device in browser.devices() {
+        println!("+ Added:");
+        print_device(&device);
+    }
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(DiscoveryEvent::Added(device)) => {
+                    println!("+ Added:");
+                    print_device(&device);
+                }
+                Ok(DiscoveryEvent::Updated { previous, device }) => {
+                    println!("~ Updated (was {} at {}:{}):", previous.friendly_name, previous.ip, previous.port);
+                    print_device(&device);
+                }
+                Ok(DiscoveryEvent::Removed(device)) => {
+                    println!("- Removed: {} ({})", device.friendly_name, device.uuid);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(_) => break,
+            },
+            _ = &mut ctrl_c => break,
+        }
+    }
+    browser.stop();
+    println!("{} device(s) online.", browser.devices().len());
+    Ok(())
+}
+
+fn print_device(device: &CastDevice) {
+    println!("  Name: {}", device.friendly_name);
+    println!("  Model: {}", device.model_name);
+    println!("  IP: {}:{}", device.ip, device.port);
+    for other in device.addresses.iter().filter(|ip| **ip != device.ip) {
+        println!("      {}", other);
+    }
+    println!("  UUID: {}", device.uuid);
+    let kind = if device.is_group() {
+        "Cast group"
+    } else if device.capabilities.has_video() {
+        "Video"
+    } else if device.capabilities.has_audio() {
+        "Audio only"
+    } else {
+        "Unknown"
+    };
+    println!("  Type: {} ({})", kind, device.capabilities);
+    println!("  Status: {}", device.status_text.as_deref().unwrap_or("Idle"));
+    if let Some(version) = device.version {
+        println!("  Protocol: v{}", version);
+    }
+}
+
 pub async fn connect_only(ip: &str) -> Result<(), Box<dyn Error>> {
     println!("Connecting to {}...", ip);
     let client = CastClient::connect(ip, 8009).await?;
@@ -762,9 +1168,17 @@ pub async fn launch_app(ip: &str, app_id: &str) -> Result<(), Box<dyn Error>> {
     let client = CastClient::connect(ip, 8009).await?;
     println!("Connecting to receiver...");
     client.connect_receiver().await?;
+    let mut app = Application::new(&client, app_id);
+    if !app.is_available().await? {
+        return Err(format!("App {} is not available on this device", app_id).into());
+    }
     println!("Launching app {}...", app_id);
-    client.launch_app(app_id).await?;
-    println!("App launched. Listening for status...");
+    let session = app.launch().await?;
+    println!(
+        "{} is running (session {}, transport {})",
+        session.display_name, session.session_id, session.transport_id
+    );
+    println!("Listening for status...");
     let mut rx = client.events();
     while let Ok(event) = rx.recv().await {
         println!("[{}] {}", event.namespace, event.payload);
@@ -772,6 +1186,101 @@ pub async fn launch_app(ip: &str, app_id: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Joins the media session another sender started and optionally controls it.
+///
+/// `action` is empty (follow the status until Ctrl+C), `play`, `pause`, `stop`,
+/// `seek <SECONDS>`, `volume <0.0-1.0>` (the device volume) or `stream-volume <0.0-1.0>`.
+pub async fn attach(ip: &str, action: &[String]) -> Result<(), Box<dyn Error>> {
+    println!("Connecting to {}...", ip);
+    let client = CastClient::connect(ip, 8009).await?;
+    let result = run_attached(&client, action).await;
+    let _ = client.close().await;
+    result
+}
+
+/// Runs an `attach` action on the connected device.
+async fn run_attached(client: &CastClient, action: &[String]) -> Result<(), Box<dyn Error>> {
+    client.connect_receiver().await?;
+    let mut events = client.device_events();
+    let receiver = ReceiverController::new(client);
+    let argument = |name: &str| -> Result<f32, Box<dyn Error>> {
+        action
+            .get(1)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| format!("Usage: castru attach <IP> {} <NUMBER>", name).into())
+    };
+    match action.first().map(String::as_str) {
+        // The device volume needs no media session.
+        Some("volume") => return Ok(receiver.set_volume(argument("volume")?).await?),
+        None | Some("play" | "pause" | "stop" | "seek" | "stream-volume") => {}
+        Some(other) => return Err(format!("Unknown attach action: {}", other).into()),
+    }
+
+    let session = receiver.join_media_session().await?;
+    println!(
+        "Joined {} ({})",
+        session.application.display_name, session.application.status_text
+    );
+    let Some(status) = &session.status else {
+        println!("Nothing is playing.");
+        return Ok(());
+    };
+    println!("{}", describe_media_status(status));
+
+    match action.first().map(String::as_str) {
+        None => {
+            println!("Following playback (Ctrl+C to exit)...");
+            let ctrl_c = tokio::signal::ctrl_c();
+            tokio::pin!(ctrl_c);
+            loop {
+                tokio::select! {
+                    event = events.recv() => match event {
+                        Ok(DeviceEvent::MediaStatus { source_id, status, .. }) if source_id == session.media().transport_id() => {
+                            for s in &status {
+                                println!("{}", describe_media_status(s));
+                            }
+                        }
+                        Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(_) => break,
+                    },
+                    _ = &mut ctrl_c => break,
+                }
+            }
+        }
+        Some("play") => session.play().await?,
+        Some("pause") => session.pause().await?,
+        Some("stop") => session.stop().await?,
+        Some("seek") => session.seek(argument("seek")?).await?,
+        Some("stream-volume") => session.set_stream_volume(argument("stream-volume")?).await?,
+        Some(_) => unreachable!("other actions are handled before joining"),
+    }
+    Ok(())
+}
+
+fn describe_media_status(status: &crate::protocol::media::MediaStatus) -> String {
+    let title = status
+        .media
+        .as_ref()
+        .map(|m| {
+            m.metadata
+                .as_ref()
+                .and_then(|md| md.title())
+                .unwrap_or(&m.content_id)
+                .to_string()
+        })
+        .unwrap_or_default();
+    let duration = status
+        .duration()
+        .map(|d| format!("/{:.0}s", d))
+        .unwrap_or_default();
+    format!(
+        "[{}] {:.0}s{} {}",
+        status.player_state, status.current_time, duration, title
+    )
+    .trim_end()
+    .to_string()
+}
+
 fn setup_logging(path: &str) -> Result<(), Box<dyn Error>> {
     let file = std::fs::File::create(path)?;
     simplelog::WriteLogger::init(
@@ -782,6 +1291,24 @@ fn setup_logging(path: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Client settings for `device`, pinning its certificate to its UUID when `pins` is given.
+///
+/// Devices given by `--ip` have no known UUID, so nothing is pinned for them.
+fn client_config(device: &CastDevice, pins: Option<&Arc<FingerprintStore>>) -> CastClientConfig {
+    let mut config = CastClientConfig::default();
+    match pins {
+        Some(store) if device.uuid != UNKNOWN_UUID => {
+            config.certificate_policy = CertificatePolicy::TrustOnFirstUse {
+                device_id: device.uuid.clone(),
+                store: store.clone(),
+            };
+        }
+        Some(_) => log::warn!("Not pinning the certificate of {}: its UUID is unknown", device.ip),
+        None => {}
+    }
+    config
+}
+
 fn get_local_ip() -> Option<IpAddr> {
     use std::net::UdpSocket;
     let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
@@ -789,17 +1316,109 @@ fn get_local_ip() -> Option<IpAddr> {
     socket.local_addr().ok().map(|addr| addr.ip())
 }
 
+/// Opens the on-disk cache of finished transcodes, sized by `--cache-size`.
+fn transcode_cache(config: &Config) -> Arc<TranscodeCache> {
+    let mut cache_config = CacheConfig::default();
+    if let Some(mb) = config.transcode_cache_mb {
+        cache_config.max_bytes = mb * 1024 * 1024;
+    }
+    Arc::new(TranscodeCache::new(cache_config))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn load_media(
     app: &DefaultMediaReceiver,
     server: &StreamServer,
     source: &MediaSource,
-    server_base: &str,
     start_time: f64,
     torrent_manager: &TorrentManager,
     tui: &TuiController,
     app_state: &mut AppState,
     probe_tx: Option<mpsc::Sender<InternalEvent>>,
-) -> Result<(bool, MediaProbeResult, f64), Box<dyn Error>> {
+) -> Result<MediaProbeResult, Box<dyn Error>> {
+    let prepared = prepare_media(
+        server,
+        source,
+        start_time,
+        torrent_manager,
+        tui,
+        app_state,
+        probe_tx,
+    )
+    .await?;
+    app.load(
+        prepared.media_info,
+        true,
+        prepared.play_position,
+        prepared.active_tracks,
+    )
+    .await?;
+    Ok(prepared.probe)
+}
+
+/// Media ready to be loaded on a receiver, with what we learned while preparing it.
+struct PreparedMedia {
+    media_info: MediaInformation,
+    play_position: f32,
+    active_tracks: Option<Vec<i32>>,
+    probe: MediaProbeResult,
+}
+
+/// A transcode of a local file, as served for the current item.
+#[derive(Clone)]
+struct Transcode {
+    input: PathBuf,
+    /// Identifies it to [`StreamServer::remove`].
+    token: String,
+    url: String,
+    output: TranscodeStream,
+}
+
+#[derive(Clone)]
+enum TranscodeStream {
+    Hls(Arc<HlsOutput>),
+    Mp4(Arc<TranscodeOutput>),
+}
+
+impl Transcode {
+    /// Where the stream starts in the input.
+    fn start_time(&self) -> f64 {
+        match &self.output {
+            TranscodeStream::Hls(output) => output.start_time(),
+            TranscodeStream::Mp4(output) => output.start_time(),
+        }
+    }
+
+    /// Whether the receiver can play the input from `time` in this stream: HLS anywhere it
+    /// has produced so far, a single MP4 stream only from its start.
+    fn covers(&self, time: f64) -> bool {
+        let time = time - self.start_time();
+        match &self.output {
+            TranscodeStream::Hls(output) => (0.0..=output.produced_duration()).contains(&time),
+            TranscodeStream::Mp4(_) => time == 0.0,
+        }
+    }
+
+    fn content_type(&self) -> String {
+        match &self.output {
+            TranscodeStream::Hls(_) => get_mime_type(Path::new(HLS_PLAYLIST)).to_string(),
+            TranscodeStream::Mp4(_) => "video/mp4".to_string(),
+        }
+    }
+}
+
+/// Serves `source` (transcoding or waiting for a torrent as needed) and describes it for
+/// the receiver. What the previous item served stops being served.
+#[allow(clippy::too_many_arguments)]
+async fn prepare_media(
+    server: &StreamServer,
+    source: &MediaSource,
+    start_time: f64,
+    torrent_manager: &TorrentManager,
+    tui: &TuiController,
+    app_state: &mut AppState,
+    probe_tx: Option<mpsc::Sender<InternalEvent>>,
+) -> Result<PreparedMedia, Box<dyn Error>> {
     // ... Copy implementation from main.rs ...
     // Note: I already copied it above but didn't paste it all because of size.
     // I need to paste the FULL content of load_media and wait_for_torrent_download.
@@ -807,9 +1426,10 @@ async fn load_media(
     // Wait, I'm writing the file NOW.
     
     // START OF COPIED FUNCTIONS
-    let mut applied_seek_offset = 0.0;
+    let mut served = Vec::new();
     app_state.torrent_handle = None; 
-    let (url, content_type, is_transcoding, probe) = match source {
+    let mut whole_transcode = None;
+    let (url, content_type, transcode, probe, local_path) = match source {
         MediaSource::FilePath(path_str) => {
             let path = Path::new(path_str);
             let probe = match probe_media(path).await {
@@ -822,49 +1442,80 @@ async fn load_media(
                         duration: None,
                         video_profile: None,
                         pix_fmt: None,
+                        tags: Default::default(),
                     }
                 }
             };
 
-            if needs_transcoding(&probe) {
-                applied_seek_offset = start_time;
-                let config = TranscodeConfig {
-                    input_path: path.to_path_buf(),
-                    start_time,
-                    target_video_codec: "libx264".to_string(),
-                    target_audio_codec: "aac".to_string(),
+            let config = TranscodeConfig {
+                input_path: path.to_path_buf(),
+                start_time,
+                target_video_codec: "libx264".to_string(),
+                target_audio_codec: "aac".to_string(),
+            };
+            let cache = app_state.transcode_cache.clone();
+            let cached = if needs_transcoding(&probe) {
+                cache.lookup(&config)
+            } else {
+                None
+            };
+
+            if let Some(cached) = cached {
+                log::info!("Serving cached transcode {:?}", cached);
+                (
+                    serve(server, &mut served, Resource::file(&cached)),
+                    get_mime_type(&cached).to_string(),
+                    None,
+                    probe,
+                    Some(path.to_path_buf()),
+                )
+            } else if needs_transcoding(&probe) {
+                let whole = app_state
+                    .whole_transcode
+                    .clone()
+                    .filter(|whole| whole.input == path);
+                let transcode = match whole.as_ref().filter(|w| w.covers(start_time)) {
+                    Some(whole) => whole.clone(),
+                    None => start_transcode(server, &config, app_state.use_hls, cache).await?,
                 };
-                let pipeline = spawn_ffmpeg(&config)?;
-                server.set_transcode_output(pipeline).await;
+                served.push(transcode.token.clone());
+                if transcode.start_time() == 0.0 {
+                    whole_transcode = Some(transcode.clone());
+                } else if let Some(whole) = whole {
+                    // Keep transcoding the whole file while the receiver plays from later on.
+                    served.push(whole.token.clone());
+                    whole_transcode = Some(whole);
+                }
                 (
-                    format!("{}/?t={}", server_base, start_time),
-                    "video/mp4".to_string(),
-                    true,
+                    transcode.url.clone(),
+                    transcode.content_type(),
+                    Some(transcode),
                     probe,
+                    Some(path.to_path_buf()),
                 )
             } else {
-                server
-                    .set_source(StreamSource::Static(path.to_path_buf()))
-                    .await;
                 (
-                    server_base.to_string(),
+                    serve(server, &mut served, Resource::file(path)),
                     get_mime_type(path).to_string(),
-                    false,
+                    None,
                     probe,
+                    Some(path.to_path_buf()),
                 )
             }
         }
         MediaSource::Url(u) => (
             u.clone(),
             "video/mp4".to_string(),
-            false,
+            None,
             MediaProbeResult {
                 video_codec: None,
                 audio_codec: None,
                 duration: None,
                 video_profile: None,
                 pix_fmt: None,
+                tags: Default::default(),
             },
+            None,
         ),
         MediaSource::Magnet(uri) => {
             let init_state = TuiState {
@@ -879,108 +1530,350 @@ async fn load_media(
                 device_name: app_state.device_name.clone(),
                 animation_frame: app_state.animation_frame,
                 torrent_progress: None,
+                members: Vec::new(),
             };
             let _ = tui.draw(&init_state);
 
             let info = torrent_manager.start_magnet(uri).await?;
             app_state.torrent_handle = Some(wait_for_torrent_download(&info, tui, app_state, probe_tx.clone()).await?);
             
-            server
-                .set_source(StreamSource::Growing {
+            let url = serve(
+                server,
+                &mut served,
+                Resource::media(StreamSource::Growing {
                     path: info.path.clone(),
                     total_size: info.total_size,
                     handle: info.handle.clone(),
                     file_offset: info.file_offset,
                     piece_length: info.piece_length,
-                })
-                .await;
+                }),
+            );
 
             let mime = get_mime_type(&info.path).to_string();
             (
-                server_base.to_string(),
+                url,
                 mime,
-                false,
+                None,
                 MediaProbeResult {
                     video_codec: None,
                     audio_codec: None,
                     duration: None,
                     video_profile: None,
                     pix_fmt: None,
+                    tags: Default::default(),
                 },
+                Some(info.path.clone()),
             )
         }
         MediaSource::TorrentFile(path_str) => {
             let info = torrent_manager.start_torrent_file(path_str).await?;
             app_state.torrent_handle = Some(wait_for_torrent_download(&info, tui, app_state, probe_tx.clone()).await?);
 
-            server
-                .set_source(StreamSource::Growing {
+            let url = serve(
+                server,
+                &mut served,
+                Resource::media(StreamSource::Growing {
                     path: info.path.clone(),
                     total_size: info.total_size,
                     handle: info.handle.clone(),
                     file_offset: info.file_offset,
                     piece_length: info.piece_length,
-                })
-                .await;
+                }),
+            );
 
             let mime = get_mime_type(&info.path).to_string();
             (
-                server_base.to_string(),
+                url,
                 mime,
-                false,
+                None,
                 MediaProbeResult {
                     video_codec: None,
                     audio_codec: None,
                     duration: None,
                     video_profile: None,
                     pix_fmt: None,
+                    tags: Default::default(),
                 },
+                Some(info.path.clone()),
             )
         }
     };
 
-    let tracks = if let Some(sub_path_str) = &app_state.subtitles {
-        let sub_path = Path::new(sub_path_str);
-        if sub_path.exists() {
-             server.set_subtitle(sub_path.to_path_buf()).await;
-             Some(vec![MediaTrack {
-                 track_id: 1,
-                 track_type: "TEXT".to_string(),
-                 track_content_id: Some(format!("{}/subtitle", server_base)),
-                 track_content_type: Some("text/vtt".to_string()),
-                 name: Some("Subtitle".to_string()),
-                 language: Some("en".to_string()),
-                 subtype: Some("SUBTITLES".to_string()),
-             }])
-        } else {
-            None
-        }
+    let tracks = subtitle_tracks(server, app_state.subtitles.as_deref(), &mut served);
+
+    let mut media_info = MediaInformation::new(url, content_type);
+    if let Some(path) = &local_path {
+        media_info = media_info.with_metadata(local_metadata(server, path, &probe, &mut served));
+    }
+    replace_served(server, app_state, served);
+    if let Some(tracks) = tracks {
+        media_info = media_info.with_tracks(tracks);
+    }
+    app_state.transcode = transcode;
+    app_state.whole_transcode = whole_transcode;
+
+    let play_position = (start_time - app_state.stream_offset()) as f32;
+
+    let active_tracks = if media_info.tracks.is_some() {
+        Some(vec![1])
     } else {
         None
     };
 
-    let media_info = MediaInformation {
-        content_id: url,
-        stream_type: "BUFFERED".to_string(),
-        content_type,
-        metadata: None,
-        tracks,
-    };
+    Ok(PreparedMedia {
+        media_info,
+        play_position,
+        active_tracks,
+        probe,
+    })
+}
 
-    let play_position = if is_transcoding {
-        0.0
+/// Starts transcoding `config` and serves the output, caching it once done if it covers
+/// the whole input.
+async fn start_transcode(
+    server: &StreamServer,
+    config: &TranscodeConfig,
+    use_hls: bool,
+    cache: Arc<TranscodeCache>,
+) -> Result<Transcode, Box<dyn Error>> {
+    // Only a transcode of the whole file is worth caching.
+    let key = if cache.is_enabled() && config.start_time == 0.0 {
+        TranscodeCache::key(config)
     } else {
-        start_time as f32
+        None
+    };
+    let (output, resource) = if use_hls {
+        let output = HlsOutput::new(spawn_hls(config)?);
+        let resource = server.add(Resource::Hls(output.clone()));
+        if let Err(e) = output.wait_ready(HLS_READY_TIMEOUT).await {
+            server.remove(&resource.token);
+            return Err(e.into());
+        }
+        if let Some(key) = key {
+            let output = output.clone();
+            tokio::spawn(async move {
+                if output.wait_complete().await {
+                    if let Err(e) = cache.store(&key, &output.playlist_path()).await {
+                        log::warn!("Failed to cache transcode: {}", e);
+                    }
+                }
+            });
+        }
+        (TranscodeStream::Hls(output), resource)
+    } else {
+        // Replaying an item still being transcoded picks up where it has got to.
+        let output = match key.as_deref().and_then(|key| server.find_transcode(key)) {
+            Some(output) => output,
+            None => {
+                let output = TranscodeOutput::start(
+                    spawn_ffmpeg(config)?,
+                    cache.partial_path(),
+                    key.clone(),
+                )?;
+                if let Some(key) = key {
+                    let output = output.clone();
+                    tokio::spawn(async move {
+                        if output.wait_complete().await {
+                            if let Err(e) = cache.store(&key, output.path()).await {
+                                log::warn!("Failed to cache transcode: {}", e);
+                            }
+                        }
+                    });
+                }
+                output
+            }
+        };
+        let resource = server.add(Resource::Transcode(output.clone()));
+        (TranscodeStream::Mp4(output), resource)
     };
+    Ok(Transcode {
+        input: config.input_path.clone(),
+        token: resource.token,
+        url: resource.url,
+        output,
+    })
+}
 
-    let active_tracks = if media_info.tracks.is_some() {
-        Some(vec![1])
+/// The receiver time to seek to for `new_time` in the source, or `None` if the stream has
+/// to be reloaded there.
+///
+/// A transcoded stream can only be sought where [`Transcode::covers`] it; reloading serves
+/// the whole file's transcode instead if that has got far enough.
+fn receiver_seek_time(app_state: &AppState, new_time: f64) -> Option<f32> {
+    match &app_state.transcode {
+        None => Some(new_time as f32),
+        Some(transcode) => transcode
+            .covers(new_time)
+            .then(|| (new_time - transcode.start_time()) as f32),
+    }
+}
+
+/// Serves `resource` for the item being prepared, remembering it in `served`.
+fn serve(server: &StreamServer, served: &mut Vec<String>, resource: Resource) -> String {
+    let resource = server.add(resource);
+    served.push(resource.token);
+    resource.url
+}
+
+/// Makes `served` the current item's resources and stops serving the previous item's.
+fn replace_served(server: &StreamServer, app_state: &mut AppState, served: Vec<String>) {
+    for token in std::mem::replace(&mut app_state.served, served) {
+        server.remove(&token);
+    }
+}
+
+/// Serves the sidecar subtitle file, if any, and returns the track describing it.
+fn subtitle_tracks(
+    server: &StreamServer,
+    subtitles: Option<&str>,
+    served: &mut Vec<String>,
+) -> Option<Vec<MediaTrack>> {
+    let sub_path = Path::new(subtitles?);
+    if !sub_path.exists() {
+        return None;
+    }
+    let url = serve(server, served, Resource::Subtitle(sub_path.to_path_buf()));
+    Some(vec![MediaTrack {
+        track_id: 1,
+        track_type: "TEXT".to_string(),
+        track_content_id: Some(url),
+        track_content_type: Some("text/vtt".to_string()),
+        name: Some("Subtitle".to_string()),
+        language: Some("en".to_string()),
+        subtype: Some("SUBTITLES".to_string()),
+    }])
+}
+
+/// Describes a local file from its tags and name, serving any cover art found next to it.
+fn local_metadata(
+    server: &StreamServer,
+    path: &Path,
+    probe: &MediaProbeResult,
+    served: &mut Vec<String>,
+) -> MediaMetadata {
+    let metadata = guess_metadata(path, &probe.tags);
+    match find_artwork(path) {
+        Some(artwork) => {
+            let url = serve(server, served, Resource::file(artwork));
+            metadata.with_images(vec![Image::new(url)])
+        }
+        None => metadata,
+    }
+}
+
+/// Loads every URL of the playlist into the receiver's queue, starting at `start_index`.
+#[allow(clippy::too_many_arguments)]
+async fn load_queue(
+    app: &DefaultMediaReceiver,
+    server: &StreamServer,
+    playlist: &VecDeque<MediaSource>,
+    start_index: usize,
+    start_time: f64,
+    repeat: bool,
+    app_state: &mut AppState,
+) -> Result<(), Box<dyn Error>> {
+    let mut served = Vec::new();
+    let tracks = subtitle_tracks(server, app_state.subtitles.as_deref(), &mut served);
+    replace_served(server, app_state, served);
+    let items = playlist
+        .iter()
+        .filter_map(|source| match source {
+            MediaSource::Url(u) => Some(u),
+            _ => None,
+        })
+        .map(|url| {
+            let mut media = MediaInformation::new(url.clone(), "video/mp4");
+            media.tracks = tracks.clone();
+            QueueItem {
+                active_track_ids: tracks.as_ref().map(|_| vec![1]),
+                ..QueueItem::new(media)
+            }
+        })
+        .collect();
+    let repeat_mode = if repeat {
+        RepeatMode::RepeatAll
     } else {
-        None
+        RepeatMode::RepeatOff
     };
+    let queue = app
+        .queue_load(items, start_index, repeat_mode, start_time as f32)
+        .await?;
+
+    app_state.media_session_id = Some(queue.media_session_id);
+    app_state.queue_item_ids = queue.item_ids;
+
+    app_state.current_media_idx = start_index;
+    app_state.source = playlist.get(start_index).cloned();
+    app_state.torrent_handle = None;
+    app_state.transcode = None;
+    app_state.whole_transcode = None;
+    app_state.current_time = start_time;
+    app_state.last_known_time = start_time;
+    app_state.last_update_instant = std::time::Instant::now();
+    Ok(())
+}
 
-    app.load(media_info, true, play_position, active_tracks).await?;
-    Ok((is_transcoding, probe, applied_seek_offset))
+/// Loads playlist item `idx` on every group member, starting at `start_time`.
+#[allow(clippy::too_many_arguments)]
+async fn load_group_item(
+    group: &CastGroup,
+    server: &StreamServer,
+    playlist: &VecDeque<MediaSource>,
+    idx: usize,
+    start_time: f64,
+    torrent_manager: &TorrentManager,
+    tui: &TuiController,
+    app_state: &mut AppState,
+) -> Result<(), Box<dyn Error>> {
+    let source = playlist.get(idx).ok_or("Playlist index out of range")?;
+    app_state.current_media_idx = idx;
+    app_state.source = Some(source.clone());
+    let prepared = prepare_media(
+        server,
+        source,
+        start_time,
+        torrent_manager,
+        tui,
+        app_state,
+        None,
+    )
+    .await?;
+    app_state.total_duration = prepared.probe.duration;
+    app_state.video_codec = prepared.probe.video_codec;
+    app_state.audio_codec = prepared.probe.audio_codec;
+    group
+        .load(
+            prepared.media_info,
+            true,
+            prepared.play_position,
+            prepared.active_tracks,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Draws the leader's playback with a line per group member.
+fn draw_group(tui: &TuiController, group: &CastGroup, app_state: &AppState) {
+    let members = group.states();
+    let leader = members.first();
+    let tui_state = TuiState {
+        status: leader.map(|l| l.player_state.clone()).unwrap_or_default(),
+        current_time: leader.map(|l| l.position()).unwrap_or(0.0) + app_state.stream_offset() as f32,
+        total_duration: app_state
+            .total_duration
+            .map(|d| d as f32)
+            .or(leader.and_then(|l| l.duration)),
+        volume_level: app_state.volume_level,
+        is_muted: app_state.is_muted,
+        media_title: None,
+        video_codec: app_state.video_codec.clone(),
+        audio_codec: app_state.audio_codec.clone(),
+        device_name: app_state.device_name.clone(),
+        animation_frame: app_state.animation_frame,
+        torrent_progress: app_state.torrent_progress,
+        members,
+    };
+    let _ = tui.draw(&tui_state);
 }
 
 async fn wait_for_torrent_download(
@@ -1053,6 +1946,7 @@ async fn wait_for_torrent_download(
             device_name: app_state.device_name.clone(),
             animation_frame: app_state.animation_frame,
             torrent_progress: Some(pct),
+            members: Vec::new(),
         };
         let _ = tui.draw(&tui_state);
 
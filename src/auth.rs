@@ -0,0 +1,313 @@
+//! Cast device authentication.
+//!
+//! The sender challenges the device on the deviceauth namespace. The device answers with
+//! its device certificate, the intermediates it chains through, and a signature over the
+//! sender nonce followed by the TLS certificate it presented. A receiver that merely
+//! copied another device's TLS certificate cannot produce that signature.
+
+use crate::error::CastError;
+use crate::proto::{AuthResponse, DeviceAuthMessage, HashAlgorithm, SignatureAlgorithm};
+use prost::Message;
+use std::time::SystemTime;
+
+/// Outcome of a successful device authentication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceAuthResult {
+    pub credentials: CredentialTrust,
+    /// Whether the signature covers our nonce. Older devices ignore the nonce and sign
+    /// their TLS certificate alone, which proves less: such a signature can be replayed.
+    pub nonce_signed: bool,
+}
+
+impl DeviceAuthResult {
+    /// Whether every check was made: a trusted chain and a signature over our nonce.
+    pub fn is_complete(&self) -> bool {
+        self.credentials == CredentialTrust::Trusted && self.nonce_signed
+    }
+}
+
+/// How far the device's credentials were checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialTrust {
+    /// The device owns its TLS certificate and its credentials chain to a trusted root.
+    Trusted,
+    /// The device owns its TLS certificate; no trusted roots were given to check its
+    /// credentials against.
+    SignatureOnly,
+}
+
+/// Algorithms a device may sign the challenge with when it asks for PKCS#1 v1.5 over
+/// SHA-256; devices with EC keys sign with ECDSA instead.
+static PKCS1_OR_ECDSA_SHA256: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P384_SHA256,
+];
+
+static PSS_SHA256: &[&webpki::SignatureAlgorithm] = &[&webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY];
+
+/// Algorithms accepted for the signatures inside the device's certificate chain.
+static CHAIN_ALGORITHMS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+];
+
+fn auth_failure(reason: impl Into<String>) -> CastError {
+    CastError::Auth(reason.into())
+}
+
+/// Decodes a binary reply received on the deviceauth namespace.
+pub fn parse_reply(payload: &[u8]) -> Result<AuthResponse, CastError> {
+    let reply = DeviceAuthMessage::decode(payload)?;
+    if let Some(error) = reply.error {
+        return Err(auth_failure(format!(
+            "device reported {}",
+            error.error_type().as_str_name()
+        )));
+    }
+    reply
+        .response
+        .ok_or_else(|| auth_failure("reply carries neither a response nor an error"))
+}
+
+/// Checks a device's `response` to a challenge carrying `sender_nonce`.
+///
+/// `peer_certificate` is the DER certificate the device presented in the TLS handshake.
+/// `trust_anchors` are the DER root certificates the device certificate must chain to;
+/// when empty, only the signature is checked.
+pub fn verify_response(
+    response: &AuthResponse,
+    peer_certificate: &[u8],
+    sender_nonce: &[u8],
+    trust_anchors: &[Vec<u8>],
+    now: SystemTime,
+) -> Result<DeviceAuthResult, CastError> {
+    if response
+        .sender_nonce
+        .as_deref()
+        .is_some_and(|echoed| echoed != sender_nonce)
+    {
+        return Err(auth_failure("device echoed a different sender nonce"));
+    }
+
+    let device_cert = webpki::EndEntityCert::try_from(response.client_auth_certificate.as_slice())
+        .map_err(|e| auth_failure(format!("invalid device certificate: {:?}", e)))?;
+
+    let algorithms = match (response.signature_algorithm(), response.hash_algorithm()) {
+        (
+            SignatureAlgorithm::RsassaPkcs1v15 | SignatureAlgorithm::Unspecified,
+            HashAlgorithm::Sha256,
+        ) => PKCS1_OR_ECDSA_SHA256,
+        (SignatureAlgorithm::RsassaPss, HashAlgorithm::Sha256) => PSS_SHA256,
+        (signature, hash) => {
+            return Err(auth_failure(format!(
+                "unsupported signature scheme {} with {}",
+                signature.as_str_name(),
+                hash.as_str_name()
+            )))
+        }
+    };
+
+    // Older devices ignore the nonce and sign the TLS certificate alone.
+    let nonce_signed = response.sender_nonce.is_some();
+    let mut signed = response.sender_nonce.clone().unwrap_or_default();
+    signed.extend_from_slice(peer_certificate);
+    let signature_matches = algorithms.iter().any(|algorithm| {
+        device_cert
+            .verify_signature(algorithm, &signed, &response.signature)
+            .is_ok()
+    });
+    if !signature_matches {
+        return Err(auth_failure("signature does not match the TLS peer certificate"));
+    }
+
+    if trust_anchors.is_empty() {
+        return Ok(DeviceAuthResult {
+            credentials: CredentialTrust::SignatureOnly,
+            nonce_signed,
+        });
+    }
+
+    let anchors = trust_anchors
+        .iter()
+        .map(|der| webpki::TrustAnchor::try_from_cert_der(der))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| auth_failure(format!("invalid trust anchor: {:?}", e)))?;
+    let intermediates: Vec<&[u8]> = response.client_ca.iter().map(Vec::as_slice).collect();
+    let time = webpki::Time::try_from(now).map_err(|_| auth_failure("system time is before 1970"))?;
+
+    device_cert
+        .verify_for_usage(
+            CHAIN_ALGORITHMS,
+            &anchors,
+            &intermediates,
+            time,
+            webpki::KeyUsage::client_auth(),
+            &[],
+        )
+        .map_err(|e| auth_failure(format!("device certificate is not trusted: {:?}", e)))?;
+
+    Ok(DeviceAuthResult {
+        credentials: CredentialTrust::Trusted,
+        nonce_signed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{AuthError, auth_error};
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair, SigningKey,
+    };
+
+    struct Chain {
+        root: Vec<u8>,
+        intermediate: Vec<u8>,
+        device: Vec<u8>,
+        device_key: KeyPair,
+    }
+
+    fn ca_params(name: &str) -> CertificateParams {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        params
+    }
+
+    fn chain() -> Chain {
+        let root_key = KeyPair::generate().unwrap();
+        let root_params = ca_params("Test Cast Root CA");
+        let root = root_params.self_signed(&root_key).unwrap();
+        let root_issuer = Issuer::new(root_params, root_key);
+
+        let ica_key = KeyPair::generate().unwrap();
+        let ica_params = ca_params("Test Cast ICA");
+        let intermediate = ica_params.signed_by(&ica_key, &root_issuer).unwrap();
+        let ica_issuer = Issuer::new(ica_params, ica_key);
+
+        let device_key = KeyPair::generate().unwrap();
+        let mut device_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        device_params
+            .distinguished_name
+            .push(DnType::CommonName, "Living Room TV");
+        let device = device_params.signed_by(&device_key, &ica_issuer).unwrap();
+
+        Chain {
+            root: root.der().to_vec(),
+            intermediate: intermediate.der().to_vec(),
+            device: device.der().to_vec(),
+            device_key,
+        }
+    }
+
+    fn peer_certificate() -> Vec<u8> {
+        rcgen::generate_simple_self_signed(vec!["localhost".into()])
+            .unwrap()
+            .cert
+            .der()
+            .to_vec()
+    }
+
+    fn respond(chain: &Chain, nonce: &[u8], peer: &[u8]) -> AuthResponse {
+        let mut signed = nonce.to_vec();
+        signed.extend_from_slice(peer);
+        AuthResponse {
+            signature: chain.device_key.sign(&signed).unwrap(),
+            client_auth_certificate: chain.device.clone(),
+            client_ca: vec![chain.intermediate.clone()],
+            signature_algorithm: Some(SignatureAlgorithm::RsassaPkcs1v15 as i32),
+            sender_nonce: Some(nonce.to_vec()),
+            hash_algorithm: Some(HashAlgorithm::Sha256 as i32),
+            crl: None,
+        }
+    }
+
+    #[test]
+    fn test_verify_trusted_chain() {
+        let chain = chain();
+        let peer = peer_certificate();
+        let response = respond(&chain, b"nonce", &peer);
+
+        let anchors = vec![chain.root.clone()];
+        let result = verify_response(&response, &peer, b"nonce", &anchors, SystemTime::now());
+        let result = result.unwrap();
+        assert_eq!(result.credentials, CredentialTrust::Trusted);
+        assert!(result.is_complete());
+
+        let result = verify_response(&response, &peer, b"nonce", &[], SystemTime::now());
+        assert_eq!(result.unwrap().credentials, CredentialTrust::SignatureOnly);
+    }
+
+    #[test]
+    fn test_legacy_signature_is_reported() {
+        let chain = chain();
+        let peer = peer_certificate();
+        // The device leaves out the nonce and signs its TLS certificate alone.
+        let mut response = respond(&chain, b"", &peer);
+        response.sender_nonce = None;
+
+        let anchors = vec![chain.root.clone()];
+        let result = verify_response(&response, &peer, b"nonce", &anchors, SystemTime::now());
+        let result = result.unwrap();
+        assert_eq!(result.credentials, CredentialTrust::Trusted);
+        assert!(!result.nonce_signed);
+        assert!(!result.is_complete());
+    }
+
+    #[test]
+    fn test_reject_spoofed_peer_certificate() {
+        let chain = chain();
+        let genuine = peer_certificate();
+        let response = respond(&chain, b"nonce", &genuine);
+
+        // A spoofing receiver replays the response but presents its own TLS certificate.
+        let spoofed = peer_certificate();
+        let anchors = vec![chain.root.clone()];
+        let err = verify_response(&response, &spoofed, b"nonce", &anchors, SystemTime::now());
+        assert!(matches!(err, Err(CastError::Auth(reason)) if reason.contains("signature")));
+    }
+
+    #[test]
+    fn test_reject_untrusted_chain_and_wrong_nonce() {
+        let chain = chain();
+        let other = self::chain();
+        let peer = peer_certificate();
+        let response = respond(&chain, b"nonce", &peer);
+
+        let anchors = vec![other.root.clone()];
+        let err = verify_response(&response, &peer, b"nonce", &anchors, SystemTime::now());
+        assert!(matches!(err, Err(CastError::Auth(reason)) if reason.contains("not trusted")));
+
+        let err = verify_response(&response, &peer, b"other", &[], SystemTime::now());
+        assert!(matches!(err, Err(CastError::Auth(reason)) if reason.contains("nonce")));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let chain = chain();
+        let response = respond(&chain, b"nonce", &peer_certificate());
+        let reply = DeviceAuthMessage {
+            challenge: None,
+            response: Some(response.clone()),
+            error: None,
+        };
+        assert_eq!(parse_reply(&reply.encode_to_vec()).unwrap(), response);
+
+        let reply = DeviceAuthMessage {
+            challenge: None,
+            response: None,
+            error: Some(AuthError {
+                error_type: auth_error::ErrorType::SignatureAlgorithmUnavailable as i32,
+            }),
+        };
+        let err = parse_reply(&reply.encode_to_vec());
+        assert!(matches!(err, Err(CastError::Auth(reason)) if reason.contains("SIGNATURE_ALGORITHM_UNAVAILABLE")));
+    }
+}
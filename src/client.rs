@@ -1,19 +1,41 @@
-use crate::codec::CastCodec;
+use crate::auth::{self, DeviceAuthResult};
+use crate::codec::{CastCodec, MAX_FRAME_SIZE};
 use crate::error::CastError;
+use crate::events::DeviceEvent;
+use crate::proto::cast_message::PayloadType;
 use crate::proto::CastMessage;
 use crate::protocol::connection::{self, Connection};
+use crate::protocol::deviceauth;
 use crate::protocol::heartbeat::{self, Heartbeat};
 use crate::protocol::media::{self, MediaRequest};
-use crate::protocol::receiver::{self, ReceiverRequest, Volume};
-use crate::tls::create_tls_connector;
-use bytes::BytesMut;
+use crate::protocol::receiver::{self, ReceiverRequest, ReceiverResponse, ReceiverStatusData, Volume};
+use crate::protocol::Request;
+use crate::tls::{create_tls_connector_with_policy, fingerprint_mismatch, CertificatePolicy};
+use prost::Message;
 use rustls::ServerName;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use futures_util::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio_rustls::client::TlsStream;
+use tokio_util::codec::{FramedRead, FramedWrite};
 use tokio::time::{self, Duration};
 
+/// Default time to wait for a reply to a request sent with [`CastClient::request`].
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default interval between PINGs sent to the device.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default silence window after which the connection is considered dead.
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+
 /// Event received from the Cast device
 #[derive(Debug, Clone)]
 pub struct CastEvent {
@@ -21,16 +43,367 @@ pub struct CastEvent {
     pub payload: String,
 }
 
+/// Payload of a Cast message, which is either a string or raw bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Payload {
+    /// Extracts the payload of `msg` according to its `payload_type`.
+    pub fn from_message(msg: &CastMessage) -> Option<Self> {
+        if msg.payload_type == PayloadType::Binary as i32 {
+            msg.payload_binary.clone().map(Payload::Binary)
+        } else {
+            msg.payload_utf8.clone().map(Payload::Text)
+        }
+    }
+}
+
+/// A message received on a namespace subscribed with [`CastClient::subscribe_namespace`].
+#[derive(Debug, Clone)]
+pub struct NamespaceMessage {
+    pub source_id: String,
+    pub destination_id: String,
+    pub namespace: String,
+    pub payload: Payload,
+}
+
+/// The only part of a reply we need to look at to route it to its request.
+#[derive(Deserialize)]
+struct ReplyHeader {
+    #[serde(rename = "requestId", default)]
+    request_id: i32,
+}
+
+/// State of the TLS session between the client and the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The first connection attempt is in progress.
+    Connecting,
+    /// The TLS session is up.
+    Connected,
+    /// The session dropped (or an attempt failed) and the client is retrying.
+    Reconnecting,
+    /// The client gave up and will not reconnect.
+    Closed,
+}
+
+/// A transition of the connection to the device.
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    /// The first TLS session was established.
+    Connected,
+    /// The TLS session was re-established after a drop.
+    ///
+    /// CONNECT has already been re-sent to receiver-0 and every joined transport.
+    Reconnected,
+    /// The TLS session dropped.
+    Disconnected { reason: String },
+    /// A connection attempt failed.
+    ConnectFailed { attempt: u32, reason: String },
+    /// Waiting `delay` before connection attempt number `attempt`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// The device sent CLOSE for the virtual connection to `transport_id`.
+    ///
+    /// The transport is forgotten and will not be re-opened after a reconnect.
+    TransportClosed { transport_id: String },
+    /// The client was closed or the reconnect policy gave up; it is unusable from now on.
+    Closed,
+}
+
+/// How the client retries after the connection drops or an attempt fails.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between retries.
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each consecutive failure.
+    pub multiplier: u32,
+    /// Give up after this many consecutive failed attempts (`None` retries forever).
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Never reconnect; the client closes as soon as the connection is lost.
+    pub fn never() -> Self {
+        Self {
+            max_attempts: Some(0),
+            ..Self::default()
+        }
+    }
+
+    /// Delay to wait after `failures` consecutive failed attempts.
+    pub fn delay_for(&self, failures: u32) -> Duration {
+        let factor = self.multiplier.max(1).saturating_pow(failures);
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+
+    fn should_retry(&self, failures: u32) -> bool {
+        self.max_attempts.is_none_or(|max| failures < max)
+    }
+}
+
+/// Settings for [`CastClient::connect_with_config`].
+#[derive(Debug, Clone)]
+pub struct CastClientConfig {
+    /// Source ID put on every outgoing message; a unique `sender-…` ID is generated if `None`.
+    pub sender_id: Option<String>,
+    pub reconnect: ReconnectPolicy,
+    pub request_timeout: Duration,
+    /// How often to PING the device.
+    pub heartbeat_interval: Duration,
+    /// Drop and reconnect if nothing (not even a PONG) arrives for this long.
+    pub heartbeat_timeout: Duration,
+    /// How the device's TLS certificate is checked on every (re)connection.
+    pub certificate_policy: CertificatePolicy,
+    /// Largest frame accepted from or sent to the device.
+    pub max_frame_size: usize,
+}
+
+impl Default for CastClientConfig {
+    fn default() -> Self {
+        Self {
+            sender_id: None,
+            reconnect: ReconnectPolicy::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+            certificate_policy: CertificatePolicy::AcceptAny,
+            max_frame_size: MAX_FRAME_SIZE,
+        }
+    }
+}
+
 /// A client for communicating with a Google Cast device.
 ///
 /// Handles TLS connection, message framing, heartbeats, and event dispatching.
 #[derive(Clone)]
 pub struct CastClient {
     command_tx: mpsc::Sender<CastMessage>,
+    shared: Arc<Shared>,
+    task: Arc<TaskHandle>,
+    request_timeout: Duration,
+}
+
+/// Handle on the background I/O task, held only by the client clones.
+///
+/// When the last clone is dropped the shutdown sender goes with it, which stops the task.
+struct TaskHandle {
+    shutdown_tx: watch::Sender<bool>,
+    join: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TaskHandle {
+    fn new() -> (Self, watch::Receiver<bool>) {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        (
+            Self {
+                shutdown_tx,
+                join: Mutex::new(None),
+            },
+            shutdown_rx,
+        )
+    }
+}
+
+/// Generates a sender ID that won't collide with other senders on the same device.
+pub fn generate_sender_id() -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    format!("sender-{}", &id[..12])
+}
+
+/// Registry of open virtual connections, in the order they were opened.
+///
+/// Connections are reference-counted so controllers sharing a transport over one TLS
+/// channel don't close it under each other.
+#[derive(Debug, Default)]
+struct VirtualConnections {
+    entries: Vec<(String, usize)>,
+}
+
+impl VirtualConnections {
+    /// Registers a user of `destination_id`; returns true if the connection is new.
+    fn open(&mut self, destination_id: &str) -> bool {
+        match self.entries.iter_mut().find(|(id, _)| id == destination_id) {
+            Some((_, users)) => {
+                *users += 1;
+                false
+            }
+            None => {
+                self.entries.push((destination_id.to_string(), 1));
+                true
+            }
+        }
+    }
+
+    /// Releases a user of `destination_id`; returns true if that was the last one.
+    fn release(&mut self, destination_id: &str) -> bool {
+        let Some(pos) = self.entries.iter().position(|(id, _)| id == destination_id) else {
+            return false;
+        };
+        self.entries[pos].1 -= 1;
+        if self.entries[pos].1 == 0 {
+            self.entries.remove(pos);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forgets `destination_id` regardless of how many users it has.
+    fn remove(&mut self, destination_id: &str) {
+        self.entries.retain(|(id, _)| id != destination_id);
+    }
+
+    fn contains(&self, destination_id: &str) -> bool {
+        self.entries.iter().any(|(id, _)| id == destination_id)
+    }
+
+    fn destinations(&self) -> Vec<String> {
+        self.entries.iter().map(|(id, _)| id.clone()).collect()
+    }
+}
+
+/// State shared between all clones of a client and its background I/O task.
+struct Shared {
     event_tx: broadcast::Sender<CastEvent>,
+    device_event_tx: broadcast::Sender<DeviceEvent>,
+    connection_event_tx: broadcast::Sender<ConnectionEvent>,
+    state_tx: watch::Sender<ConnectionState>,
+    /// Latest `RECEIVER_STATUS` from the platform receiver.
+    receiver_status_tx: watch::Sender<Option<ReceiverStatusData>>,
+    /// Source ID of every message this client sends.
+    sender_id: String,
+    max_frame_size: usize,
+    /// DER certificate the device presented in the current TLS session.
+    peer_certificate: Mutex<Option<Vec<u8>>>,
+    /// Destinations we have sent CONNECT to; re-opened after every reconnect.
+    connections: Mutex<VirtualConnections>,
+    /// Per-namespace subscriptions, see [`CastClient::subscribe_namespace`].
+    namespaces: Mutex<HashMap<String, broadcast::Sender<NamespaceMessage>>>,
+    /// Requests waiting for a reply, keyed by `requestId`.
+    pending: Mutex<HashMap<i32, oneshot::Sender<CastEvent>>>,
+    next_request_id: AtomicI32,
+}
+
+impl Shared {
+    fn new(sender_id: String, max_frame_size: usize) -> Self {
+        let (event_tx, _) = broadcast::channel(32);
+        let (device_event_tx, _) = broadcast::channel(32);
+        let (connection_event_tx, _) = broadcast::channel(32);
+        let (state_tx, _) = watch::channel(ConnectionState::Connecting);
+        let (receiver_status_tx, _) = watch::channel(None);
+        Self {
+            event_tx,
+            device_event_tx,
+            connection_event_tx,
+            state_tx,
+            receiver_status_tx,
+            sender_id,
+            max_frame_size,
+            peer_certificate: Mutex::new(None),
+            connections: Mutex::new(VirtualConnections::default()),
+            namespaces: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            next_request_id: AtomicI32::new(1),
+        }
+    }
+
+    /// Publishes an inbound message to subscribers and completes the matching pending request, if any.
+    fn dispatch(&self, msg: &CastMessage) {
+        self.publish_namespace(msg);
+
+        // Only pay for decoding when someone is listening.
+        if self.device_event_tx.receiver_count() > 0 {
+            if let Some(typed) = DeviceEvent::from_message(msg) {
+                let _ = self.device_event_tx.send(typed);
+            }
+        }
+
+        let Some(Payload::Text(payload)) = Payload::from_message(msg) else {
+            return;
+        };
+        let event = CastEvent {
+            namespace: msg.namespace.clone(),
+            payload: payload.clone(),
+        };
+
+        if msg.namespace == connection::NAMESPACE
+            && serde_json::from_str::<Connection>(&payload).ok() == Some(Connection::Close)
+        {
+            self.connections.lock().unwrap().remove(&msg.source_id);
+            self.emit(ConnectionEvent::TransportClosed {
+                transport_id: msg.source_id.clone(),
+            });
+        }
+
+        // Update the cache before waking a request for the status, so the caller sees it.
+        if msg.namespace == receiver::NAMESPACE {
+            if let Ok(ReceiverResponse::ReceiverStatus { status, .. }) = serde_json::from_str(&payload)
+            {
+                self.receiver_status_tx.send_replace(Some(status));
+            }
+        }
+
+        let request_id = serde_json::from_str::<ReplyHeader>(&payload)
+            .map(|h| h.request_id)
+            .unwrap_or(0);
+        if request_id != 0 {
+            if let Some(tx) = self.pending.lock().unwrap().remove(&request_id) {
+                let _ = tx.send(event.clone());
+            }
+        }
+
+        let _ = self.event_tx.send(event);
+    }
+
+    fn publish_namespace(&self, msg: &CastMessage) {
+        let mut namespaces = self.namespaces.lock().unwrap();
+        let Some(tx) = namespaces.get(&msg.namespace) else {
+            return;
+        };
+        let Some(payload) = Payload::from_message(msg) else {
+            return;
+        };
+        let message = NamespaceMessage {
+            source_id: msg.source_id.clone(),
+            destination_id: msg.destination_id.clone(),
+            namespace: msg.namespace.clone(),
+            payload,
+        };
+        if tx.send(message).is_err() {
+            // Every subscriber is gone.
+            namespaces.remove(&msg.namespace);
+        }
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.state_tx.send_replace(state);
+    }
+
+    fn emit(&self, event: ConnectionEvent) {
+        let _ = self.connection_event_tx.send(event);
+    }
 }
 
 use crate::controllers::media::MediaController;
+use crate::controllers::namespace::NamespaceChannel;
 use crate::controllers::receiver::ReceiverController;
 
 impl CastClient {
@@ -42,151 +415,279 @@ impl CastClient {
         MediaController::new(self, transport_id)
     }
 
+    /// Opens a channel for a custom namespace on `destination_id`.
+    pub fn channel(&self, namespace: &str, destination_id: &str) -> NamespaceChannel {
+        NamespaceChannel::new(self, namespace, destination_id)
+    }
+
     /// Connects to a Cast device at the given host (IP) and port (default 8009).
     ///
     /// Establishes a TLS connection and starts a background task for heartbeats and message reading.
     pub async fn connect(host: &str, port: u16) -> Result<Self, CastError> {
-        let (command_tx, mut command_rx) = mpsc::channel::<CastMessage>(32);
-        let (event_tx, _) = broadcast::channel::<CastEvent>(32);
-        let event_tx_clone = event_tx.clone();
-
-        let host_owned = host.to_string();
-
-        tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(5));
-            let mut retry_delay = Duration::from_secs(1);
-
-            loop {
-                // Connection attempt loop
-                let addr = format!("{}:{}", host_owned, port);
-                let tcp_stream_res = TcpStream::connect(&addr).await;
-
-                if let Err(e) = tcp_stream_res {
-                    eprintln!("Connection failed: {}. Retrying in {:?}...", e, retry_delay);
-                    time::sleep(retry_delay).await;
-                    retry_delay = std::cmp::min(retry_delay * 2, Duration::from_secs(30));
-                    continue;
-                }
-                let tcp_stream = tcp_stream_res.unwrap();
-
-                let connector = create_tls_connector();
-                // Re-parse domain (should be cheap)
-                let domain = match host_owned.parse::<std::net::IpAddr>() {
-                    Ok(ip) => ServerName::IpAddress(ip),
-                    Err(_) => ServerName::try_from(host_owned.as_str()).unwrap(), // Should handle error better
-                };
-
-                let stream_res = connector.connect(domain, tcp_stream).await;
-                if let Err(e) = stream_res {
-                    eprintln!(
-                        "TLS Handshake failed: {}. Retrying in {:?}...",
-                        e, retry_delay
-                    );
-                    time::sleep(retry_delay).await;
-                    retry_delay = std::cmp::min(retry_delay * 2, Duration::from_secs(30));
-                    continue;
-                }
-                let stream = stream_res.unwrap();
-                let (mut reader, mut writer) = tokio::io::split(stream);
-                let mut buf = BytesMut::with_capacity(1024);
+        Self::connect_with_config(host, port, CastClientConfig::default()).await
+    }
 
-                println!("Connected to {}", host_owned);
-                retry_delay = Duration::from_secs(1); // Reset on success
+    /// Like [`CastClient::connect`], with explicit reconnect, timeout and certificate settings.
+    ///
+    /// Fails if the first connection cannot be established, e.g. with
+    /// [`CastError::CertificateMismatch`] when a pinned certificate changed.
+    pub async fn connect_with_config(
+        host: &str,
+        port: u16,
+        config: CastClientConfig,
+    ) -> Result<Self, CastError> {
+        let stream = open_stream(host, port, &config.certificate_policy).await?;
+        let (command_tx, command_rx) = mpsc::channel::<CastMessage>(32);
+        let sender_id = config.sender_id.clone().unwrap_or_else(generate_sender_id);
+        let shared = Arc::new(Shared::new(sender_id, config.max_frame_size));
+        let (task, shutdown_rx) = TaskHandle::new();
 
-                // Inner loop for active connection
-                loop {
-                    tokio::select! {
-                        _ = interval.tick() => {
-                            let ping = Heartbeat::Ping;
-                            let payload = serde_json::to_string(&ping).unwrap();
-                            let msg = CastMessage {
-                                protocol_version: 0,
-                                source_id: "sender-0".to_string(),
-                                destination_id: "receiver-0".to_string(),
-                                namespace: heartbeat::NAMESPACE.to_string(),
-                                payload_type: 0,
-                                payload_utf8: Some(payload),
-                                payload_binary: None,
-                            };
-
-                            let mut encode_buf = BytesMut::new();
-                            if CastCodec::encode(&msg, &mut encode_buf).is_ok() {
-                                 if let Err(_e) = writer.write_all(&encode_buf).await {
-                                     break; // Reconnect
-                                 }
-                            }
-                        }
-                        Some(msg) = command_rx.recv() => {
-                            let mut encode_buf = BytesMut::new();
-                            if CastCodec::encode(&msg, &mut encode_buf).is_ok() {
-                                 if let Err(_e) = writer.write_all(&encode_buf).await {
-                                     break; // Reconnect
-                                 }
-                            }
-                        }
-                        res = reader.read_buf(&mut buf) => {
-                             match res {
-                                 Ok(0) => break, // EOF, Reconnect
-                                 Ok(_) => {
-                                     loop {
-                                         match CastCodec::decode(&mut buf) {
-                                             Ok(Some(msg)) => {
-                                                 if let Some(payload) = &msg.payload_utf8 {
-                                                     let event = CastEvent {
-                                                         namespace: msg.namespace.clone(),
-                                                         payload: payload.clone(),
-                                                     };
-                                                     let _ = event_tx_clone.send(event);
-                                                 }
-                                             }
-                                             Ok(None) => break,
-                                             Err(_e) => {
-                                                 break;
-                                             },
-                                         }
-                                     }
-                                 }
-                                 Err(_e) => break, // Reconnect
-                             }
-                        }
-                    }
-                }
-                println!("Disconnected. Retrying in 5s...");
-                time::sleep(Duration::from_secs(5)).await;
-            }
-        });
+        let request_timeout = config.request_timeout;
+        let join = tokio::spawn(run_connection(
+            host.to_string(),
+            port,
+            config,
+            stream,
+            shared.clone(),
+            command_rx,
+            shutdown_rx,
+        ));
+        *task.join.lock().unwrap() = Some(join);
 
         Ok(Self {
             command_tx,
-            event_tx,
+            shared,
+            task: Arc::new(task),
+            request_timeout,
         })
     }
 
+    /// Returns the source ID this client puts on outgoing messages.
+    pub fn sender_id(&self) -> &str {
+        &self.shared.sender_id
+    }
+
+    /// Sets how long [`CastClient::request`] waits for a reply before giving up.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Allocates a new request ID.
+    ///
+    /// IDs are unique across all clones of this client and always positive,
+    /// since the device uses `0` for unsolicited status broadcasts.
+    pub fn next_request_id(&self) -> i32 {
+        self.shared
+            .next_request_id
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| {
+                Some(if id == i32::MAX { 1 } else { id + 1 })
+            })
+            .unwrap_or(1)
+    }
+
+    /// Sends a request and waits for the reply carrying the same `requestId`.
+    ///
+    /// Resolves with whatever the device answered (e.g. `MEDIA_STATUS`, `RECEIVER_STATUS`,
+    /// `LOAD_FAILED` or `INVALID_REQUEST`), or fails with [`CastError::Timeout`]
+    /// if nothing arrives within the request timeout.
+    pub async fn request<T: Request>(
+        &self,
+        destination_id: &str,
+        namespace: &str,
+        request: &T,
+    ) -> Result<CastEvent, CastError> {
+        let request_id = request.request_id();
+        let payload = serde_json::to_string(request)?;
+        let msg = CastMessage {
+            protocol_version: 0,
+            source_id: self.sender_id().to_string(),
+            destination_id: destination_id.to_string(),
+            namespace: namespace.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(payload),
+            payload_binary: None,
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.shared.pending.lock().unwrap().insert(request_id, tx);
+
+        if let Err(e) = self.send_message(msg).await {
+            self.shared.pending.lock().unwrap().remove(&request_id);
+            return Err(e);
+        }
+
+        match time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(event)) => Ok(event),
+            Ok(Err(_)) => Err(CastError::Protocol("Channel closed".into())),
+            Err(_) => {
+                self.shared.pending.lock().unwrap().remove(&request_id);
+                Err(CastError::Timeout(request_id))
+            }
+        }
+    }
+
+    /// Queues a message for the device.
+    ///
+    /// Messages larger than the configured frame limit are rejected here rather than
+    /// tearing down the connection.
     pub async fn send_message(&self, msg: CastMessage) -> Result<(), CastError> {
+        let size = msg.encoded_len();
+        if size > self.shared.max_frame_size {
+            return Err(CastError::FrameTooLarge {
+                size,
+                max: self.shared.max_frame_size,
+            });
+        }
         self.command_tx
             .send(msg)
             .await
             .map_err(|_| CastError::Protocol("Channel closed".into()))
     }
 
-    pub async fn connect_receiver(&self) -> Result<(), CastError> {
-        let msg = Connection::Connect;
-        let payload = serde_json::to_string(&msg).unwrap();
-
-        let cast_msg = CastMessage {
+    /// Sends a string or binary payload to `destination_id` on `namespace`.
+    pub async fn send_payload(
+        &self,
+        destination_id: &str,
+        namespace: &str,
+        payload: Payload,
+    ) -> Result<(), CastError> {
+        let (payload_type, payload_utf8, payload_binary) = match payload {
+            Payload::Text(text) => (PayloadType::String, Some(text), None),
+            Payload::Binary(data) => (PayloadType::Binary, None, Some(data)),
+        };
+        let msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
-            destination_id: "receiver-0".to_string(),
-            namespace: connection::NAMESPACE.to_string(),
-            payload_type: 0,
-            payload_utf8: Some(payload),
-            payload_binary: None,
+            source_id: self.sender_id().to_string(),
+            destination_id: destination_id.to_string(),
+            namespace: namespace.to_string(),
+            payload_type: payload_type as i32,
+            payload_utf8,
+            payload_binary,
         };
-        self.send_message(cast_msg).await
+        self.send_message(msg).await
+    }
+
+    pub async fn connect_receiver(&self) -> Result<(), CastError> {
+        self.connect_transport("receiver-0").await
+    }
+
+    /// Opens a virtual connection to `destination_id` (receiver-0 or an app transport).
+    ///
+    /// The connection is remembered and re-opened automatically after a reconnect.
+    /// CONNECT is only sent the first time; later calls just add a user, so pair each
+    /// call with [`CastClient::disconnect_transport`].
+    pub async fn connect_transport(&self, destination_id: &str) -> Result<(), CastError> {
+        let is_new = self.shared.connections.lock().unwrap().open(destination_id);
+        if !is_new {
+            return Ok(());
+        }
+        self.send_message(connect_message(self.sender_id(), destination_id))
+            .await
+    }
+
+    /// Releases the virtual connection to `destination_id`.
+    ///
+    /// CLOSE is sent once the last user has released it, after which it is no longer
+    /// re-opened on reconnect.
+    pub async fn disconnect_transport(&self, destination_id: &str) -> Result<(), CastError> {
+        let was_last = self.shared.connections.lock().unwrap().release(destination_id);
+        if !was_last {
+            return Ok(());
+        }
+        self.send_message(close_message(self.sender_id(), destination_id))
+            .await
+    }
+
+    /// Returns true if a virtual connection to `destination_id` is open.
+    pub fn is_transport_connected(&self, destination_id: &str) -> bool {
+        self.shared
+            .connections
+            .lock()
+            .unwrap()
+            .contains(destination_id)
+    }
+
+    /// Destinations with an open virtual connection, in the order they were opened.
+    pub fn virtual_connections(&self) -> Vec<String> {
+        self.shared.connections.lock().unwrap().destinations()
+    }
+
+    /// Shuts the client down.
+    ///
+    /// Sends CLOSE to every connected destination, flushes the connection, stops the
+    /// heartbeat and waits for the background task to finish. Every clone of the client
+    /// is unusable afterwards; calling `close` again is a no-op.
+    pub async fn close(&self) -> Result<(), CastError> {
+        self.task.shutdown_tx.send_replace(true);
+        let join = self.task.join.lock().unwrap().take();
+        if let Some(join) = join {
+            join.await
+                .map_err(|e| CastError::Protocol(format!("I/O task failed: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Challenges the device to prove it owns the TLS certificate it presented.
+    ///
+    /// `trust_anchors` are the DER root certificates the device credentials must chain to;
+    /// pass none to only check the signature. Check [`DeviceAuthResult::is_complete`]
+    /// before relying on it, as older devices do not sign the nonce. See [`crate::auth`].
+    pub async fn authenticate(
+        &self,
+        trust_anchors: &[Vec<u8>],
+    ) -> Result<DeviceAuthResult, CastError> {
+        let mut state = self.connection_state();
+        time::timeout(
+            self.request_timeout,
+            state.wait_for(|s| *s == ConnectionState::Connected),
+        )
+        .await
+        .map_err(|_| CastError::Auth("not connected to the device".into()))?
+        .map_err(|_| CastError::Protocol("Channel closed".into()))?;
+        let peer_certificate = self
+            .shared
+            .peer_certificate
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| CastError::Auth("device presented no TLS certificate".into()))?;
+
+        let nonce = uuid::Uuid::new_v4().as_bytes().to_vec();
+        let mut replies = self.subscribe_namespace(deviceauth::NAMESPACE);
+        let challenge = deviceauth::challenge(nonce.clone()).encode_to_vec();
+        self.send_payload("receiver-0", deviceauth::NAMESPACE, Payload::Binary(challenge))
+            .await?;
+
+        let reply = time::timeout(self.request_timeout, async {
+            loop {
+                match replies.recv().await {
+                    Ok(NamespaceMessage {
+                        payload: Payload::Binary(data),
+                        ..
+                    }) => return Ok(data),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(CastError::Protocol("Channel closed".into()))
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| CastError::Auth("device did not answer the challenge".into()))??;
+
+        let response = auth::parse_reply(&reply)?;
+        auth::verify_response(
+            &response,
+            &peer_certificate,
+            &nonce,
+            trust_anchors,
+            SystemTime::now(),
+        )
     }
 
     pub async fn launch_app(&self, app_id: &str) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Manage request IDs dynamically
+        let request_id = self.next_request_id();
         let msg = ReceiverRequest::Launch {
             app_id: app_id.to_string(),
             request_id,
@@ -195,7 +696,7 @@ impl CastClient {
 
         let cast_msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.sender_id().to_string(),
             destination_id: "receiver-0".to_string(),
             namespace: receiver::NAMESPACE.to_string(),
             payload_type: 0,
@@ -211,7 +712,7 @@ impl CastClient {
         media_session_id: i32,
         time: f32,
     ) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Manage request IDs dynamically
+        let request_id = self.next_request_id();
         let msg = MediaRequest::Seek {
             request_id,
             media_session_id,
@@ -222,7 +723,7 @@ impl CastClient {
 
         let cast_msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.sender_id().to_string(),
             destination_id: destination_id.to_string(),
             namespace: media::NAMESPACE.to_string(),
             payload_type: 0,
@@ -237,7 +738,7 @@ impl CastClient {
         destination_id: &str,
         media_session_id: Option<i32>,
     ) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Manage request IDs dynamically
+        let request_id = self.next_request_id();
         let msg = MediaRequest::GetStatus {
             request_id,
             media_session_id,
@@ -246,7 +747,7 @@ impl CastClient {
 
         let cast_msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.sender_id().to_string(),
             destination_id: destination_id.to_string(),
             namespace: media::NAMESPACE.to_string(),
             payload_type: 0,
@@ -257,19 +758,20 @@ impl CastClient {
     }
 
     pub async fn set_volume(&self, level: f32) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Manage request IDs dynamically
+        let request_id = self.next_request_id();
         let msg = ReceiverRequest::SetVolume {
             request_id,
             volume: Volume {
                 level: Some(level),
                 muted: None,
+                ..Default::default()
             },
         };
         let payload = serde_json::to_string(&msg).unwrap();
 
         let cast_msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.sender_id().to_string(),
             destination_id: "receiver-0".to_string(),
             namespace: receiver::NAMESPACE.to_string(),
             payload_type: 0,
@@ -279,28 +781,325 @@ impl CastClient {
         self.send_message(cast_msg).await
     }
 
+    /// Subscribes to the raw stream of messages received from the device.
     pub fn events(&self) -> broadcast::Receiver<CastEvent> {
-        self.event_tx.subscribe()
+        self.shared.event_tx.subscribe()
+    }
+
+    /// Returns the current state of the connection to the device.
+    pub fn state(&self) -> ConnectionState {
+        *self.shared.state_tx.borrow()
+    }
+
+    /// Watches the state of the connection to the device.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.shared.state_tx.subscribe()
+    }
+
+    /// Watches the latest receiver status (running applications, volume, standby).
+    ///
+    /// Holds `None` until the device first reports its status.
+    pub fn receiver_status(&self) -> watch::Receiver<Option<ReceiverStatusData>> {
+        self.shared.receiver_status_tx.subscribe()
+    }
+
+    /// Subscribes to connection lifecycle transitions.
+    pub fn connection_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.shared.connection_event_tx.subscribe()
+    }
+
+    /// Subscribes to every message, string or binary, received on `namespace`.
+    pub fn subscribe_namespace(&self, namespace: &str) -> broadcast::Receiver<NamespaceMessage> {
+        self.shared
+            .namespaces
+            .lock()
+            .unwrap()
+            .entry(namespace.to_string())
+            .or_insert_with(|| broadcast::channel(32).0)
+            .subscribe()
+    }
+
+    /// Subscribes to messages received from the device, decoded into [`DeviceEvent`]s.
+    pub fn device_events(&self) -> broadcast::Receiver<DeviceEvent> {
+        self.shared.device_event_tx.subscribe()
     }
 
     #[cfg(test)]
     pub fn new_mock() -> (Self, mpsc::Receiver<CastMessage>) {
         let (command_tx, command_rx) = mpsc::channel(32);
-        let (event_tx, _) = broadcast::channel(32);
+        let (task, _) = TaskHandle::new();
         (
             Self {
                 command_tx,
-                event_tx,
+                shared: Arc::new(Shared::new(generate_sender_id(), MAX_FRAME_SIZE)),
+                task: Arc::new(task),
+                request_timeout: DEFAULT_REQUEST_TIMEOUT,
             },
             command_rx,
         )
     }
+
+    /// Feeds a message into the client as if it had been read from the device.
+    #[cfg(test)]
+    pub(crate) fn inject_message(&self, msg: &CastMessage) {
+        self.shared.dispatch(msg);
+    }
+
+    /// Publishes a connection event as if the I/O task had.
+    #[cfg(test)]
+    pub(crate) fn inject_connection_event(&self, event: ConnectionEvent) {
+        self.shared.emit(event);
+    }
+}
+
+fn connect_message(source_id: &str, destination_id: &str) -> CastMessage {
+    CastMessage {
+        protocol_version: 0,
+        source_id: source_id.to_string(),
+        destination_id: destination_id.to_string(),
+        namespace: connection::NAMESPACE.to_string(),
+        payload_type: 0,
+        payload_utf8: Some(serde_json::to_string(&Connection::Connect).unwrap()),
+        payload_binary: None,
+    }
+}
+
+fn close_message(source_id: &str, destination_id: &str) -> CastMessage {
+    CastMessage {
+        payload_utf8: Some(serde_json::to_string(&Connection::Close).unwrap()),
+        ..connect_message(source_id, destination_id)
+    }
+}
+
+fn heartbeat_message(source_id: &str, destination_id: &str, heartbeat: &Heartbeat) -> CastMessage {
+    CastMessage {
+        protocol_version: 0,
+        source_id: source_id.to_string(),
+        destination_id: destination_id.to_string(),
+        namespace: heartbeat::NAMESPACE.to_string(),
+        payload_type: 0,
+        payload_utf8: Some(serde_json::to_string(heartbeat).unwrap()),
+        payload_binary: None,
+    }
+}
+
+/// Returns true if `msg` is a PING from the device that we must answer.
+fn is_ping(msg: &CastMessage) -> bool {
+    msg.namespace == heartbeat::NAMESPACE
+        && msg
+            .payload_utf8
+            .as_deref()
+            .and_then(|p| serde_json::from_str::<Heartbeat>(p).ok())
+            == Some(Heartbeat::Ping)
+}
+
+async fn open_stream(
+    host: &str,
+    port: u16,
+    policy: &CertificatePolicy,
+) -> Result<TlsStream<TcpStream>, CastError> {
+    let tcp_stream = TcpStream::connect((host, port)).await?;
+
+    let domain = match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => ServerName::IpAddress(ip),
+        Err(_) => ServerName::try_from(host).map_err(|e| CastError::Tls(e.to_string()))?,
+    };
+
+    let stream = create_tls_connector_with_policy(policy)
+        .connect(domain, tcp_stream)
+        .await
+        .map_err(|e| match fingerprint_mismatch(&e) {
+            Some(mismatch) => mismatch.into(),
+            None => CastError::Tls(e.to_string()),
+        })?;
+
+    // Persist a certificate pinned during the handshake, now that it is over.
+    if let CertificatePolicy::TrustOnFirstUse { store, .. } = policy {
+        let store = store.clone();
+        match tokio::task::spawn_blocking(move || store.flush()).await {
+            Ok(Err(e)) => log::warn!("Failed to save certificate fingerprints: {}", e),
+            Err(e) => log::warn!("Failed to save certificate fingerprints: {}", e),
+            Ok(Ok(())) => {}
+        }
+    }
+    Ok(stream)
+}
+
+/// Background task owning the TLS session: connects, reconnects according to the policy,
+/// sends heartbeats and queued commands, and dispatches inbound messages.
+///
+/// A session that stays silent for longer than the heartbeat timeout is treated as dead,
+/// so half-open TCP connections are detected and reconnected. The task ends once
+/// `shutdown_rx` fires or every client clone is dropped.
+async fn run_connection(
+    host: String,
+    port: u16,
+    config: CastClientConfig,
+    initial: TlsStream<TcpStream>,
+    shared: Arc<Shared>,
+    mut command_rx: mpsc::Receiver<CastMessage>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let policy = config.reconnect;
+    let mut interval = time::interval(config.heartbeat_interval);
+    let mut failures: u32 = 0;
+    let mut has_connected = false;
+    let mut initial = Some(initial);
+
+    loop {
+        let opened = match initial.take() {
+            Some(stream) => Ok(stream),
+            None => tokio::select! {
+                res = open_stream(&host, port, &config.certificate_policy) => res,
+                _ = shutdown_rx.changed() => break,
+            },
+        };
+        match opened {
+            Ok(stream) => {
+                failures = 0;
+                *shared.peer_certificate.lock().unwrap() = stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .map(|cert| cert.0.clone());
+                let codec = CastCodec::with_max_frame_size(config.max_frame_size);
+                let (reader, writer) = tokio::io::split(stream);
+                let mut reader = FramedRead::new(reader, codec.clone());
+                let mut writer = FramedWrite::new(writer, codec);
+
+                // Re-open virtual connections before anything queued goes out.
+                let destinations = shared.connections.lock().unwrap().destinations();
+                let mut reason = None;
+                for destination in destinations {
+                    let connect = connect_message(&shared.sender_id, &destination);
+                    if let Err(e) = writer.send(connect).await {
+                        reason = Some(e.to_string());
+                        break;
+                    }
+                }
+
+                if reason.is_none() {
+                    log::info!("Connected to {}:{}", host, port);
+                    shared.set_state(ConnectionState::Connected);
+                    shared.emit(if has_connected {
+                        ConnectionEvent::Reconnected
+                    } else {
+                        ConnectionEvent::Connected
+                    });
+                    has_connected = true;
+                }
+
+                // Inner loop for active connection
+                let mut last_seen = time::Instant::now();
+                let mut closing = false;
+                interval.reset();
+                while reason.is_none() && !closing {
+                    tokio::select! {
+                        _ = shutdown_rx.changed() => closing = true,
+                        _ = interval.tick() => {
+                            let msg = heartbeat_message(&shared.sender_id, "receiver-0", &Heartbeat::Ping);
+                            if let Err(e) = writer.send(msg).await {
+                                reason = Some(e.to_string());
+                            }
+                        }
+                        _ = time::sleep_until(last_seen + config.heartbeat_timeout) => {
+                            reason = Some(format!(
+                                "no message from device for {:?}",
+                                config.heartbeat_timeout
+                            ));
+                        }
+                        msg = command_rx.recv() => match msg {
+                            Some(msg) => {
+                                if let Err(e) = writer.send(msg).await {
+                                    reason = Some(e.to_string());
+                                }
+                            }
+                            None => closing = true,
+                        },
+                        frame = reader.next() => {
+                            match frame {
+                                None => reason = Some("connection closed by device".to_string()),
+                                Some(Ok(msg)) => {
+                                    last_seen = time::Instant::now();
+                                    if is_ping(&msg) {
+                                        let pong = heartbeat_message(&shared.sender_id, &msg.source_id, &Heartbeat::Pong);
+                                        if let Err(e) = writer.send(pong).await {
+                                            reason = Some(e.to_string());
+                                        }
+                                    }
+                                    shared.dispatch(&msg);
+                                }
+                                Some(Err(e)) => reason = Some(e.to_string()),
+                            }
+                        }
+                    }
+                }
+
+                if closing {
+                    // Flush whatever was queued before close() was called.
+                    while let Ok(msg) = command_rx.try_recv() {
+                        if writer.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Close app transports before the platform connection.
+                    let destinations = shared.connections.lock().unwrap().destinations();
+                    for destination in destinations.iter().rev() {
+                        let close = close_message(&shared.sender_id, destination);
+                        if writer.send(close).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Flushes and shuts down the TLS stream.
+                    let _ = writer.close().await;
+                    break;
+                }
+
+                let reason = reason.unwrap_or_default();
+                log::warn!("Disconnected from {}:{}: {}", host, port, reason);
+                shared.emit(ConnectionEvent::Disconnected { reason });
+            }
+            Err(e) => {
+                failures += 1;
+                log::warn!("Connection to {}:{} failed: {}", host, port, e);
+                shared.emit(ConnectionEvent::ConnectFailed {
+                    attempt: failures,
+                    reason: e.to_string(),
+                });
+                // Retrying cannot fix a certificate that no longer matches its pin.
+                if matches!(e, CastError::CertificateMismatch { .. }) {
+                    break;
+                }
+            }
+        }
+
+        if !policy.should_retry(failures) {
+            log::error!("Giving up on {}:{} after {} attempts", host, port, failures);
+            break;
+        }
+
+        let delay = policy.delay_for(failures);
+        shared.set_state(ConnectionState::Reconnecting);
+        shared.emit(ConnectionEvent::Reconnecting {
+            attempt: failures + 1,
+            delay,
+        });
+        tokio::select! {
+            _ = time::sleep(delay) => {}
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+
+    log::info!("Connection to {}:{} closed", host, port);
+    *shared.connections.lock().unwrap() = VirtualConnections::default();
+    shared.set_state(ConnectionState::Closed);
+    shared.emit(ConnectionEvent::Closed);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protocol::receiver::ReceiverRequest;
 
     #[tokio::test]
     async fn test_client_send_connection_message() {
@@ -312,6 +1111,42 @@ mod tests {
         assert!(msg.payload_utf8.unwrap().contains("\"type\":\"CONNECT\""));
     }
 
+    #[tokio::test]
+    async fn test_client_sender_ids_are_unique() {
+        let (first, mut rx) = CastClient::new_mock();
+        let (second, _) = CastClient::new_mock();
+        assert!(first.sender_id().starts_with("sender-"));
+        assert_ne!(first.sender_id(), second.sender_id());
+
+        first.launch_app("CC1AD845").await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().source_id, first.sender_id());
+    }
+
+    #[tokio::test]
+    async fn test_client_shares_virtual_connections() {
+        let (client, mut rx) = CastClient::new_mock();
+        client.connect_receiver().await.unwrap();
+        client.connect_transport("web-1").await.unwrap();
+        client.connect_transport("web-1").await.unwrap();
+        assert_eq!(client.virtual_connections(), vec!["receiver-0", "web-1"]);
+
+        // Only the first user of a transport sends CONNECT.
+        assert_eq!(rx.recv().await.unwrap().destination_id, "receiver-0");
+        assert_eq!(rx.recv().await.unwrap().destination_id, "web-1");
+        assert!(rx.try_recv().is_err());
+
+        // And only the last one sends CLOSE.
+        client.disconnect_transport("web-1").await.unwrap();
+        assert!(rx.try_recv().is_err());
+        assert!(client.is_transport_connected("web-1"));
+
+        client.disconnect_transport("web-1").await.unwrap();
+        let close = rx.recv().await.unwrap();
+        assert_eq!(close.destination_id, "web-1");
+        assert!(close.payload_utf8.unwrap().contains("\"CLOSE\""));
+        assert!(!client.is_transport_connected("web-1"));
+    }
+
     #[tokio::test]
     async fn test_client_launch_app() {
         let (client, mut rx) = CastClient::new_mock();
@@ -335,4 +1170,122 @@ mod tests {
         assert!(payload.contains("\"type\":\"SET_VOLUME\""));
         assert!(payload.contains("\"level\":0.5"));
     }
+
+    #[tokio::test]
+    async fn test_client_rejects_oversized_message() {
+        let (client, mut rx) = CastClient::new_mock();
+        let res = client
+            .send_payload(
+                "web-1",
+                "urn:x-cast:com.example.big",
+                Payload::Binary(vec![0; MAX_FRAME_SIZE + 1]),
+            )
+            .await;
+        assert!(matches!(res, Err(CastError::FrameTooLarge { max: MAX_FRAME_SIZE, .. })));
+        assert!(rx.try_recv().is_err());
+    }
+
+    fn reply(namespace: &str, payload: &str) -> CastMessage {
+        CastMessage {
+            protocol_version: 0,
+            source_id: "receiver-0".to_string(),
+            destination_id: "sender-0".to_string(),
+            namespace: namespace.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(payload.to_string()),
+            payload_binary: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_request_ids_are_unique() {
+        let (client, mut rx) = CastClient::new_mock();
+        client.launch_app("CC1AD845").await.unwrap();
+        client.set_volume(0.5).await.unwrap();
+
+        let first = rx.recv().await.unwrap().payload_utf8.unwrap();
+        let second = rx.recv().await.unwrap().payload_utf8.unwrap();
+        assert!(first.contains("\"requestId\":1"));
+        assert!(second.contains("\"requestId\":2"));
+        assert_eq!(client.clone().next_request_id(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_request_resolves_with_matching_reply() {
+        let (client, mut rx) = CastClient::new_mock();
+        let request_id = client.next_request_id();
+        let msg = ReceiverRequest::GetStatus { request_id };
+
+        let requester = client.clone();
+        let handle = tokio::spawn(async move {
+            requester
+                .request("receiver-0", receiver::NAMESPACE, &msg)
+                .await
+        });
+
+        let sent = rx.recv().await.unwrap();
+        assert!(sent
+            .payload_utf8
+            .unwrap()
+            .contains(&format!("\"requestId\":{}", request_id)));
+
+        // Unrelated broadcast and a reply to another request must not resolve it.
+        client.inject_message(&reply(
+            receiver::NAMESPACE,
+            r#"{"type":"RECEIVER_STATUS","requestId":0,"status":{}}"#,
+        ));
+        client.inject_message(&reply(
+            media::NAMESPACE,
+            r#"{"type":"INVALID_REQUEST","requestId":999}"#,
+        ));
+        client.inject_message(&reply(
+            receiver::NAMESPACE,
+            &format!(
+                r#"{{"type":"RECEIVER_STATUS","requestId":{},"status":{{}}}}"#,
+                request_id
+            ),
+        ));
+
+        let event = handle.await.unwrap().unwrap();
+        assert_eq!(event.namespace, receiver::NAMESPACE);
+        assert!(event.payload.contains("RECEIVER_STATUS"));
+        assert!(event
+            .payload
+            .contains(&format!("\"requestId\":{}", request_id)));
+    }
+
+    #[tokio::test]
+    async fn test_client_request_times_out() {
+        let (client, _rx) = CastClient::new_mock();
+        let client = client.with_request_timeout(Duration::from_millis(20));
+        let request_id = client.next_request_id();
+        let msg = MediaRequest::GetStatus {
+            request_id,
+            media_session_id: None,
+        };
+
+        let res = client
+            .request("web-1", media::NAMESPACE, &msg)
+            .await;
+        assert!(matches!(res, Err(CastError::Timeout(id)) if id == request_id));
+        assert!(client.shared.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_client_publishes_typed_events() {
+        let (client, _rx) = CastClient::new_mock();
+        let mut raw = client.events();
+        let mut typed = client.device_events();
+
+        client.inject_message(&reply(
+            media::NAMESPACE,
+            r#"{"type":"LOAD_FAILED","requestId":4}"#,
+        ));
+
+        assert!(raw.recv().await.unwrap().payload.contains("LOAD_FAILED"));
+        assert!(matches!(
+            typed.recv().await.unwrap(),
+            DeviceEvent::LoadFailed { request_id: 4, .. }
+        ));
+    }
 }
@@ -2,18 +2,65 @@ use crate::error::CastError;
 use crate::proto::CastMessage;
 use bytes::{Buf, BufMut, BytesMut};
 use prost::Message;
+use tokio_util::codec::{Decoder, Encoder};
 
-pub struct CastCodec;
+/// Largest message the Cast protocol allows (64 KiB, excluding the length prefix).
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Length-prefixed protobuf framing for [`CastMessage`]s.
+///
+/// Frames announcing more than `max_frame_size` bytes are rejected with
+/// [`CastError::FrameTooLarge`] before any memory is reserved for them.
+#[derive(Debug, Clone)]
+pub struct CastCodec {
+    max_frame_size: usize,
+}
+
+impl Default for CastCodec {
+    fn default() -> Self {
+        Self {
+            max_frame_size: MAX_FRAME_SIZE,
+        }
+    }
+}
 
 impl CastCodec {
-    pub fn encode(item: &CastMessage, dst: &mut BytesMut) -> Result<(), CastError> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest frame accepted in either direction.
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+}
+
+impl Encoder<CastMessage> for CastCodec {
+    type Error = CastError;
+
+    fn encode(&mut self, item: CastMessage, dst: &mut BytesMut) -> Result<(), CastError> {
         let len = item.encoded_len();
+        if len > self.max_frame_size {
+            return Err(CastError::FrameTooLarge {
+                size: len,
+                max: self.max_frame_size,
+            });
+        }
         dst.reserve(4 + len);
         dst.put_u32(len as u32);
         item.encode(dst).map_err(CastError::from)
     }
+}
+
+impl Decoder for CastCodec {
+    type Item = CastMessage;
+    type Error = CastError;
 
-    pub fn decode(src: &mut BytesMut) -> Result<Option<CastMessage>, CastError> {
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<CastMessage>, CastError> {
         if src.len() < 4 {
             return Ok(None);
         }
@@ -23,9 +70,16 @@ impl CastCodec {
         len_bytes.copy_from_slice(&src[..4]);
         let len = u32::from_be_bytes(len_bytes) as usize;
 
+        if len > self.max_frame_size {
+            return Err(CastError::FrameTooLarge {
+                size: len,
+                max: self.max_frame_size,
+            });
+        }
+
         if src.len() < 4 + len {
             // Not enough data yet
-            dst_reserve(src, 4 + len - src.len());
+            src.reserve(4 + len - src.len());
             return Ok(None);
         }
 
@@ -37,28 +91,29 @@ impl CastCodec {
     }
 }
 
-fn dst_reserve(dst: &mut BytesMut, additional: usize) {
-    dst.reserve(additional);
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::proto::CastMessage;
     use bytes::BufMut;
 
+    fn ping() -> CastMessage {
+        CastMessage {
+            protocol_version: 0, // CASTV2_1_0
+            source_id: "sender-0".to_string(),
+            destination_id: "receiver-0".to_string(),
+            namespace: "urn:x-cast:com.google.cast.tp.heartbeat".to_string(),
+            payload_type: 0, // STRING
+            payload_utf8: Some("PING".to_string()),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_encode_decode() {
-        let mut msg = CastMessage::default();
-        msg.protocol_version = 0; // CASTV2_1_0
-        msg.source_id = "sender-0".to_string();
-        msg.destination_id = "receiver-0".to_string();
-        msg.namespace = "urn:x-cast:com.google.cast.tp.heartbeat".to_string();
-        msg.payload_type = 0; // STRING
-        msg.payload_utf8 = Some("PING".to_string());
-
+        let mut codec = CastCodec::new();
         let mut buf = BytesMut::new();
-        CastCodec::encode(&msg, &mut buf).unwrap();
+        codec.encode(ping(), &mut buf).unwrap();
 
         // Check length
         assert!(buf.len() > 4);
@@ -66,7 +121,7 @@ mod tests {
         assert_eq!(len as usize, buf.len() - 4);
 
         // Decode
-        let decoded = CastCodec::decode(&mut buf).unwrap().unwrap();
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
         assert_eq!(decoded.source_id, "sender-0");
         assert_eq!(decoded.payload_utf8, Some("PING".to_string()));
     }
@@ -79,8 +134,30 @@ mod tests {
         // Add 5 bytes of data
         buf.put_slice(&[0u8; 5]);
 
-        let res = CastCodec::decode(&mut buf).unwrap();
+        let res = CastCodec::new().decode(&mut buf).unwrap();
         assert!(res.is_none());
         assert_eq!(buf.len(), 9); // 4 + 5 (not consumed)
     }
+
+    #[test]
+    fn test_decode_rejects_oversized_frame() {
+        let mut buf = BytesMut::new();
+        buf.put_u32(u32::MAX);
+
+        let err = CastCodec::new().decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            CastError::FrameTooLarge { size, max } if size == u32::MAX as usize && max == MAX_FRAME_SIZE
+        ));
+        assert!(buf.capacity() < 1024);
+    }
+
+    #[test]
+    fn test_encode_rejects_oversized_message() {
+        let mut codec = CastCodec::with_max_frame_size(16);
+        let mut buf = BytesMut::new();
+        let err = codec.encode(ping(), &mut buf).unwrap_err();
+        assert!(matches!(err, CastError::FrameTooLarge { max: 16, .. }));
+        assert!(buf.is_empty());
+    }
 }
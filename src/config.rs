@@ -1,8 +1,14 @@
 
 #[derive(Debug, Clone, Default)]
 pub struct Config {
+    /// The first `--ip` given.
     pub target_ip: Option<String>,
+    /// The first `--name` given.
     pub target_name: Option<String>,
+    /// Every `--ip` given; more than one target casts to all of them as a group.
+    pub target_ips: Vec<String>,
+    /// Every `--name` given.
+    pub target_names: Vec<String>,
     pub log_file: Option<String>,
     pub inputs: Vec<String>,
     pub myip: Option<String>,
@@ -11,12 +17,23 @@ pub struct Config {
     pub volume: Option<f32>,
     pub loop_playlist: bool,
     pub quiet: bool,
+    /// Transcode to a single fragmented MP4 stream instead of HLS.
+    pub no_hls: bool,
+    /// `--cache-size`: how many MB of finished transcodes to keep; 0 disables the cache.
+    pub transcode_cache_mb: Option<u64>,
+    /// Pin each device's TLS certificate to its discovered UUID on first use.
+    pub pin_certificates: bool,
 }
 
 impl Config {
+    /// Whether several devices were targeted, to be driven in step.
+    pub fn is_group(&self) -> bool {
+        self.target_ips.len() + self.target_names.len() > 1
+    }
+
     pub fn parse(args: &[String]) -> Self {
-        let mut target_ip = None;
-        let mut target_name = None;
+        let mut target_ips = Vec::new();
+        let mut target_names = Vec::new();
         let mut log_file = None;
         let mut inputs = Vec::new();
         let mut myip = None;
@@ -25,19 +42,22 @@ impl Config {
         let mut volume = None;
         let mut loop_playlist = false;
         let mut quiet = false;
+        let mut no_hls = false;
+        let mut transcode_cache_mb = None;
+        let mut pin_certificates = false;
 
         let mut i = 0;
         while i < args.len() {
             match args[i].as_str() {
                 "--ip" => {
                     if i + 1 < args.len() {
-                        target_ip = Some(args[i + 1].clone());
+                        target_ips.push(args[i + 1].clone());
                         i += 1;
                     }
                 }
                 "--name" => {
                     if i + 1 < args.len() {
-                        target_name = Some(args[i + 1].clone());
+                        target_names.push(args[i + 1].clone());
                         i += 1;
                     }
                 }
@@ -81,6 +101,20 @@ impl Config {
                 "--quiet" => {
                     quiet = true;
                 }
+                "--no-hls" => {
+                    no_hls = true;
+                }
+                "--pin-certificates" => {
+                    pin_certificates = true;
+                }
+                "--cache-size" => {
+                    if i + 1 < args.len() {
+                        if let Ok(mb) = args[i + 1].parse::<u64>() {
+                            transcode_cache_mb = Some(mb);
+                        }
+                        i += 1;
+                    }
+                }
                 val => {
                     inputs.push(val.to_string());
                 }
@@ -89,8 +123,10 @@ impl Config {
         }
 
         Self {
-            target_ip,
-            target_name,
+            target_ip: target_ips.first().cloned(),
+            target_name: target_names.first().cloned(),
+            target_ips,
+            target_names,
             log_file,
             inputs,
             myip,
@@ -99,6 +135,61 @@ impl Config {
             volume,
             loop_playlist,
             quiet,
+            no_hls,
+            transcode_cache_mb,
+            pin_certificates,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_repeated_targets_form_a_group() {
+        let config = Config::parse(&args(&["--ip", "10.0.0.2", "movie.mp4"]));
+        assert_eq!(config.target_ip.as_deref(), Some("10.0.0.2"));
+        assert!(!config.is_group());
+
+        let config = Config::parse(&args(&[
+            "--name", "Kitchen", "--name", "Living Room", "--ip", "10.0.0.9", "song.mp3",
+        ]));
+        assert_eq!(config.target_name.as_deref(), Some("Kitchen"));
+        assert_eq!(config.target_names, ["Kitchen", "Living Room"]);
+        assert_eq!(config.target_ips, ["10.0.0.9"]);
+        assert_eq!(config.inputs, ["song.mp3"]);
+        assert!(config.is_group());
+    }
+
+    #[test]
+    fn test_cache_size_flag() {
+        assert_eq!(Config::parse(&args(&["movie.mkv"])).transcode_cache_mb, None);
+
+        let config = Config::parse(&args(&["--cache-size", "512", "movie.mkv"]));
+        assert_eq!(config.transcode_cache_mb, Some(512));
+        assert_eq!(config.inputs, ["movie.mkv"]);
+    }
+
+    #[test]
+    fn test_pin_certificates_flag() {
+        assert!(!Config::parse(&args(&["movie.mkv"])).pin_certificates);
+
+        let config = Config::parse(&args(&["--pin-certificates", "movie.mkv"]));
+        assert!(config.pin_certificates);
+        assert_eq!(config.inputs, ["movie.mkv"]);
+    }
+
+    #[test]
+    fn test_no_hls_flag() {
+        assert!(!Config::parse(&args(&["movie.mkv"])).no_hls);
+
+        let config = Config::parse(&args(&["--no-hls", "movie.mkv"]));
+        assert!(config.no_hls);
+        assert_eq!(config.inputs, ["movie.mkv"]);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::client::CastClient;
+use crate::controllers::media::MediaController;
+use crate::controllers::namespace::NamespaceChannel;
+use crate::controllers::receiver::ReceiverController;
+use crate::error::CastError;
+use crate::protocol::receiver::{self, ReceiverStatusData};
+use std::time::Duration;
+
+/// App ID of Google's Default Media Receiver.
+pub const DEFAULT_MEDIA_RECEIVER_ID: &str = "CC1AD845";
+
+/// How long a launched application may take to report its transport.
+const TRANSPORT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A receiver application identified by its App ID.
+///
+/// Launches (or joins) the application, connects to its transport, and stops it again.
+/// Talk to the running application through [`Application::media`] or
+/// [`Application::channel`].
+pub struct Application {
+    client: CastClient,
+    receiver: ReceiverController,
+    app_id: String,
+    session: Option<receiver::Application>,
+}
+
+impl Application {
+    /// An application with a custom App ID, e.g. one registered in the Cast developer console.
+    pub fn new(client: &CastClient, app_id: &str) -> Self {
+        Self {
+            client: client.clone(),
+            receiver: ReceiverController::new(client),
+            app_id: app_id.to_string(),
+            session: None,
+        }
+    }
+
+    /// Google's Default Media Receiver, which plays media from URLs.
+    pub fn default_media_receiver(client: &CastClient) -> Self {
+        Self::new(client, DEFAULT_MEDIA_RECEIVER_ID)
+    }
+
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    /// The running session we are connected to, once launched or joined.
+    pub fn session(&self) -> Option<&receiver::Application> {
+        self.session.as_ref()
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session.as_ref().map(|s| s.session_id.as_str())
+    }
+
+    pub fn transport_id(&self) -> Option<&str> {
+        self.session.as_ref().map(|s| s.transport_id.as_str())
+    }
+
+    /// Asks the device whether it can run this application.
+    pub async fn is_available(&self) -> Result<bool, CastError> {
+        let availability = self.receiver.get_app_availability(&[&self.app_id]).await?;
+        Ok(availability.get(&self.app_id).copied().unwrap_or(false))
+    }
+
+    /// Launches the application, waits for its transport and connects to it.
+    ///
+    /// Fails with [`CastError::LaunchFailed`] if the device refuses to launch it.
+    pub async fn launch(&mut self) -> Result<&receiver::Application, CastError> {
+        let mut status_rx = self.client.receiver_status();
+        self.receiver.launch(&self.app_id).await?;
+
+        // The confirmation may come before the application has a transport.
+        let app_id = self.app_id.clone();
+        let wait = status_rx
+            .wait_for(|status| status.as_ref().is_some_and(|s| running(s, &app_id).is_some()));
+        let session = match tokio::time::timeout(TRANSPORT_TIMEOUT, wait).await {
+            Ok(Ok(status)) => status.as_ref().and_then(|s| running(s, &app_id)).cloned(),
+            Ok(Err(_)) => return Err(CastError::Protocol("Client closed".into())),
+            Err(_) => None,
+        }
+        .ok_or_else(|| CastError::Protocol("Timeout waiting for application launch".into()))?;
+
+        self.connect(session).await
+    }
+
+    /// Connects to the application if it is already running, without relaunching it.
+    ///
+    /// Returns `false` if it is not running.
+    pub async fn join(&mut self) -> Result<bool, CastError> {
+        let status = self.receiver.fetch_status().await?;
+        match running(&status, &self.app_id).cloned() {
+            Some(session) => {
+                self.connect(session).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Stops the application's session and disconnects from it.
+    pub async fn stop(&mut self) -> Result<(), CastError> {
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        self.receiver.stop_app(&session.session_id).await?;
+        self.client.disconnect_transport(&session.transport_id).await
+    }
+
+    /// A media controller for the running application.
+    pub fn media(&self) -> Option<MediaController> {
+        let transport_id = self.transport_id()?;
+        Some(MediaController::new(&self.client, transport_id))
+    }
+
+    /// A channel for a custom namespace of the running application.
+    pub fn channel(&self, namespace: &str) -> Option<NamespaceChannel> {
+        let transport_id = self.transport_id()?;
+        Some(self.client.channel(namespace, transport_id))
+    }
+
+    async fn connect(
+        &mut self,
+        session: receiver::Application,
+    ) -> Result<&receiver::Application, CastError> {
+        self.receiver.join_session(&session.transport_id).await?;
+        Ok(self.session.insert(session))
+    }
+}
+
+/// The session of `app_id` in `status`, once it has a transport to connect to.
+fn running<'a>(status: &'a ReceiverStatusData, app_id: &str) -> Option<&'a receiver::Application> {
+    status
+        .application(app_id)
+        .filter(|app| !app.transport_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Request;
+    use crate::proto::CastMessage;
+    use crate::protocol::receiver::ReceiverRequest;
+    use serde_json::{json, Value};
+    use tokio::sync::mpsc;
+
+    fn reply(payload: Value) -> CastMessage {
+        CastMessage {
+            protocol_version: 0,
+            source_id: "receiver-0".to_string(),
+            destination_id: "*".to_string(),
+            namespace: receiver::NAMESPACE.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(payload.to_string()),
+            payload_binary: None,
+        }
+    }
+
+    /// Waits for the next receiver request the client sends, skipping CONNECTs.
+    async fn next_request(rx: &mut mpsc::Receiver<CastMessage>) -> ReceiverRequest {
+        loop {
+            let msg = rx.recv().await.unwrap();
+            if msg.namespace == receiver::NAMESPACE {
+                return serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_availability_and_launch() {
+        let (client, mut rx) = CastClient::new_mock();
+        let mut app = Application::new(&client, "ABCD1234");
+
+        let device = async {
+            let ReceiverRequest::GetAppAvailability { request_id, app_ids } =
+                next_request(&mut rx).await
+            else {
+                panic!("expected GET_APP_AVAILABILITY");
+            };
+            assert_eq!(app_ids, ["ABCD1234"]);
+            client.inject_message(&reply(json!({
+                "requestId": request_id,
+                "responseType": "GET_APP_AVAILABILITY",
+                "availability": {"ABCD1234": "APP_AVAILABLE"}
+            })));
+
+            let ReceiverRequest::Launch { request_id, .. } = next_request(&mut rx).await else {
+                panic!("expected LAUNCH");
+            };
+            client.inject_message(&reply(json!({
+                "type": "RECEIVER_STATUS",
+                "requestId": request_id,
+                "status": {"applications": [{
+                    "appId": "ABCD1234",
+                    "displayName": "Custom App",
+                    "sessionId": "session-9",
+                    "transportId": "web-9",
+                    "statusText": "",
+                    "isIdleScreen": false
+                }]}
+            })));
+        };
+
+        let launch = async {
+            assert!(app.is_available().await.unwrap());
+            app.launch().await.map(|s| s.session_id.clone())
+        };
+        let (session_id, ()) = tokio::join!(launch, device);
+        assert_eq!(session_id.unwrap(), "session-9");
+        assert_eq!(app.transport_id(), Some("web-9"));
+        assert!(client.is_transport_connected("web-9"));
+    }
+
+    #[tokio::test]
+    async fn test_launch_error_is_typed() {
+        let (client, mut rx) = CastClient::new_mock();
+        let mut app = Application::new(&client, "NOPE0000");
+
+        let device = async {
+            let request = next_request(&mut rx).await;
+            client.inject_message(&reply(json!({
+                "type": "LAUNCH_ERROR",
+                "requestId": request.request_id(),
+                "reason": "NOT_FOUND"
+            })));
+        };
+
+        let launch = async { app.launch().await.map(|_| ()) };
+        let (result, ()) = tokio::join!(launch, device);
+        match result {
+            Err(CastError::LaunchFailed { app_id, reason }) => {
+                assert_eq!(app_id, "NOPE0000");
+                assert_eq!(reason, "NOT_FOUND");
+            }
+            other => panic!("expected LaunchFailed, got {:?}", other),
+        }
+        assert!(app.session().is_none());
+    }
+}
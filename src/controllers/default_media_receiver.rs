@@ -1,31 +1,33 @@
 use crate::client::CastClient;
+use crate::controllers::application::Application;
 use crate::controllers::media::MediaController;
-use crate::controllers::receiver::ReceiverController;
 use crate::error::CastError;
-use crate::protocol::media::MediaInformation;
-use crate::protocol::receiver::{ReceiverResponse, NAMESPACE as RECEIVER_NAMESPACE};
+use crate::protocol::media::{MediaInformation, QueueItem, RepeatMode};
 
-const DEFAULT_MEDIA_RECEIVER_ID: &str = "CC1AD845";
+/// A queue the receiver accepted with [`DefaultMediaReceiver::queue_load`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedQueue {
+    pub media_session_id: i32,
+    /// The IDs the receiver gave the items, in the order they were loaded; `currentItemId`
+    /// in later media statuses refers to these.
+    pub item_ids: Vec<i32>,
+}
 
 /// A high-level wrapper for the Default Media Receiver application.
 ///
 /// This struct simplifies the process of launching the Default Media Receiver,
 /// connecting to it, and loading media.
 pub struct DefaultMediaReceiver {
-    client: CastClient,
-    receiver_controller: ReceiverController,
+    app: Application,
     media_controller: Option<MediaController>,
-    app_id: String,
 }
 
 impl DefaultMediaReceiver {
     /// Creates a new DefaultMediaReceiver instance.
     pub fn new(client: &CastClient) -> Self {
         Self {
-            client: client.clone(),
-            receiver_controller: ReceiverController::new(client),
+            app: Application::default_media_receiver(client),
             media_controller: None,
-            app_id: DEFAULT_MEDIA_RECEIVER_ID.to_string(),
         }
     }
 
@@ -33,40 +35,18 @@ impl DefaultMediaReceiver {
     ///
     /// This method will:
     /// 1. Send a LAUNCH request for the Default Media Receiver.
-    /// 2. Wait for a RECEIVER_STATUS message indicating the app is running.
+    /// 2. Wait for the receiver status to show the app running with a transport ID.
     /// 3. Connect to the application's transport ID.
     /// 4. Initialize the internal MediaController.
     pub async fn launch(&mut self) -> Result<(), CastError> {
-        let mut rx = self.client.events();
-
-        self.receiver_controller.launch_app(&self.app_id).await?;
-
-        // Wait for the application to be ready
-        // We look for a RECEIVER_STATUS message that contains our App ID with a transportId
-        let timeout = tokio::time::sleep(tokio::time::Duration::from_secs(15));
-        tokio::pin!(timeout);
-
-        loop {
-            tokio::select! {
-                Ok(event) = rx.recv() => {
-                    if event.namespace == RECEIVER_NAMESPACE {
-                        if let Ok(ReceiverResponse::ReceiverStatus { status, .. }) = serde_json::from_str::<ReceiverResponse>(&event.payload) {
-                            if let Some(app) = status.applications.iter().find(|a| a.app_id == self.app_id) {
-                                // App is running, connect to it
-                                self.receiver_controller.join_session(&app.transport_id).await?;
-
-                                // Initialize MediaController
-                                self.media_controller = Some(MediaController::new(&self.client, &app.transport_id));
-                                return Ok(());
-                            }
-                        }
-                    }
-                }
-                _ = &mut timeout => {
-                    return Err(CastError::Protocol("Timeout waiting for application launch".into()));
-                }
-            }
-        }
+        self.app.launch().await?;
+        self.media_controller = self.app.media();
+        Ok(())
+    }
+
+    /// The underlying application, for its session ID or stopping it.
+    pub fn application(&self) -> &Application {
+        &self.app
     }
 
     /// Check if the receiver has been launched and connected.
@@ -74,6 +54,11 @@ impl DefaultMediaReceiver {
         self.media_controller.is_some()
     }
 
+    /// The controller for the launched app's media session, for requests not wrapped here.
+    pub fn media(&self) -> Option<&MediaController> {
+        self.media_controller.as_ref()
+    }
+
     /// Loads media content into the receiver.
     pub async fn load(
         &self,
@@ -124,6 +109,17 @@ impl DefaultMediaReceiver {
         }
     }
 
+    /// Requests the current media status from the receiver.
+    pub async fn get_status(&self, media_session_id: Option<i32>) -> Result<(), CastError> {
+        if let Some(controller) = &self.media_controller {
+            controller.get_status(media_session_id).await
+        } else {
+            Err(CastError::Protocol(
+                "MediaController not initialized. Call launch() first.".into(),
+            ))
+        }
+    }
+
     /// Seeks to a specific time in the media.
     pub async fn seek(&self, media_session_id: i32, time: f32) -> Result<(), CastError> {
         if let Some(controller) = &self.media_controller {
@@ -134,4 +130,60 @@ impl DefaultMediaReceiver {
             ))
         }
     }
+
+    /// Hands a whole playlist to the receiver, which then advances through it by itself.
+    /// Returns the new media session and the IDs the receiver gave the items.
+    pub async fn queue_load(
+        &self,
+        items: Vec<QueueItem>,
+        start_index: usize,
+        repeat_mode: RepeatMode,
+        current_time: f32,
+    ) -> Result<LoadedQueue, CastError> {
+        if let Some(controller) = &self.media_controller {
+            let status = controller
+                .queue_load(
+                    items,
+                    Some(start_index as i32),
+                    Some(repeat_mode),
+                    Some(current_time),
+                )
+                .await?;
+            let media_session_id = status
+                .first()
+                .map(|s| s.media_session_id)
+                .ok_or_else(|| CastError::Protocol("Queue loaded without a media session".into()))?;
+            let item_ids = controller.queue_get_item_ids(media_session_id).await?;
+            Ok(LoadedQueue {
+                media_session_id,
+                item_ids,
+            })
+        } else {
+            Err(CastError::Protocol(
+                "MediaController not initialized. Call launch() first.".into(),
+            ))
+        }
+    }
+
+    /// Skips to the next item of the receiver's queue.
+    pub async fn queue_next(&self, media_session_id: i32) -> Result<(), CastError> {
+        if let Some(controller) = &self.media_controller {
+            controller.queue_next(media_session_id).await
+        } else {
+            Err(CastError::Protocol(
+                "MediaController not initialized. Call launch() first.".into(),
+            ))
+        }
+    }
+
+    /// Goes back to the previous item of the receiver's queue.
+    pub async fn queue_prev(&self, media_session_id: i32) -> Result<(), CastError> {
+        if let Some(controller) = &self.media_controller {
+            controller.queue_prev(media_session_id).await
+        } else {
+            Err(CastError::Protocol(
+                "MediaController not initialized. Call launch() first.".into(),
+            ))
+        }
+    }
 }
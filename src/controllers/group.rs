@@ -0,0 +1,505 @@
+use crate::client::{CastClient, ConnectionEvent};
+use crate::controllers::default_media_receiver::DefaultMediaReceiver;
+use crate::controllers::receiver::ReceiverController;
+use crate::error::CastError;
+use crate::events::DeviceEvent;
+use crate::protocol::media::MediaInformation;
+use futures_util::future::join_all;
+use std::time::Instant;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+/// How far (in seconds) a member may drift from the leader before [`CastGroup::resync`]
+/// seeks it back.
+pub const DEFAULT_MAX_DRIFT: f32 = 1.0;
+
+/// The last known playback state of one device in a [`CastGroup`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberState {
+    pub name: String,
+    /// `IDLE`, `PLAYING`, `PAUSED` or `BUFFERING`.
+    pub player_state: String,
+    pub idle_reason: Option<String>,
+    pub current_time: f32,
+    pub duration: Option<f32>,
+    pub volume_level: Option<f32>,
+    pub is_muted: bool,
+    pub media_session_id: Option<i32>,
+    pub connected: bool,
+    /// When `current_time` was reported.
+    pub updated_at: Instant,
+}
+
+impl MemberState {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            player_state: "IDLE".to_string(),
+            idle_reason: None,
+            current_time: 0.0,
+            duration: None,
+            volume_level: None,
+            is_muted: false,
+            media_session_id: None,
+            connected: true,
+            updated_at: Instant::now(),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.player_state == "PLAYING"
+    }
+
+    /// The current position, extrapolated from the last report while playing.
+    pub fn position(&self) -> f32 {
+        if self.is_playing() {
+            self.current_time + self.updated_at.elapsed().as_secs_f32()
+        } else {
+            self.current_time
+        }
+    }
+}
+
+struct Member {
+    receiver: ReceiverController,
+    app: DefaultMediaReceiver,
+    task: JoinHandle<()>,
+}
+
+/// Casts the same media to several devices and keeps them in step.
+///
+/// Every command goes to all members at once; playback commands fail with
+/// [`CastError::NoMediaSession`] for members that have not reported a media session yet,
+/// and still reach the others. The first member is the leader: pausing
+/// lines everyone up on its position, and [`CastGroup::resync`] seeks drifting members
+/// back to it. Each member's state is tracked from its own status messages.
+pub struct CastGroup {
+    members: Vec<Member>,
+    states: watch::Sender<Vec<MemberState>>,
+}
+
+impl CastGroup {
+    /// Creates a group from connected clients, each with a display name.
+    pub fn new(clients: Vec<(String, CastClient)>) -> Self {
+        let (states, _) = watch::channel(
+            clients
+                .iter()
+                .map(|(name, _)| MemberState::new(name.clone()))
+                .collect(),
+        );
+        let members = clients
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, client))| Member {
+                receiver: ReceiverController::new(&client),
+                app: DefaultMediaReceiver::new(&client),
+                // Subscribe now so nothing reported after `new` returns is missed.
+                task: tokio::spawn(track_member(
+                    client.device_events(),
+                    client.connection_events(),
+                    index,
+                    states.clone(),
+                )),
+            })
+            .collect();
+        Self { members, states }
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The latest state of every member, in the order they were added.
+    pub fn states(&self) -> Vec<MemberState> {
+        self.states.borrow().clone()
+    }
+
+    /// Watches member states as the devices report changes.
+    pub fn watch_states(&self) -> watch::Receiver<Vec<MemberState>> {
+        self.states.subscribe()
+    }
+
+    /// The state of the member the others follow.
+    pub fn leader(&self) -> Option<MemberState> {
+        self.states.borrow().first().cloned()
+    }
+
+    /// Launches the Default Media Receiver on every member.
+    pub async fn launch(&mut self) -> Result<(), CastError> {
+        let results = join_all(self.members.iter_mut().map(|m| m.app.launch())).await;
+        first_error(results)
+    }
+
+    /// Loads the same media on every member.
+    pub async fn load(
+        &self,
+        media: MediaInformation,
+        autoplay: bool,
+        current_time: f32,
+        active_track_ids: Option<Vec<i32>>,
+    ) -> Result<(), CastError> {
+        let results = join_all(self.members.iter().map(|m| {
+            m.app
+                .load(media.clone(), autoplay, current_time, active_track_ids.clone())
+        }))
+        .await;
+        first_error(results)
+    }
+
+    pub async fn play(&self) -> Result<(), CastError> {
+        let sessions = self.session_ids();
+        let results = join_all(self.members.iter().zip(sessions).map(|(m, sid)| async move {
+            m.app.play(sid?).await
+        }))
+        .await;
+        first_error(results)
+    }
+
+    /// Pauses every member, then seeks them all to where the leader paused so they resume
+    /// together.
+    pub async fn pause(&self) -> Result<(), CastError> {
+        let position = self.leader().map(|l| l.position());
+        let sessions = self.session_ids();
+        let results = join_all(self.members.iter().zip(sessions).map(|(m, sid)| async move {
+            m.app.pause(sid?).await
+        }))
+        .await;
+        first_error(results)?;
+        match position {
+            Some(position) => self.seek(position).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn seek(&self, time: f32) -> Result<(), CastError> {
+        let sessions = self.session_ids();
+        let results = join_all(self.members.iter().zip(sessions).map(|(m, sid)| async move {
+            m.app.seek(sid?, time).await
+        }))
+        .await;
+        first_error(results)
+    }
+
+    pub async fn stop(&self) -> Result<(), CastError> {
+        let sessions = self.session_ids();
+        let results = join_all(self.members.iter().zip(sessions).map(|(m, sid)| async move {
+            m.app.stop(sid?).await
+        }))
+        .await;
+        first_error(results)
+    }
+
+    /// Sets the device volume of every member.
+    pub async fn set_volume(&self, level: f32) -> Result<(), CastError> {
+        let results = join_all(self.members.iter().map(|m| m.receiver.set_volume(level))).await;
+        first_error(results)
+    }
+
+    pub async fn set_mute(&self, muted: bool) -> Result<(), CastError> {
+        let results = join_all(self.members.iter().map(|m| m.receiver.set_mute(muted))).await;
+        first_error(results)
+    }
+
+    /// Seeks every playing member that is more than `max_drift` seconds away from the leader
+    /// back to the leader's position. Returns how many were corrected.
+    pub async fn resync(&self, max_drift: f32) -> Result<usize, CastError> {
+        let states = self.states();
+        let Some(leader) = states.first().filter(|l| l.is_playing() && l.connected) else {
+            return Ok(0);
+        };
+        let target = leader.position();
+        let drifting: Vec<_> = self
+            .members
+            .iter()
+            .zip(&states)
+            .skip(1)
+            .filter(|(_, s)| s.is_playing() && s.connected)
+            .filter(|(_, s)| (s.position() - target).abs() > max_drift)
+            .filter_map(|(m, s)| Some((m, s, s.media_session_id?)))
+            .collect();
+        for (_, state, _) in &drifting {
+            log::info!(
+                "{} drifted {:.1}s from {}; seeking to {:.1}",
+                state.name,
+                state.position() - target,
+                leader.name,
+                target
+            );
+        }
+        let results = join_all(drifting.iter().map(|(m, _, sid)| m.app.seek(*sid, target))).await;
+        first_error(results)?;
+        Ok(drifting.len())
+    }
+
+    /// Each member's media session, or the error to report for it if it has none yet.
+    fn session_ids(&self) -> Vec<Result<i32, CastError>> {
+        self.states
+            .borrow()
+            .iter()
+            .map(|s| {
+                s.media_session_id
+                    .ok_or_else(|| CastError::NoMediaSession(s.name.clone()))
+            })
+            .collect()
+    }
+}
+
+impl Drop for CastGroup {
+    fn drop(&mut self) {
+        for member in &self.members {
+            member.task.abort();
+        }
+    }
+}
+
+/// Keeps the state of member `index` up to date from its client's events.
+async fn track_member(
+    mut events: broadcast::Receiver<DeviceEvent>,
+    mut connection_events: broadcast::Receiver<ConnectionEvent>,
+    index: usize,
+    states: watch::Sender<Vec<MemberState>>,
+) {
+    let update = |f: &dyn Fn(&mut MemberState)| {
+        states.send_modify(|states| {
+            if let Some(state) = states.get_mut(index) {
+                f(state)
+            }
+        })
+    };
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(DeviceEvent::MediaStatus { status, .. }) => {
+                    let Some(s) = status.first() else { continue };
+                    update(&|state| {
+                        state.player_state = s.player_state.clone();
+                        state.idle_reason = s.idle_reason.clone();
+                        state.current_time = s.current_time;
+                        state.updated_at = Instant::now();
+                        state.media_session_id = Some(s.media_session_id);
+                        if let Some(duration) = s.duration().filter(|d| *d > 0.0) {
+                            state.duration = Some(duration);
+                        }
+                    });
+                }
+                Ok(DeviceEvent::ReceiverStatus { status, .. }) => {
+                    let Some(volume) = status.volume else { continue };
+                    update(&|state| {
+                        state.volume_level = volume.level.or(state.volume_level);
+                        state.is_muted = volume.muted.unwrap_or(state.is_muted);
+                    });
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            event = connection_events.recv() => match event {
+                Ok(ConnectionEvent::Disconnected { .. }) => update(&|state| state.connected = false),
+                Ok(ConnectionEvent::Connected | ConnectionEvent::Reconnected) => {
+                    update(&|state| state.connected = true)
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+/// Every member has been sent the command; report the first failure, if any.
+fn first_error(results: Vec<Result<(), CastError>>) -> Result<(), CastError> {
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::CastMessage;
+    use crate::protocol::{media, receiver};
+    use serde_json::{json, Value};
+    use tokio::sync::mpsc;
+
+    fn media_status(player_state: &str, current_time: f32) -> CastMessage {
+        session_status(3, player_state, current_time)
+    }
+
+    fn session_status(media_session_id: i32, player_state: &str, current_time: f32) -> CastMessage {
+        CastMessage {
+            protocol_version: 0,
+            source_id: "web-1".to_string(),
+            destination_id: "*".to_string(),
+            namespace: media::NAMESPACE.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(
+                json!({
+                    "type": "MEDIA_STATUS",
+                    "requestId": 0,
+                    "status": [{
+                        "mediaSessionId": media_session_id,
+                        "playbackRate": 1,
+                        "playerState": player_state,
+                        "currentTime": current_time,
+                        "supportedMediaCommands": 15,
+                        "media": {"contentId": "http://x/a.mp4", "duration": 600.0}
+                    }]
+                })
+                .to_string(),
+            ),
+            payload_binary: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_member_states_follow_each_device() {
+        let (kitchen, _kitchen_rx) = CastClient::new_mock();
+        let (lounge, _lounge_rx) = CastClient::new_mock();
+        let group = CastGroup::new(vec![
+            ("Kitchen".to_string(), kitchen.clone()),
+            ("Lounge".to_string(), lounge.clone()),
+        ]);
+        let mut watch = group.watch_states();
+
+        lounge.inject_message(&media_status("PAUSED", 42.0));
+        let states = watch
+            .wait_for(|s| s[1].player_state == "PAUSED")
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(states[0].name, "Kitchen");
+        assert_eq!(states[0].player_state, "IDLE");
+        assert_eq!(states[1].current_time, 42.0);
+        assert_eq!(states[1].position(), 42.0);
+        assert_eq!(states[1].duration, Some(600.0));
+        assert_eq!(states[1].media_session_id, Some(3));
+    }
+
+    /// Answers the LAUNCH the client sends like a device starting the Default Media
+    /// Receiver on transport `web-1`.
+    async fn answer_launch(client: &CastClient, rx: &mut mpsc::Receiver<CastMessage>) {
+        let request = next_command(rx, "LAUNCH").await;
+        client.inject_message(&CastMessage {
+            protocol_version: 0,
+            source_id: "receiver-0".to_string(),
+            destination_id: "*".to_string(),
+            namespace: receiver::NAMESPACE.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(
+                json!({
+                    "type": "RECEIVER_STATUS",
+                    "requestId": request["requestId"],
+                    "status": {"applications": [{
+                        "appId": "CC1AD845",
+                        "displayName": "Default Media Receiver",
+                        "sessionId": "session-1",
+                        "transportId": "web-1",
+                        "statusText": "",
+                        "isIdleScreen": false,
+                        "namespaces": [{"name": media::NAMESPACE}]
+                    }]}
+                })
+                .to_string(),
+            ),
+            payload_binary: None,
+        });
+    }
+
+    /// Waits for the next message of type `kind` the client sends, skipping the others.
+    async fn next_command(rx: &mut mpsc::Receiver<CastMessage>, kind: &str) -> Value {
+        loop {
+            let msg = rx.recv().await.unwrap();
+            let payload: Value = serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+            if payload["type"] == kind {
+                return payload;
+            }
+        }
+    }
+
+    /// A launched group of two mock members, with what each sends.
+    async fn launched_group() -> (CastGroup, Vec<(CastClient, mpsc::Receiver<CastMessage>)>) {
+        let (kitchen, mut kitchen_rx) = CastClient::new_mock();
+        let (lounge, mut lounge_rx) = CastClient::new_mock();
+        let mut group = CastGroup::new(vec![
+            ("Kitchen".to_string(), kitchen.clone()),
+            ("Lounge".to_string(), lounge.clone()),
+        ]);
+        let (launched, (), ()) = tokio::join!(
+            group.launch(),
+            answer_launch(&kitchen, &mut kitchen_rx),
+            answer_launch(&lounge, &mut lounge_rx),
+        );
+        launched.unwrap();
+        (group, vec![(kitchen, kitchen_rx), (lounge, lounge_rx)])
+    }
+
+    #[tokio::test]
+    async fn test_commands_go_to_each_members_session() {
+        let (group, mut members) = launched_group().await;
+        let mut watch = group.watch_states();
+        members[0].0.inject_message(&session_status(3, "PAUSED", 10.0));
+        members[1].0.inject_message(&session_status(8, "PAUSED", 10.0));
+        watch
+            .wait_for(|s| s.iter().all(|m| m.media_session_id.is_some()))
+            .await
+            .unwrap();
+
+        group.play().await.unwrap();
+        assert_eq!(next_command(&mut members[0].1, "PLAY").await["mediaSessionId"], 3);
+        assert_eq!(next_command(&mut members[1].1, "PLAY").await["mediaSessionId"], 8);
+
+        group.seek(42.0).await.unwrap();
+        for ((_, rx), session) in members.iter_mut().zip([3, 8]) {
+            let seek = next_command(rx, "SEEK").await;
+            assert_eq!(seek["mediaSessionId"], session);
+            assert_eq!(seek["currentTime"], 42.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_member_without_session_is_reported() {
+        let (group, mut members) = launched_group().await;
+        let mut watch = group.watch_states();
+        members[0].0.inject_message(&session_status(3, "PAUSED", 10.0));
+        watch.wait_for(|s| s[0].media_session_id.is_some()).await.unwrap();
+
+        // The lounge has not reported a session, so nothing is guessed for it.
+        let err = group.play().await.unwrap_err();
+        assert!(matches!(err, CastError::NoMediaSession(name) if name == "Lounge"));
+        assert_eq!(next_command(&mut members[0].1, "PLAY").await["mediaSessionId"], 3);
+        while let Ok(msg) = members[1].1.try_recv() {
+            assert!(!msg.payload_utf8.unwrap().contains("\"PLAY\""));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_member_is_not_resynced() {
+        let (group, mut members) = launched_group().await;
+        let mut watch = group.watch_states();
+        members[0].0.inject_message(&session_status(3, "PLAYING", 100.0));
+        members[1].0.inject_message(&session_status(8, "PLAYING", 50.0));
+        members[1].0.inject_connection_event(ConnectionEvent::Disconnected {
+            reason: "gone".to_string(),
+        });
+        watch
+            .wait_for(|s| s[1].current_time == 50.0 && !s[1].connected)
+            .await
+            .unwrap();
+        assert_eq!(group.resync(DEFAULT_MAX_DRIFT).await.unwrap(), 0);
+
+        // Back online and still far behind, so it is corrected.
+        members[1].0.inject_connection_event(ConnectionEvent::Reconnected);
+        watch.wait_for(|s| s[1].connected).await.unwrap();
+        assert_eq!(group.resync(DEFAULT_MAX_DRIFT).await.unwrap(), 1);
+        assert_eq!(next_command(&mut members[1].1, "SEEK").await["mediaSessionId"], 8);
+    }
+
+    #[tokio::test]
+    async fn test_commands_fail_until_launched() {
+        let (client, _rx) = CastClient::new_mock();
+        let group = CastGroup::new(vec![("Kitchen".to_string(), client)]);
+        assert!(group.play().await.is_err());
+        // Nothing is playing, so there is nothing to resync.
+        assert_eq!(group.resync(DEFAULT_MAX_DRIFT).await.unwrap(), 0);
+    }
+}
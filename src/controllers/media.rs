@@ -1,7 +1,10 @@
-use crate::client::CastClient;
+use crate::client::{CastClient, CastEvent};
 use crate::error::CastError;
 use crate::proto::CastMessage;
-use crate::protocol::media::{self, MediaInformation, MediaRequest};
+use crate::protocol::media::{
+    self, MediaInformation, MediaRequest, MediaResponse, MediaStatus, QueueItem, RepeatMode,
+    TextTrackStyle, Volume,
+};
 use std::collections::VecDeque;
 
 #[derive(Debug, Clone)]
@@ -45,6 +48,11 @@ impl MediaController {
         }
     }
 
+    /// The transport ID of the application this controller talks to.
+    pub fn transport_id(&self) -> &str {
+        &self.transport_id
+    }
+
     /// Loads media content.
     pub async fn load(
         &self,
@@ -53,7 +61,7 @@ impl MediaController {
         current_time: f32,
         active_track_ids: Option<Vec<i32>>,
     ) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Randomize
+        let request_id = self.client.next_request_id();
         let msg = MediaRequest::Load {
             request_id,
             session_id: self.transport_id.clone(),
@@ -66,7 +74,7 @@ impl MediaController {
     }
 
     pub async fn play(&self, media_session_id: i32) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = MediaRequest::Play {
             request_id,
             media_session_id,
@@ -75,7 +83,7 @@ impl MediaController {
     }
 
     pub async fn pause(&self, media_session_id: i32) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = MediaRequest::Pause {
             request_id,
             media_session_id,
@@ -97,7 +105,7 @@ impl MediaController {
     }
 
     pub async fn stop(&self, media_session_id: i32) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = MediaRequest::Stop {
             request_id,
             media_session_id,
@@ -106,7 +114,7 @@ impl MediaController {
     }
 
     pub async fn seek(&self, media_session_id: i32, time: f32) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = MediaRequest::Seek {
             request_id,
             media_session_id,
@@ -116,23 +124,298 @@ impl MediaController {
         self.send_media_request(msg).await
     }
 
-    pub async fn set_volume(&self, _media_session_id: i32, _level: f32) -> Result<(), CastError> {
-        Ok(())
+    /// Requests the current media status; the reply arrives as a `MEDIA_STATUS` event.
+    pub async fn get_status(&self, media_session_id: Option<i32>) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::GetStatus {
+            request_id,
+            media_session_id,
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Asks for the media status and waits for it; empty when nothing is loaded.
+    pub async fn fetch_status(
+        &self,
+        media_session_id: Option<i32>,
+    ) -> Result<Vec<MediaStatus>, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .request_media(MediaRequest::GetStatus {
+                request_id,
+                media_session_id,
+            })
+            .await?;
+        match reply {
+            MediaResponse::MediaStatus { status, .. } => Ok(status),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    /// Sets the stream volume of the session (the device volume is left alone).
+    pub async fn set_volume(&self, media_session_id: i32, level: f32) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::Volume {
+            request_id,
+            media_session_id,
+            volume: Volume {
+                level: Some(level),
+                muted: None,
+            },
+        };
+        self.send_media_request(msg).await
     }
 
     pub async fn set_stream_mute(
         &self,
-        _media_session_id: i32,
-        _muted: bool,
+        media_session_id: i32,
+        muted: bool,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::Volume {
+            request_id,
+            media_session_id,
+            volume: Volume {
+                level: None,
+                muted: Some(muted),
+            },
+        };
+        self.send_media_request(msg).await
+    }
+
+    pub async fn set_playback_rate(
+        &self,
+        media_session_id: i32,
+        playback_rate: f32,
     ) -> Result<(), CastError> {
-        Ok(())
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::SetPlaybackRate {
+            request_id,
+            media_session_id,
+            playback_rate,
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Enables exactly the given tracks (e.g. a subtitle track); pass none to disable all.
+    pub async fn set_active_tracks(
+        &self,
+        media_session_id: i32,
+        active_track_ids: Vec<i32>,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::EditTracksInfo {
+            request_id,
+            media_session_id,
+            active_track_ids: Some(active_track_ids),
+            text_track_style: None,
+        };
+        self.send_media_request(msg).await
+    }
+
+    pub async fn set_text_track_style(
+        &self,
+        media_session_id: i32,
+        style: TextTrackStyle,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::EditTracksInfo {
+            request_id,
+            media_session_id,
+            active_track_ids: None,
+            text_track_style: Some(style),
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Replaces the queue with `items` and starts playing at `start_index`, `current_time`
+    /// seconds in.
+    ///
+    /// Plain [`MediaInformation`]s convert into items with `QueueItem::from`. Returns the
+    /// media status the receiver answers with, which carries the new session's ID.
+    pub async fn queue_load(
+        &self,
+        items: Vec<QueueItem>,
+        start_index: Option<i32>,
+        repeat_mode: Option<RepeatMode>,
+        current_time: Option<f32>,
+    ) -> Result<Vec<MediaStatus>, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .request_media(MediaRequest::QueueLoad {
+                request_id,
+                items,
+                start_index,
+                repeat_mode,
+                current_time,
+            })
+            .await?;
+        match reply {
+            MediaResponse::MediaStatus { status, .. } => Ok(status),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    /// Inserts `items` before the item with ID `insert_before`, or appends them.
+    pub async fn queue_insert(
+        &self,
+        media_session_id: i32,
+        items: Vec<QueueItem>,
+        insert_before: Option<i32>,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueueInsert {
+            request_id,
+            media_session_id,
+            items,
+            insert_before,
+        };
+        self.send_media_request(msg).await
+    }
+
+    pub async fn queue_remove(
+        &self,
+        media_session_id: i32,
+        item_ids: Vec<i32>,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueueRemove {
+            request_id,
+            media_session_id,
+            item_ids,
+        };
+        self.send_media_request(msg).await
+    }
+
+    pub async fn queue_reorder(
+        &self,
+        media_session_id: i32,
+        item_ids: Vec<i32>,
+        insert_before: Option<i32>,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueueReorder {
+            request_id,
+            media_session_id,
+            item_ids,
+            insert_before,
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Moves `jump` items forward (or backward if negative) in the queue.
+    pub async fn queue_jump(&self, media_session_id: i32, jump: i32) -> Result<(), CastError> {
+        self.queue_update(media_session_id, Some(jump), None, None)
+            .await
+    }
+
+    pub async fn queue_set_repeat_mode(
+        &self,
+        media_session_id: i32,
+        repeat_mode: RepeatMode,
+    ) -> Result<(), CastError> {
+        self.queue_update(media_session_id, None, Some(repeat_mode), None)
+            .await
+    }
+
+    pub async fn queue_shuffle(&self, media_session_id: i32) -> Result<(), CastError> {
+        self.queue_update(media_session_id, None, None, Some(true))
+            .await
+    }
+
+    pub async fn queue_next(&self, media_session_id: i32) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueueNext {
+            request_id,
+            media_session_id,
+        };
+        self.send_media_request(msg).await
+    }
+
+    pub async fn queue_prev(&self, media_session_id: i32) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueuePrev {
+            request_id,
+            media_session_id,
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Fetches the IDs of every item in the queue, in order.
+    pub async fn queue_get_item_ids(&self, media_session_id: i32) -> Result<Vec<i32>, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .request_media(MediaRequest::QueueGetItemIds {
+                request_id,
+                media_session_id,
+            })
+            .await?;
+        match reply {
+            MediaResponse::QueueItemIds { item_ids, .. } => Ok(item_ids),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    /// Fetches the full queue items with the given IDs.
+    pub async fn queue_get_items(
+        &self,
+        media_session_id: i32,
+        item_ids: Vec<i32>,
+    ) -> Result<Vec<QueueItem>, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .request_media(MediaRequest::QueueGetItems {
+                request_id,
+                media_session_id,
+                item_ids,
+            })
+            .await?;
+        match reply {
+            MediaResponse::QueueItems { items, .. } => Ok(items),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    async fn queue_update(
+        &self,
+        media_session_id: i32,
+        jump: Option<i32>,
+        repeat_mode: Option<RepeatMode>,
+        shuffle: Option<bool>,
+    ) -> Result<(), CastError> {
+        let request_id = self.client.next_request_id();
+        let msg = MediaRequest::QueueUpdate {
+            request_id,
+            media_session_id,
+            current_item_id: None,
+            jump,
+            repeat_mode,
+            shuffle,
+            current_time: None,
+        };
+        self.send_media_request(msg).await
+    }
+
+    /// Sends a media request and waits for the receiver's reply to it.
+    ///
+    /// Build the request with an ID from [`CastClient::next_request_id`].
+    pub async fn request(&self, request: MediaRequest) -> Result<CastEvent, CastError> {
+        self.client
+            .request(&self.transport_id, media::NAMESPACE, &request)
+            .await
+    }
+
+    /// Like [`MediaController::request`], decoding the reply as a [`MediaResponse`].
+    async fn request_media(&self, request: MediaRequest) -> Result<MediaResponse, CastError> {
+        let reply = self.request(request).await?;
+        Ok(serde_json::from_str(&reply.payload)?)
     }
 
     async fn send_media_request(&self, request: MediaRequest) -> Result<(), CastError> {
         let payload = serde_json::to_string(&request).unwrap();
         let msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.client.sender_id().to_string(),
             destination_id: self.transport_id.clone(),
             namespace: media::NAMESPACE.to_string(),
             payload_type: 0,
@@ -142,3 +425,130 @@ impl MediaController {
         self.client.send_message(msg).await
     }
 }
+
+fn unexpected_reply(reply: MediaResponse) -> CastError {
+    match reply {
+        MediaResponse::InvalidRequest { reason, .. } => CastError::Protocol(format!(
+            "Invalid request: {}",
+            reason.unwrap_or_default()
+        )),
+        other => CastError::Protocol(format!("Unexpected reply: {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Request;
+
+    #[tokio::test]
+    async fn test_set_stream_mute_sends_volume() {
+        let (client, mut rx) = CastClient::new_mock();
+        let media = MediaController::new(&client, "web-7");
+
+        media.set_stream_mute(3, true).await.unwrap();
+
+        let msg = rx.recv().await.unwrap();
+        assert_eq!(msg.destination_id, "web-7");
+        assert_eq!(msg.namespace, media::NAMESPACE);
+        let sent: MediaRequest = serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+        assert!(matches!(
+            sent,
+            MediaRequest::Volume {
+                media_session_id: 3,
+                volume: Volume {
+                    level: None,
+                    muted: Some(true),
+                },
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_queue_load_sends_items() {
+        let (client, mut rx) = CastClient::new_mock();
+        let media = MediaController::new(&client, "web-7");
+        let info = MediaInformation {
+            content_id: "http://example.com/a.mp4".to_string(),
+            stream_type: "BUFFERED".to_string(),
+            content_type: "video/mp4".to_string(),
+            duration: None,
+            metadata: None,
+            tracks: None,
+        };
+
+        let device = async {
+            let msg = rx.recv().await.unwrap();
+            let sent: MediaRequest =
+                serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+            let request_id = sent.request_id();
+            let MediaRequest::QueueLoad {
+                items, repeat_mode, ..
+            } = sent
+            else {
+                panic!("expected QUEUE_LOAD, got {:?}", sent);
+            };
+            assert_eq!(items, vec![QueueItem::new(info.clone())]);
+            assert_eq!(repeat_mode, Some(RepeatMode::RepeatAll));
+            let reply = serde_json::json!({
+                "type": "MEDIA_STATUS",
+                "requestId": request_id,
+                "status": [{
+                    "mediaSessionId": 4,
+                    "playbackRate": 1,
+                    "playerState": "BUFFERING",
+                    "currentTime": 0,
+                    "supportedMediaCommands": 15,
+                    "currentItemId": 11
+                }]
+            });
+            client.inject_message(&CastMessage {
+                protocol_version: 0,
+                source_id: "web-7".to_string(),
+                destination_id: client.sender_id().to_string(),
+                namespace: media::NAMESPACE.to_string(),
+                payload_type: 0,
+                payload_utf8: Some(reply.to_string()),
+                payload_binary: None,
+            });
+        };
+
+        let (status, ()) = tokio::join!(
+            media.queue_load(vec![info.clone().into()], Some(0), Some(RepeatMode::RepeatAll), None),
+            device
+        );
+        let status = status.unwrap();
+        assert_eq!(status[0].media_session_id, 4);
+        assert_eq!(status[0].current_item_id, Some(11));
+    }
+
+    #[tokio::test]
+    async fn test_queue_get_item_ids_awaits_reply() {
+        let (client, mut rx) = CastClient::new_mock();
+        let media = MediaController::new(&client, "web-7");
+
+        let device = async {
+            let msg = rx.recv().await.unwrap();
+            let sent: MediaRequest =
+                serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+            assert!(matches!(sent, MediaRequest::QueueGetItemIds { .. }));
+            let reply = MediaResponse::QueueItemIds {
+                request_id: sent.request_id(),
+                item_ids: vec![1, 2, 3],
+            };
+            client.inject_message(&CastMessage {
+                protocol_version: 0,
+                source_id: "web-7".to_string(),
+                destination_id: client.sender_id().to_string(),
+                namespace: media::NAMESPACE.to_string(),
+                payload_type: 0,
+                payload_utf8: Some(serde_json::to_string(&reply).unwrap()),
+                payload_binary: None,
+            });
+        };
+
+        let (item_ids, ()) = tokio::join!(media.queue_get_item_ids(3), device);
+        assert_eq!(item_ids.unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -1,4 +1,7 @@
+pub mod application;
 pub mod default_media_receiver;
+pub mod group;
 pub mod media;
+pub mod namespace;
 pub mod receiver;
 pub mod tui;
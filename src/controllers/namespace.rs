@@ -0,0 +1,125 @@
+use crate::client::{CastClient, CastEvent, NamespaceMessage, Payload};
+use crate::error::CastError;
+use crate::protocol::Request;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Channel for a custom `urn:x-cast:` namespace.
+///
+/// Sends string, JSON or binary payloads to one destination (usually the transport ID
+/// of a custom receiver app) and receives everything the device sends on the namespace.
+pub struct NamespaceChannel {
+    client: CastClient,
+    namespace: String,
+    destination_id: String,
+}
+
+impl NamespaceChannel {
+    /// Creates a channel for `namespace` on `destination_id`.
+    pub fn new(client: &CastClient, namespace: &str, destination_id: &str) -> Self {
+        Self {
+            client: client.clone(),
+            namespace: namespace.to_string(),
+            destination_id: destination_id.to_string(),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn destination_id(&self) -> &str {
+        &self.destination_id
+    }
+
+    /// Sends a string payload.
+    pub async fn send_text(&self, text: &str) -> Result<(), CastError> {
+        self.send(Payload::Text(text.to_string())).await
+    }
+
+    /// Serializes `value` to JSON and sends it as a string payload.
+    pub async fn send_json<T: Serialize>(&self, value: &T) -> Result<(), CastError> {
+        self.send(Payload::Text(serde_json::to_string(value)?)).await
+    }
+
+    /// Sends a binary payload.
+    pub async fn send_binary(&self, data: &[u8]) -> Result<(), CastError> {
+        self.send(Payload::Binary(data.to_vec())).await
+    }
+
+    /// Sends a JSON request carrying `requestId` and waits for the reply with the same ID.
+    pub async fn request<T: Request>(&self, request: &T) -> Result<CastEvent, CastError> {
+        self.client
+            .request(&self.destination_id, &self.namespace, request)
+            .await
+    }
+
+    /// Subscribes to messages received on this namespace, from any source.
+    pub fn subscribe(&self) -> broadcast::Receiver<NamespaceMessage> {
+        self.client.subscribe_namespace(&self.namespace)
+    }
+
+    async fn send(&self, payload: Payload) -> Result<(), CastError> {
+        self.client
+            .send_payload(&self.destination_id, &self.namespace, payload)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::CastMessage;
+
+    const NAMESPACE: &str = "urn:x-cast:com.example.game";
+
+    #[tokio::test]
+    async fn test_channel_sends_text_and_binary() {
+        let (client, mut rx) = CastClient::new_mock();
+        let channel = client.channel(NAMESPACE, "web-7");
+
+        channel.send_json(&serde_json::json!({"move": "e4"})).await.unwrap();
+        channel.send_binary(&[0xde, 0xad]).await.unwrap();
+
+        let text = rx.recv().await.unwrap();
+        assert_eq!(text.destination_id, "web-7");
+        assert_eq!(text.namespace, NAMESPACE);
+        assert_eq!(text.payload_type, 0);
+        assert_eq!(text.payload_utf8.as_deref(), Some(r#"{"move":"e4"}"#));
+
+        let binary = rx.recv().await.unwrap();
+        assert_eq!(binary.payload_type, 1);
+        assert_eq!(binary.payload_utf8, None);
+        assert_eq!(binary.payload_binary, Some(vec![0xde, 0xad]));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_only_sees_its_namespace() {
+        let (client, _rx) = CastClient::new_mock();
+        let mut sub = client.channel(NAMESPACE, "web-7").subscribe();
+
+        client.inject_message(&CastMessage {
+            protocol_version: 0,
+            source_id: "web-7".to_string(),
+            destination_id: "sender-0".to_string(),
+            namespace: "urn:x-cast:com.example.other".to_string(),
+            payload_type: 0,
+            payload_utf8: Some("ignored".to_string()),
+            payload_binary: None,
+        });
+        client.inject_message(&CastMessage {
+            protocol_version: 0,
+            source_id: "web-7".to_string(),
+            destination_id: "sender-0".to_string(),
+            namespace: NAMESPACE.to_string(),
+            payload_type: 1,
+            payload_utf8: None,
+            payload_binary: Some(vec![1, 2, 3]),
+        });
+
+        let msg = sub.recv().await.unwrap();
+        assert_eq!(msg.source_id, "web-7");
+        assert_eq!(msg.payload, Payload::Binary(vec![1, 2, 3]));
+        assert!(sub.try_recv().is_err());
+    }
+}
@@ -1,12 +1,71 @@
-use crate::client::CastClient;
+use crate::client::{CastClient, CastEvent};
+use crate::controllers::media::MediaController;
 use crate::error::CastError;
 use crate::proto::CastMessage;
-use crate::protocol::connection::{self, Connection};
-use crate::protocol::receiver::{self, ReceiverRequest};
+use crate::protocol::media::{self, MediaStatus};
+use crate::protocol::receiver::{
+    self, AppAvailability, AppAvailabilityResponse, Application, ReceiverRequest,
+    ReceiverResponse, ReceiverStatusData,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How long the device may take to answer a LAUNCH; starting an app can be slow.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A media application started by another sender, joined without interrupting it.
+///
+/// Its playback commands go to the media session that was running when it was joined.
+pub struct JoinedSession {
+    pub application: Application,
+    /// What the application is playing; `None` when it is idle.
+    pub status: Option<MediaStatus>,
+    media: MediaController,
+}
+
+impl JoinedSession {
+    /// The joined media session, or `None` if the application was idle.
+    pub fn media_session_id(&self) -> Option<i32> {
+        self.status.as_ref().map(|s| s.media_session_id)
+    }
+
+    /// The controller for the application's transport, for requests not wrapped here.
+    pub fn media(&self) -> &MediaController {
+        &self.media
+    }
+
+    pub async fn play(&self) -> Result<(), CastError> {
+        self.media.play(self.session()?).await
+    }
+
+    pub async fn pause(&self) -> Result<(), CastError> {
+        self.media.pause(self.session()?).await
+    }
+
+    pub async fn stop(&self) -> Result<(), CastError> {
+        self.media.stop(self.session()?).await
+    }
+
+    pub async fn seek(&self, time: f32) -> Result<(), CastError> {
+        self.media.seek(self.session()?, time).await
+    }
+
+    /// Sets the volume of this stream only; the device volume is left alone.
+    pub async fn set_stream_volume(&self, level: f32) -> Result<(), CastError> {
+        self.media.set_volume(self.session()?, level).await
+    }
+
+    fn session(&self) -> Result<i32, CastError> {
+        self.media_session_id()
+            .ok_or_else(|| CastError::Protocol("Nothing is playing in the joined session".into()))
+    }
+}
 
 /// Controller for the Receiver namespace (Platform).
 ///
-/// Handles launching apps, stopping apps, and checking device status.
+/// Handles launching apps, stopping apps, and checking device status. The latest status
+/// the device reported is cached by the client and readable at any time.
 pub struct ReceiverController {
     client: CastClient,
 }
@@ -26,7 +85,7 @@ impl ReceiverController {
 
     /// Stops an application session.
     pub async fn stop_app(&self, session_id: &str) -> Result<(), CastError> {
-        let request_id = 1; // TODO: Randomize
+        let request_id = self.client.next_request_id();
         let msg = ReceiverRequest::Stop {
             request_id,
             session_id: session_id.to_string(),
@@ -36,55 +95,147 @@ impl ReceiverController {
 
     /// Requests the current status of the receiver (volume, running apps).
     pub async fn get_status(&self) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = ReceiverRequest::GetStatus { request_id };
         self.send_receiver_request(msg).await
     }
+
+    /// Asks the receiver for its status and waits for it; the cache is updated as well.
+    pub async fn fetch_status(&self) -> Result<ReceiverStatusData, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self.request(ReceiverRequest::GetStatus { request_id }).await?;
+        match serde_json::from_str(&reply.payload)? {
+            ReceiverResponse::ReceiverStatus { status, .. } => Ok(status),
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    /// Launches `app_id` and waits for the device to confirm.
+    ///
+    /// Returns the receiver status from the confirmation, or [`CastError::LaunchFailed`]
+    /// if the device answered with LAUNCH_ERROR.
+    pub async fn launch(&self, app_id: &str) -> Result<ReceiverStatusData, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .client
+            .clone()
+            .with_request_timeout(LAUNCH_TIMEOUT)
+            .request(
+                "receiver-0",
+                receiver::NAMESPACE,
+                &ReceiverRequest::Launch {
+                    app_id: app_id.to_string(),
+                    request_id,
+                },
+            )
+            .await?;
+        match serde_json::from_str(&reply.payload)? {
+            ReceiverResponse::ReceiverStatus { status, .. } => Ok(status),
+            ReceiverResponse::LaunchError { reason, .. }
+            | ReceiverResponse::InvalidRequest { reason, .. } => Err(CastError::LaunchFailed {
+                app_id: app_id.to_string(),
+                reason: reason.unwrap_or_else(|| "unknown".to_string()),
+            }),
+        }
+    }
+
+    /// Asks whether the device can run each of `app_ids`.
+    pub async fn get_app_availability(
+        &self,
+        app_ids: &[&str],
+    ) -> Result<HashMap<String, bool>, CastError> {
+        let request_id = self.client.next_request_id();
+        let reply = self
+            .request(ReceiverRequest::GetAppAvailability {
+                request_id,
+                app_ids: app_ids.iter().map(|id| id.to_string()).collect(),
+            })
+            .await?;
+        let response: AppAvailabilityResponse = serde_json::from_str(&reply.payload)?;
+        Ok(response
+            .availability
+            .into_iter()
+            .map(|(app_id, a)| (app_id, a == AppAvailability::AppAvailable))
+            .collect())
+    }
+
+    /// The last status the receiver reported, or `None` if it has not reported one yet.
+    pub fn status(&self) -> Option<ReceiverStatusData> {
+        self.client.receiver_status().borrow().clone()
+    }
+
+    /// Watches the receiver status as the device reports changes.
+    pub fn watch_status(&self) -> watch::Receiver<Option<ReceiverStatusData>> {
+        self.client.receiver_status()
+    }
     /// Joins an existing application session by connecting to its transport ID.
     pub async fn join_session(&self, transport_id: &str) -> Result<(), CastError> {
-        let msg = Connection::Connect;
-        let payload = serde_json::to_string(&msg).unwrap();
+        self.client.connect_transport(transport_id).await
+    }
 
-        let cast_msg = CastMessage {
-            protocol_version: 0,
-            source_id: "sender-0".to_string(),
-            destination_id: transport_id.to_string(),
-            namespace: connection::NAMESPACE.to_string(),
-            payload_type: 0,
-            payload_utf8: Some(payload),
-            payload_binary: None,
-        };
-        self.client.send_message(cast_msg).await
+    /// Takes over whatever media app is already running (e.g. one a phone started).
+    ///
+    /// Finds a running application that supports the media namespace, connects to it
+    /// and fetches its media status. Playback carries on untouched.
+    pub async fn join_media_session(&self) -> Result<JoinedSession, CastError> {
+        let status = self.fetch_status().await?;
+        let application = status
+            .applications
+            .into_iter()
+            .find(|a| a.supports_namespace(media::NAMESPACE))
+            .ok_or_else(|| {
+                CastError::Protocol("No running application supports media control".into())
+            })?;
+
+        self.join_session(&application.transport_id).await?;
+        let media = MediaController::new(&self.client, &application.transport_id);
+        let status = media.fetch_status(None).await?.into_iter().next();
+        Ok(JoinedSession {
+            application,
+            media,
+            status,
+        })
     }
     pub async fn set_volume(&self, level: f32) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = ReceiverRequest::SetVolume {
             request_id,
             volume: receiver::Volume {
                 level: Some(level),
                 muted: None,
+                ..Default::default()
             },
         };
         self.send_receiver_request(msg).await
     }
 
     pub async fn set_mute(&self, muted: bool) -> Result<(), CastError> {
-        let request_id = 1;
+        let request_id = self.client.next_request_id();
         let msg = ReceiverRequest::SetVolume {
             request_id,
             volume: receiver::Volume {
                 level: None,
                 muted: Some(muted),
+                ..Default::default()
             },
         };
         self.send_receiver_request(msg).await
     }
 
+    /// Sends a receiver request and waits for the platform's reply to it.
+    ///
+    /// Build the request with an ID from [`CastClient::next_request_id`].
+    pub async fn request(&self, request: ReceiverRequest) -> Result<CastEvent, CastError> {
+        self.client
+            .request("receiver-0", receiver::NAMESPACE, &request)
+            .await
+    }
+
     async fn send_receiver_request(&self, request: ReceiverRequest) -> Result<(), CastError> {
         let payload = serde_json::to_string(&request).unwrap();
         let msg = CastMessage {
             protocol_version: 0,
-            source_id: "sender-0".to_string(),
+            source_id: self.client.sender_id().to_string(),
             destination_id: "receiver-0".to_string(),
             namespace: receiver::NAMESPACE.to_string(),
             payload_type: 0,
@@ -94,3 +245,83 @@ impl ReceiverController {
         self.client.send_message(msg).await
     }
 }
+
+fn unexpected_reply(reply: ReceiverResponse) -> CastError {
+    CastError::Protocol(format!("Unexpected reply: {:?}", reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Request;
+    use serde_json::json;
+
+    fn status_message(request_id: i32) -> CastMessage {
+        let payload = json!({
+            "type": "RECEIVER_STATUS",
+            "requestId": request_id,
+            "status": {
+                "applications": [{
+                    "appId": "CC1AD845",
+                    "displayName": "Default Media Receiver",
+                    "sessionId": "session-1",
+                    "transportId": "web-7",
+                    "statusText": "Now Casting",
+                    "isIdleScreen": false,
+                    "namespaces": [{"name": "urn:x-cast:com.google.cast.media"}]
+                }],
+                "volume": {"level": 0.5, "muted": false, "controlType": "attenuation", "stepInterval": 0.05},
+                "isActiveInput": true,
+                "isStandBy": false
+            }
+        });
+        CastMessage {
+            protocol_version: 0,
+            source_id: "receiver-0".to_string(),
+            destination_id: "*".to_string(),
+            namespace: receiver::NAMESPACE.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(payload.to_string()),
+            payload_binary: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_cache_follows_device() {
+        let (client, _rx) = CastClient::new_mock();
+        let controller = ReceiverController::new(&client);
+        let mut watch = controller.watch_status();
+        assert!(controller.status().is_none());
+
+        client.inject_message(&status_message(0));
+
+        watch.changed().await.unwrap();
+        let status = controller.status().unwrap();
+        assert_eq!(status.is_active_input, Some(true));
+        assert_eq!(status.is_stand_by, Some(false));
+        let volume = status.volume.as_ref().unwrap();
+        assert_eq!(volume.control_type.as_deref(), Some("attenuation"));
+        assert_eq!(volume.step_interval, Some(0.05));
+        let app = status.foreground_application().unwrap();
+        assert_eq!(app.transport_id, "web-7");
+        assert!(app.supports_namespace("urn:x-cast:com.google.cast.media"));
+        assert_eq!(status.application("CC1AD845"), Some(app));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_status_waits_for_reply() {
+        let (client, mut rx) = CastClient::new_mock();
+        let controller = ReceiverController::new(&client);
+
+        let device = async {
+            let msg = rx.recv().await.unwrap();
+            let sent: ReceiverRequest =
+                serde_json::from_str(msg.payload_utf8.as_deref().unwrap()).unwrap();
+            client.inject_message(&status_message(sent.request_id()));
+        };
+
+        let (status, ()) = tokio::join!(controller.fetch_status(), device);
+        assert_eq!(status.unwrap().applications.len(), 1);
+        assert_eq!(controller.status().unwrap().applications[0].app_id, "CC1AD845");
+    }
+}
@@ -1,5 +1,6 @@
 //! Terminal User Interface (TUI) controller.
 
+use crate::controllers::group::MemberState;
 use crate::error::CastError;
 use crossterm::cursor::{Hide, MoveTo, Show};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
@@ -43,6 +44,8 @@ pub struct TuiState {
     pub device_name: String,
     pub animation_frame: usize,
     pub torrent_progress: Option<f32>,
+    /// Per-device state when casting to a group; empty for a single device.
+    pub members: Vec<MemberState>,
 }
 
 pub struct TuiController;
@@ -317,6 +320,33 @@ impl TuiController {
         )
         .ok();
 
+        // 9. Group members
+        for (i, member) in state.members.iter().enumerate() {
+            let line = format_member(member);
+            let m_y = v_y + 2 + i as u16;
+            if m_y >= rows.saturating_sub(2) {
+                break;
+            }
+            let m_x = (cols as usize).saturating_sub(line.len()) / 2;
+            let color = if !member.connected {
+                Color::Magenta
+            } else if member.is_playing() {
+                Color::Green
+            } else {
+                Color::Grey
+            };
+            execute!(
+                stdout,
+                MoveTo(0, m_y),
+                Clear(ClearType::CurrentLine),
+                MoveTo(m_x as u16, m_y),
+                SetForegroundColor(color),
+                Print(line),
+                ResetColor
+            )
+            .ok();
+        }
+
         // Footer
         let footer = " [Space] Toggle  [Arrow] Seek/Vol  [M] Mute  [Q] Quit ";
         let f_y = rows.saturating_sub(2);
@@ -335,6 +365,27 @@ impl TuiController {
     }
 }
 
+/// One line of the group listing: name, state, position and volume.
+fn format_member(member: &MemberState) -> String {
+    let status = if member.connected {
+        member.player_state.as_str()
+    } else {
+        "DISCONNECTED"
+    };
+    let volume = match (member.is_muted, member.volume_level) {
+        (true, _) => "Muted".to_string(),
+        (false, Some(v)) => format!("Vol {:.0}%", v * 100.0),
+        (false, None) => "Vol --%".to_string(),
+    };
+    format!(
+        " {:<20} {:<12} {} {} ",
+        member.name,
+        status,
+        format_duration(member.position()),
+        volume
+    )
+}
+
 fn format_duration(seconds: f32) -> String {
     let seconds = if seconds.is_nan() || seconds < 0.0 {
         0.0
@@ -456,6 +507,21 @@ fn render_projector_frame(frame: usize, _width: usize, height: usize) -> Vec<Str
     buffer
 }
 
+fn get_animation_frames(frame: usize, w: usize, h: usize) -> Vec<String> {
+    render_projector_frame(frame, w, h)
+}
+
+fn get_static_frame(w: usize, h: usize) -> Vec<String> {
+    render_projector_frame(0, w, h)
+}
+
+impl Drop for TuiController {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -472,6 +538,30 @@ mod tests {
         assert!(output.iter().any(|line| !line.is_empty()));
     }
 
+    #[test]
+    fn test_format_member() {
+        let mut member = MemberState {
+            name: "Kitchen".to_string(),
+            player_state: "PAUSED".to_string(),
+            idle_reason: None,
+            current_time: 83.0,
+            duration: None,
+            volume_level: Some(0.4),
+            is_muted: false,
+            media_session_id: Some(1),
+            connected: true,
+            updated_at: std::time::Instant::now(),
+        };
+        let line = format_member(&member);
+        assert!(line.contains("Kitchen"));
+        assert!(line.contains("PAUSED"));
+        assert!(line.contains("01:23"));
+        assert!(line.contains("Vol 40%"));
+
+        member.connected = false;
+        assert!(format_member(&member).contains("DISCONNECTED"));
+    }
+
     #[test]
     fn test_render_projector_frame_cycling() {
         let f0 = render_projector_frame(0, 80, 24);
@@ -481,18 +571,3 @@ mod tests {
         assert_ne!(f0, f1, "Frame 0 and Frame 1 should differ");
     }
 }
-
-fn get_animation_frames(frame: usize, w: usize, h: usize) -> Vec<String> {
-    render_projector_frame(frame, w, h)
-}
-
-fn get_static_frame(w: usize, h: usize) -> Vec<String> {
-    render_projector_frame(0, w, h)
-}
-
-impl Drop for TuiController {
-    fn drop(&mut self) {
-        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
-        let _ = disable_raw_mode();
-    }
-}
@@ -1,14 +1,22 @@
 use crate::error::CastError;
-use mdns_sd::{ServiceDaemon, ServiceEvent};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::HashMap;
+use std::fmt;
 use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+const SERVICE_TYPE: &str = "_googlecast._tcp.local.";
 
 /// Represents a discovered Cast device on the network.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CastDevice {
-    /// IP address of the device.
+    /// IP address of the device, IPv4 when it has one.
     pub ip: IpAddr,
+    /// Every address the device announced, IPv4 first.
+    pub addresses: Vec<IpAddr>,
     /// Port number (usually 8009).
     pub port: u16,
     /// Friendly name (e.g., "Living Room TV").
@@ -17,6 +25,108 @@ pub struct CastDevice {
     pub model_name: String,
     /// Unique UUID of the device.
     pub uuid: String,
+    /// What the device can do, from the `ca` TXT record.
+    pub capabilities: DeviceCapabilities,
+    /// What the device is doing (`rs`), e.g. the running app's name; `None` when idle.
+    pub status_text: Option<String>,
+    /// Path of the device icon on its port 8008 web server (`ic`).
+    pub icon_path: Option<String>,
+    /// Cast protocol version (`ve`).
+    pub version: Option<u32>,
+    /// The opaque `bs` record, a hardware identifier.
+    pub bs: Option<String>,
+}
+
+impl CastDevice {
+    /// Whether this is a speaker group set up in the Google Home app rather than a single device.
+    pub fn is_group(&self) -> bool {
+        self.capabilities.is_multizone_group() || self.model_name == "Google Cast Group"
+    }
+
+    /// URL of the device icon, if it advertises one.
+    pub fn icon_url(&self) -> Option<String> {
+        let path = self.icon_path.as_ref()?;
+        Some(match self.ip {
+            IpAddr::V4(ip) => format!("http://{}:8008{}", ip, path),
+            IpAddr::V6(ip) => format!("http://[{}]:8008{}", ip, path),
+        })
+    }
+}
+
+/// Capability bit flags advertised in the `ca` TXT record.
+///
+/// Flags combine with `|`, e.g. `DeviceCapabilities::VIDEO_OUT | DeviceCapabilities::AUDIO_OUT`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceCapabilities(u32);
+
+impl DeviceCapabilities {
+    pub const VIDEO_OUT: Self = Self(1 << 0);
+    pub const VIDEO_IN: Self = Self(1 << 1);
+    pub const AUDIO_OUT: Self = Self(1 << 2);
+    pub const AUDIO_IN: Self = Self(1 << 3);
+    pub const DEV_MODE: Self = Self(1 << 4);
+    pub const MULTIZONE_GROUP: Self = Self(1 << 5);
+
+    /// Parses the decimal `ca` value; anything unparsable means no capabilities.
+    pub fn from_txt(value: &str) -> Self {
+        Self(value.trim().parse().unwrap_or(0))
+    }
+
+    /// The raw `ca` value, including bits this type has no name for.
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether every flag in `flags` is set.
+    pub fn contains(&self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Whether the device has a screen; audio-only speakers and groups do not.
+    pub fn has_video(&self) -> bool {
+        self.contains(Self::VIDEO_OUT)
+    }
+
+    pub fn has_audio(&self) -> bool {
+        self.contains(Self::AUDIO_OUT)
+    }
+
+    /// Whether this is a multizone speaker group rather than a physical device.
+    pub fn is_multizone_group(&self) -> bool {
+        self.contains(Self::MULTIZONE_GROUP)
+    }
+}
+
+impl std::ops::BitOr for DeviceCapabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for DeviceCapabilities {
+    /// Lists the flags, e.g. `video out, audio out`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: [(DeviceCapabilities, &str); 6] = [
+            (DeviceCapabilities::VIDEO_OUT, "video out"),
+            (DeviceCapabilities::VIDEO_IN, "video in"),
+            (DeviceCapabilities::AUDIO_OUT, "audio out"),
+            (DeviceCapabilities::AUDIO_IN, "audio in"),
+            (DeviceCapabilities::DEV_MODE, "dev mode"),
+            (DeviceCapabilities::MULTIZONE_GROUP, "multizone group"),
+        ];
+        let names: Vec<&str> = NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
 }
 
 /// Discovers Cast devices on the local network using mDNS.
@@ -40,12 +150,11 @@ pub struct CastDevice {
 /// ```
 pub fn discover_devices(timeout: Duration) -> Result<Vec<CastDevice>, CastError> {
     let mdns = ServiceDaemon::new().map_err(|e| CastError::Protocol(e.to_string()))?;
-    let service_type = "_googlecast._tcp.local.";
     let receiver = mdns
-        .browse(service_type)
+        .browse(SERVICE_TYPE)
         .map_err(|e| CastError::Protocol(e.to_string()))?;
 
-    let mut devices = Vec::new();
+    let mut devices: Vec<CastDevice> = Vec::new();
     let start = Instant::now();
 
     loop {
@@ -56,30 +165,10 @@ pub fn discover_devices(timeout: Duration) -> Result<Vec<CastDevice>, CastError>
 
         if let Ok(event) = receiver.recv_timeout(remaining.unwrap()) {
             if let ServiceEvent::ServiceResolved(info) = event {
-                let friendly_name = info
-                    .get_properties()
-                    .get_property_val_str("fn")
-                    .unwrap_or("Unknown")
-                    .to_string();
-                let model_name = info
-                    .get_properties()
-                    .get_property_val_str("md")
-                    .unwrap_or("Unknown")
-                    .to_string();
-                let uuid = info
-                    .get_properties()
-                    .get_property_val_str("id")
-                    .unwrap_or("Unknown")
-                    .to_string();
-
-                if let Some(ip) = info.get_addresses().iter().next() {
-                    devices.push(CastDevice {
-                        ip: *ip,
-                        port: info.get_port(),
-                        friendly_name,
-                        model_name,
-                        uuid,
-                    });
+                if let Some(device) = device_from_info(&info) {
+                    // Devices re-announce themselves; keep the latest record of each.
+                    devices.retain(|d| d.uuid != device.uuid || device.uuid == "Unknown");
+                    devices.push(device);
                 }
             }
         } else {
@@ -87,56 +176,384 @@ pub fn discover_devices(timeout: Duration) -> Result<Vec<CastDevice>, CastError>
         }
     }
 
+    let _ = mdns.shutdown();
     Ok(devices)
 }
 
 /// Asynchronously discovers Cast devices on the local network.
 ///
-/// Returns a channel receiver that streams discovered devices.
-/// The discovery runs until the user stops receiving or for the default mDNS duration.
+/// Returns a channel receiver that streams discovered devices, again whenever one changes.
+/// Discovery stops once the receiver is dropped.
 pub fn discover_devices_async() -> Result<mpsc::Receiver<CastDevice>, CastError> {
-    let mdns = ServiceDaemon::new().map_err(|e| CastError::Protocol(e.to_string()))?;
-    let service_type = "_googlecast._tcp.local.";
-    let receiver = mdns
-        .browse(service_type)
-        .map_err(|e| CastError::Protocol(e.to_string()))?;
-
+    let mut browser = DeviceBrowser::start()?;
+    let mut events = browser.events();
+    // Subscribed first, so a device found in between shows up here or as an event.
+    let known = browser.devices();
     let (tx, rx) = mpsc::channel(32);
 
-    tokio::task::spawn_blocking(move || {
-        while let Ok(event) = receiver.recv() {
-            if let ServiceEvent::ServiceResolved(info) = event {
-                let friendly_name = info
-                    .get_properties()
-                    .get_property_val_str("fn")
-                    .unwrap_or("Unknown")
-                    .to_string();
-                let model_name = info
-                    .get_properties()
-                    .get_property_val_str("md")
-                    .unwrap_or("Unknown")
-                    .to_string();
-                let uuid = info
-                    .get_properties()
-                    .get_property_val_str("id")
-                    .unwrap_or("Unknown")
-                    .to_string();
-
-                if let Some(ip) = info.get_addresses().iter().next() {
-                    let device = CastDevice {
-                        ip: *ip,
-                        port: info.get_port(),
-                        friendly_name,
-                        model_name,
-                        uuid,
-                    };
-                    if tx.blocking_send(device).is_err() {
-                        break;
+    tokio::spawn(async move {
+        for device in known {
+            if tx.send(device).await.is_err() {
+                browser.stop();
+                return;
+            }
+        }
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(DiscoveryEvent::Added(device))
+                    | Ok(DiscoveryEvent::Updated { device, .. }) => {
+                        if tx.send(device).await.is_err() {
+                            break;
+                        }
                     }
-                }
+                    Ok(DiscoveryEvent::Removed(_)) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = tx.closed() => break,
             }
         }
+        browser.stop();
     });
 
     Ok(rx)
 }
+
+/// A change in the set of devices on the network.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiscoveryEvent {
+    /// A device appeared.
+    Added(CastDevice),
+    /// A known device changed its address, name, status or capabilities.
+    Updated {
+        previous: CastDevice,
+        device: CastDevice,
+    },
+    /// A device said goodbye or its records expired.
+    Removed(CastDevice),
+}
+
+/// Browses for Cast devices until stopped, keeping a live registry keyed by device UUID.
+///
+/// Devices that leave the network, whether they send a goodbye or just stop answering until
+/// their records' TTL runs out (mdns-sd reports both as `ServiceRemoved`), are dropped from the
+/// registry with a [`DiscoveryEvent::Removed`].
+///
+/// # Example
+///
+/// ```no_run
+/// use castru::discovery::{DeviceBrowser, DiscoveryEvent};
+///
+/// # async fn run() -> Result<(), castru::error::CastError> {
+/// let mut browser = DeviceBrowser::start()?;
+/// let mut events = browser.events();
+/// while let Ok(event) = events.recv().await {
+///     if let DiscoveryEvent::Added(device) = event {
+///         println!("{} is online", device.friendly_name);
+///     }
+/// }
+/// browser.stop();
+/// # Ok(())
+/// # }
+/// ```
+pub struct DeviceBrowser {
+    daemon: Option<ServiceDaemon>,
+    registry: Arc<Mutex<Registry>>,
+    events: broadcast::Sender<DiscoveryEvent>,
+    task: JoinHandle<()>,
+}
+
+impl DeviceBrowser {
+    /// Starts browsing in the background. Must be called within a Tokio runtime.
+    pub fn start() -> Result<Self, CastError> {
+        let daemon = ServiceDaemon::new().map_err(|e| CastError::Protocol(e.to_string()))?;
+        let receiver = daemon
+            .browse(SERVICE_TYPE)
+            .map_err(|e| CastError::Protocol(e.to_string()))?;
+        let registry = Arc::new(Mutex::new(Registry::default()));
+        let (events, _) = broadcast::channel(64);
+
+        let task = tokio::spawn({
+            let registry = registry.clone();
+            let events = events.clone();
+            async move {
+                while let Ok(event) = receiver.recv_async().await {
+                    let change = match event {
+                        ServiceEvent::ServiceResolved(info) => match device_from_info(&info) {
+                            Some(device) => registry
+                                .lock()
+                                .unwrap()
+                                .resolved(info.get_fullname(), device),
+                            None => None,
+                        },
+                        ServiceEvent::ServiceRemoved(_, fullname) => {
+                            registry.lock().unwrap().removed(&fullname)
+                        }
+                        _ => None,
+                    };
+                    if let Some(change) = change {
+                        log::debug!("Discovery: {:?}", change);
+                        let _ = events.send(change);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            daemon: Some(daemon),
+            registry,
+            events,
+            task,
+        })
+    }
+
+    /// Every device currently on the network.
+    pub fn devices(&self) -> Vec<CastDevice> {
+        self.registry.lock().unwrap().devices.values().cloned().collect()
+    }
+
+    /// The device with `uuid`, if it is on the network.
+    pub fn device(&self, uuid: &str) -> Option<CastDevice> {
+        self.registry.lock().unwrap().devices.get(uuid).cloned()
+    }
+
+    /// The device with the friendly name `name`, if it is on the network.
+    pub fn find_by_name(&self, name: &str) -> Option<CastDevice> {
+        self.registry
+            .lock()
+            .unwrap()
+            .devices
+            .values()
+            .find(|d| d.friendly_name == name)
+            .cloned()
+    }
+
+    /// Subscribes to devices being added, updated and removed from now on.
+    ///
+    /// Call [`DeviceBrowser::devices`] after subscribing to get the ones already known.
+    pub fn events(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stops browsing and shuts the mDNS daemon down. The registry keeps its last contents.
+    pub fn stop(&mut self) {
+        if let Some(daemon) = self.daemon.take() {
+            let _ = daemon.stop_browse(SERVICE_TYPE);
+            let _ = daemon.shutdown();
+        }
+        self.task.abort();
+    }
+}
+
+impl Drop for DeviceBrowser {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Known devices by UUID, and which mDNS instance announced each.
+#[derive(Default)]
+struct Registry {
+    devices: HashMap<String, CastDevice>,
+    /// Service instance full name to the UUID it resolved to.
+    instances: HashMap<String, String>,
+}
+
+impl Registry {
+    fn resolved(&mut self, fullname: &str, device: CastDevice) -> Option<DiscoveryEvent> {
+        // Without an `id` record the instance name is the only stable key.
+        let key = if device.uuid == "Unknown" {
+            fullname.to_string()
+        } else {
+            device.uuid.clone()
+        };
+        self.instances.insert(fullname.to_string(), key.clone());
+        match self.devices.insert(key, device.clone()) {
+            None => Some(DiscoveryEvent::Added(device)),
+            Some(previous) if previous != device => {
+                Some(DiscoveryEvent::Updated { previous, device })
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn removed(&mut self, fullname: &str) -> Option<DiscoveryEvent> {
+        let key = self.instances.remove(fullname)?;
+        // Another instance name may have taken over the same device since.
+        if self.instances.values().any(|k| *k == key) {
+            return None;
+        }
+        self.devices.remove(&key).map(DiscoveryEvent::Removed)
+    }
+}
+
+/// Builds a device from a resolved `_googlecast._tcp` service, if it has an address.
+fn device_from_info(info: &ServiceInfo) -> Option<CastDevice> {
+    let properties = info.get_properties();
+    let property = |key: &str| properties.get_property_val_str(key).unwrap_or("Unknown").to_string();
+    // Empty records mean "not set", e.g. `rs` while the device shows its backdrop.
+    let optional = |key: &str| {
+        properties
+            .get_property_val_str(key)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    let mut addresses: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+    addresses.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+    let ip = *addresses.first()?;
+    Some(CastDevice {
+        ip,
+        addresses,
+        port: info.get_port(),
+        friendly_name: property("fn"),
+        model_name: property("md"),
+        uuid: property("id"),
+        capabilities: optional("ca")
+            .map(|ca| DeviceCapabilities::from_txt(&ca))
+            .unwrap_or_default(),
+        status_text: optional("rs"),
+        icon_path: optional("ic"),
+        version: optional("ve").and_then(|ve| ve.parse().ok()),
+        bs: optional("bs"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_flags() {
+        // A Chromecast: video out, audio out.
+        let chromecast = DeviceCapabilities::from_txt("5");
+        assert!(chromecast.has_video());
+        assert!(chromecast.contains(DeviceCapabilities::AUDIO_OUT));
+        assert!(!chromecast.contains(DeviceCapabilities::MULTIZONE_GROUP));
+        assert!(chromecast.contains(DeviceCapabilities::VIDEO_OUT | DeviceCapabilities::AUDIO_OUT));
+        assert!(!chromecast.contains(DeviceCapabilities::VIDEO_OUT | DeviceCapabilities::VIDEO_IN));
+        assert_eq!(chromecast.bits(), 5);
+
+        // A speaker group: audio out plus the multizone flag.
+        let group = CastDevice {
+            friendly_name: "Downstairs".to_string(),
+            model_name: "Unknown".to_string(),
+            capabilities: DeviceCapabilities::from_txt("2084"),
+            ..device("group-1", "192.168.1.20")
+        };
+        assert!(group.is_group());
+        assert!(!group.capabilities.has_video());
+
+        assert_eq!(DeviceCapabilities::from_txt("garbage"), DeviceCapabilities::default());
+        assert_eq!(chromecast.to_string(), "video out, audio out");
+        assert_eq!(group.capabilities.to_string(), "audio out, multizone group");
+        assert_eq!(DeviceCapabilities::default().to_string(), "none");
+    }
+
+    #[test]
+    fn test_device_from_txt_records() {
+        let properties = HashMap::from([
+            ("id".to_string(), "8f1d0d9ab1c2".to_string()),
+            ("fn".to_string(), "Living Room TV".to_string()),
+            ("md".to_string(), "Chromecast Ultra".to_string()),
+            ("ca".to_string(), "201221".to_string()),
+            ("rs".to_string(), "YouTube".to_string()),
+            ("ic".to_string(), "/setup/icon.png".to_string()),
+            ("ve".to_string(), "05".to_string()),
+            ("bs".to_string(), "FA8FCA9E3D52".to_string()),
+        ]);
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            "Chromecast-Ultra-8f1d",
+            "8f1d.local.",
+            "fe80::1,192.168.1.10",
+            8009,
+            properties,
+        )
+        .unwrap();
+        let device = device_from_info(&info).unwrap();
+        assert_eq!(device.ip.to_string(), "192.168.1.10");
+        assert_eq!(device.addresses.len(), 2);
+        assert!(device.addresses[1].is_ipv6());
+        assert!(device.capabilities.has_video());
+        assert!(device.capabilities.has_audio());
+        assert!(!device.is_group());
+        assert_eq!(device.status_text.as_deref(), Some("YouTube"));
+        assert_eq!(device.version, Some(5));
+        assert_eq!(device.bs.as_deref(), Some("FA8FCA9E3D52"));
+        assert_eq!(
+            device.icon_url().as_deref(),
+            Some("http://192.168.1.10:8008/setup/icon.png")
+        );
+
+        // Idle devices send an empty status, and IPv6-only ones still resolve.
+        let properties = HashMap::from([("rs".to_string(), String::new())]);
+        let info =
+            ServiceInfo::new(SERVICE_TYPE, "speaker", "speaker.local.", "fe80::2", 8009, properties)
+                .unwrap();
+        let device = device_from_info(&info).unwrap();
+        assert_eq!(device.status_text, None);
+        assert_eq!(device.uuid, "Unknown");
+        assert_eq!(device.icon_url(), None);
+        assert!(device.ip.is_ipv6());
+    }
+
+    fn device(uuid: &str, ip: &str) -> CastDevice {
+        CastDevice {
+            ip: ip.parse().unwrap(),
+            addresses: vec![ip.parse().unwrap()],
+            port: 8009,
+            friendly_name: "Living Room TV".to_string(),
+            model_name: "Chromecast".to_string(),
+            uuid: uuid.to_string(),
+            capabilities: DeviceCapabilities::from_txt("5"),
+            status_text: None,
+            icon_path: None,
+            version: None,
+            bs: None,
+        }
+    }
+
+    #[test]
+    fn test_registry_tracks_presence() {
+        let mut registry = Registry::default();
+        let name = "Chromecast-abc._googlecast._tcp.local.";
+
+        let added = registry.resolved(name, device("abc", "192.168.1.10"));
+        assert_eq!(added, Some(DiscoveryEvent::Added(device("abc", "192.168.1.10"))));
+        // Re-announcing the same record is not a change.
+        assert_eq!(registry.resolved(name, device("abc", "192.168.1.10")), None);
+
+        let updated = registry.resolved(name, device("abc", "192.168.1.44"));
+        assert_eq!(
+            updated,
+            Some(DiscoveryEvent::Updated {
+                previous: device("abc", "192.168.1.10"),
+                device: device("abc", "192.168.1.44"),
+            })
+        );
+        assert_eq!(registry.devices["abc"].ip.to_string(), "192.168.1.44");
+
+        assert_eq!(registry.removed("unknown._googlecast._tcp.local."), None);
+        assert_eq!(
+            registry.removed(name),
+            Some(DiscoveryEvent::Removed(device("abc", "192.168.1.44")))
+        );
+        assert!(registry.devices.is_empty());
+    }
+
+    #[test]
+    fn test_registry_keys_by_uuid_across_instance_names() {
+        let mut registry = Registry::default();
+        registry.resolved("old-name._googlecast._tcp.local.", device("abc", "192.168.1.10"));
+        // Renamed in the Home app: same UUID, new instance name.
+        assert_eq!(
+            registry.resolved("new-name._googlecast._tcp.local.", device("abc", "192.168.1.10")),
+            None
+        );
+        // The old name expiring does not remove the device.
+        assert_eq!(registry.removed("old-name._googlecast._tcp.local."), None);
+        assert_eq!(registry.devices.len(), 1);
+    }
+}
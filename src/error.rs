@@ -6,6 +6,14 @@ pub enum CastError {
     Io(#[from] std::io::Error),
     #[error("TLS Error: {0}")]
     Tls(String),
+    #[error("TLS certificate for {device_id} changed (pinned {expected}, got {actual})")]
+    CertificateMismatch {
+        device_id: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Device Authentication Error: {0}")]
+    Auth(String),
     #[error("Protobuf Decode Error: {0}")]
     Decode(#[from] prost::DecodeError),
     #[error("Protobuf Encode Error: {0}")]
@@ -14,6 +22,14 @@ pub enum CastError {
     Json(#[from] serde_json::Error),
     #[error("Protocol Error: {0}")]
     Protocol(String),
+    #[error("Frame of {size} bytes exceeds the {max} byte limit")]
+    FrameTooLarge { size: usize, max: usize },
+    #[error("Failed to launch {app_id}: {reason}")]
+    LaunchFailed { app_id: String, reason: String },
+    #[error("{0} has not reported a media session")]
+    NoMediaSession(String),
+    #[error("Request {0} timed out waiting for a reply")]
+    Timeout(i32),
     #[error("Streaming Error: {0}")]
     Streaming(String),
     #[error("TUI Error: {0}")]
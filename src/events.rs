@@ -0,0 +1,254 @@
+//! Typed view of the messages received from a Cast device.
+
+use crate::client::Payload;
+use crate::proto::CastMessage;
+use crate::protocol::connection::{self, Connection};
+use crate::protocol::heartbeat::{self, Heartbeat};
+use crate::protocol::media::{self, MediaResponse, MediaStatus};
+use crate::protocol::receiver::{self, ReceiverResponse, ReceiverStatusData};
+use serde::Deserialize;
+
+/// A message from the Cast device, decoded according to its namespace.
+///
+/// Messages that cannot be decoded (unknown namespaces, unknown message types or
+/// malformed payloads) are surfaced as [`DeviceEvent::Unknown`] so nothing is lost.
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// `RECEIVER_STATUS` from the platform receiver.
+    ReceiverStatus {
+        request_id: i32,
+        status: ReceiverStatusData,
+    },
+    /// `MEDIA_STATUS` from the application at `source_id`.
+    MediaStatus {
+        source_id: String,
+        request_id: i32,
+        status: Vec<MediaStatus>,
+    },
+    /// `LOAD_FAILED` in reply to a LOAD request.
+    LoadFailed {
+        source_id: String,
+        request_id: i32,
+        detailed_error_code: Option<i32>,
+    },
+    /// `LOAD_CANCELLED` because a newer LOAD superseded the request.
+    LoadCancelled {
+        source_id: String,
+        request_id: i32,
+        item_id: Option<i32>,
+    },
+    /// `INVALID_REQUEST` on any namespace.
+    InvalidRequest {
+        source_id: String,
+        namespace: String,
+        request_id: i32,
+        reason: Option<String>,
+    },
+    /// `PING` or `PONG` on the heartbeat namespace.
+    Heartbeat {
+        source_id: String,
+        message: Heartbeat,
+    },
+    /// The device closed the virtual connection from `source_id`.
+    ConnectionClosed { source_id: String },
+    /// A binary payload on any namespace.
+    Binary {
+        source_id: String,
+        namespace: String,
+        payload: Vec<u8>,
+    },
+    /// Anything else, with the raw JSON payload.
+    Unknown {
+        source_id: String,
+        namespace: String,
+        payload: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct InvalidRequest {
+    #[serde(rename = "type")]
+    message_type: String,
+    #[serde(rename = "requestId", default)]
+    request_id: i32,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl DeviceEvent {
+    /// Decodes a message received from the device.
+    ///
+    /// Returns `None` for messages without a payload.
+    pub fn from_message(msg: &CastMessage) -> Option<Self> {
+        match Payload::from_message(msg)? {
+            Payload::Text(payload) => Some(Self::decode(&msg.namespace, &msg.source_id, &payload)),
+            Payload::Binary(payload) => Some(DeviceEvent::Binary {
+                source_id: msg.source_id.clone(),
+                namespace: msg.namespace.clone(),
+                payload,
+            }),
+        }
+    }
+
+    /// Decodes a string payload received on `namespace` from `source_id`.
+    pub fn decode(namespace: &str, source_id: &str, payload: &str) -> Self {
+        let source_id = source_id.to_string();
+        match namespace {
+            receiver::NAMESPACE => {
+                if let Ok(ReceiverResponse::ReceiverStatus { request_id, status }) =
+                    serde_json::from_str(payload)
+                {
+                    return DeviceEvent::ReceiverStatus { request_id, status };
+                }
+            }
+            media::NAMESPACE => match serde_json::from_str(payload) {
+                Ok(MediaResponse::MediaStatus { request_id, status }) => {
+                    return DeviceEvent::MediaStatus {
+                        source_id,
+                        request_id,
+                        status,
+                    };
+                }
+                Ok(MediaResponse::LoadFailed {
+                    request_id,
+                    detailed_error_code,
+                }) => {
+                    return DeviceEvent::LoadFailed {
+                        source_id,
+                        request_id,
+                        detailed_error_code,
+                    };
+                }
+                Ok(MediaResponse::LoadCancelled {
+                    request_id,
+                    item_id,
+                }) => {
+                    return DeviceEvent::LoadCancelled {
+                        source_id,
+                        request_id,
+                        item_id,
+                    };
+                }
+                _ => {}
+            },
+            heartbeat::NAMESPACE => {
+                if let Ok(message) = serde_json::from_str::<Heartbeat>(payload) {
+                    return DeviceEvent::Heartbeat { source_id, message };
+                }
+            }
+            connection::NAMESPACE => {
+                if let Ok(Connection::Close) = serde_json::from_str(payload) {
+                    return DeviceEvent::ConnectionClosed { source_id };
+                }
+            }
+            _ => {}
+        }
+
+        // INVALID_REQUEST has the same shape on every namespace.
+        if let Ok(invalid) = serde_json::from_str::<InvalidRequest>(payload) {
+            if invalid.message_type == "INVALID_REQUEST" {
+                return DeviceEvent::InvalidRequest {
+                    source_id,
+                    namespace: namespace.to_string(),
+                    request_id: invalid.request_id,
+                    reason: invalid.reason,
+                };
+            }
+        }
+
+        DeviceEvent::Unknown {
+            source_id,
+            namespace: namespace.to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    /// Returns the `requestId` this event answers, or `0` for unsolicited messages.
+    pub fn request_id(&self) -> i32 {
+        match self {
+            DeviceEvent::ReceiverStatus { request_id, .. }
+            | DeviceEvent::MediaStatus { request_id, .. }
+            | DeviceEvent::LoadFailed { request_id, .. }
+            | DeviceEvent::LoadCancelled { request_id, .. }
+            | DeviceEvent::InvalidRequest { request_id, .. } => *request_id,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_receiver_status() {
+        let payload = r#"{"type":"RECEIVER_STATUS","requestId":3,"status":{"applications":[{"appId":"CC1AD845","displayName":"Default Media Receiver","sessionId":"s-1","transportId":"web-5","statusText":"","isIdleScreen":false}],"volume":{"level":0.5,"muted":false}}}"#;
+        match DeviceEvent::decode(receiver::NAMESPACE, "receiver-0", payload) {
+            DeviceEvent::ReceiverStatus { request_id, status } => {
+                assert_eq!(request_id, 3);
+                assert_eq!(status.applications[0].transport_id, "web-5");
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_media_status_and_failures() {
+        let status = r#"{"type":"MEDIA_STATUS","requestId":0,"status":[{"mediaSessionId":1,"playbackRate":1,"playerState":"PLAYING","currentTime":12.5,"supportedMediaCommands":15}]}"#;
+        match DeviceEvent::decode(media::NAMESPACE, "web-5", status) {
+            DeviceEvent::MediaStatus {
+                source_id, status, ..
+            } => {
+                assert_eq!(source_id, "web-5");
+                assert_eq!(status[0].player_state, "PLAYING");
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+
+        let failed = r#"{"type":"LOAD_FAILED","requestId":7}"#;
+        assert!(matches!(
+            DeviceEvent::decode(media::NAMESPACE, "web-5", failed),
+            DeviceEvent::LoadFailed { request_id: 7, .. }
+        ));
+
+        let invalid = r#"{"type":"INVALID_REQUEST","requestId":8,"reason":"INVALID_MEDIA_SESSION_ID"}"#;
+        match DeviceEvent::decode(media::NAMESPACE, "web-5", invalid) {
+            DeviceEvent::InvalidRequest {
+                request_id, reason, ..
+            } => {
+                assert_eq!(request_id, 8);
+                assert_eq!(reason.as_deref(), Some("INVALID_MEDIA_SESSION_ID"));
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_transport_messages() {
+        assert!(matches!(
+            DeviceEvent::decode(heartbeat::NAMESPACE, "receiver-0", r#"{"type":"PING"}"#),
+            DeviceEvent::Heartbeat {
+                message: Heartbeat::Ping,
+                ..
+            }
+        ));
+        match DeviceEvent::decode(connection::NAMESPACE, "web-5", r#"{"type":"CLOSE"}"#) {
+            DeviceEvent::ConnectionClosed { source_id } => assert_eq!(source_id, "web-5"),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        let event = DeviceEvent::decode("urn:x-cast:com.example.custom", "web-5", r#"{"hello":1}"#);
+        match event {
+            DeviceEvent::Unknown {
+                namespace, payload, ..
+            } => {
+                assert_eq!(namespace, "urn:x-cast:com.example.custom");
+                assert_eq!(payload, r#"{"hello":1}"#);
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+}
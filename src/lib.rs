@@ -1,10 +1,12 @@
 pub mod app;
+pub mod auth;
 pub mod client;
 pub mod codec;
 pub mod config;
 pub mod controllers;
 pub mod discovery;
 pub mod error;
+pub mod events;
 pub mod proto;
 pub mod protocol;
 pub mod server;
@@ -16,3 +18,4 @@ pub mod utils;
 pub use client::CastClient;
 pub use discovery::{discover_devices, discover_devices_async, CastDevice};
 pub use error::CastError;
+pub use events::DeviceEvent;
@@ -1,5 +1,5 @@
 use castru::config::Config;
-use castru::app::{CastNowCore, scan_devices, connect_only, launch_app};
+use castru::app::{CastNowCore, scan_devices, watch_devices, connect_only, launch_app, attach};
 use std::env;
 use std::error::Error;
 
@@ -29,7 +29,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     match command.as_str() {
         "scan" => {
-            scan_devices().await?;
+            if args.get(2).map(String::as_str) == Some("--watch") {
+                watch_devices().await?;
+            } else {
+                scan_devices().await?;
+            }
         }
         "cast" => {
             if args.len() < 3 {
@@ -37,6 +41,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("Options:");
                 println!("  --ip <IP>      Connect to specific IP");
                 println!("  --name <NAME>  Connect to device with specific Friendly Name");
+                println!("  (repeat --ip/--name to cast to several devices in sync)");
                 return Ok(());
             }
 
@@ -55,6 +60,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let app_id = &args[3];
             launch_app(ip, app_id).await?;
         }
+        "attach" => {
+            if args.len() < 3 {
+                println!("Usage: castru attach <IP> [play|pause|stop|seek <SECONDS>|volume <0.0-1.0>|stream-volume <0.0-1.0>]");
+                return Ok(());
+            }
+            attach(&args[2], &args[3..]).await?;
+        }
         "connect" => {
             if args.len() < 3 {
                 println!("Usage: castru connect <IP>");
@@ -73,14 +85,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
 fn print_usage() {
     println!("Usage:");
-    println!("  castru scan");
+    println!("  castru scan [--watch]");
     println!("  castru cast [OPTIONS] <FILE_OR_URL> [FILE_OR_URL...]");
     println!("  castru connect <IP>");
     println!("  castru launch <IP> <APP_ID>");
+    println!("  castru attach <IP> [play|pause|stop|seek <SECONDS>|volume <0.0-1.0>|stream-volume <0.0-1.0>]");
     println!();
     println!("Options for 'cast':");
     println!("  --ip <IP>      Connect to specific IP");
     println!("  --name <NAME>  Connect to device with specific Friendly Name");
+    println!("                 Repeat --ip/--name to cast to several devices in sync");
     println!("  --log <FILE>   Output logs to specific file");
     println!("  --myip <IP>    Specify local interface IP to bind to");
     println!("  --port <PORT>  Specify internal server port");
@@ -88,6 +102,9 @@ fn print_usage() {
     println!("  --volume <0.0-1.0>  Set initial volume");
     println!("  --loop         Loop the playlist");
     println!("  --quiet        Suppress non-critical output");
+    println!("  --no-hls       Transcode to one MP4 stream instead of seekable HLS");
+    println!("  --cache-size <MB>   Disk space for finished transcodes (0 disables)");
+    println!("  --pin-certificates  Reject a discovered device whose TLS certificate changed");
 }
 
 #[cfg(test)]
@@ -0,0 +1,17 @@
+use crate::proto::{AuthChallenge, DeviceAuthMessage, HashAlgorithm, SignatureAlgorithm};
+
+pub const NAMESPACE: &str = "urn:x-cast:com.google.cast.tp.deviceauth";
+
+/// Builds a challenge asking the device to sign `sender_nonce` and our view of its
+/// TLS certificate with RSASSA-PKCS1-v1_5 over SHA-256.
+pub fn challenge(sender_nonce: Vec<u8>) -> DeviceAuthMessage {
+    DeviceAuthMessage {
+        challenge: Some(AuthChallenge {
+            signature_algorithm: Some(SignatureAlgorithm::RsassaPkcs1v15 as i32),
+            sender_nonce: Some(sender_nonce),
+            hash_algorithm: Some(HashAlgorithm::Sha256 as i32),
+        }),
+        response: None,
+        error: None,
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum MediaRequest {
     #[serde(rename = "GET_STATUS")]
@@ -61,23 +61,211 @@ pub enum MediaRequest {
         #[serde(rename = "mediaSessionId")]
         media_session_id: i32,
     },
+    /// Sets the stream volume (not the device volume) of a media session.
+    #[serde(rename = "VOLUME")]
+    Volume {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        volume: Volume,
+    },
+    #[serde(rename = "SET_PLAYBACK_RATE")]
+    SetPlaybackRate {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "playbackRate")]
+        playback_rate: f32,
+    },
+    /// Switches the active tracks and/or the text track style.
+    #[serde(rename = "EDIT_TRACKS_INFO")]
+    EditTracksInfo {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "activeTrackIds", skip_serializing_if = "Option::is_none")]
+        active_track_ids: Option<Vec<i32>>,
+        #[serde(rename = "textTrackStyle", skip_serializing_if = "Option::is_none")]
+        text_track_style: Option<TextTrackStyle>,
+    },
+    /// Replaces the current queue and starts playing it.
+    #[serde(rename = "QUEUE_LOAD")]
+    QueueLoad {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        items: Vec<QueueItem>,
+        #[serde(rename = "startIndex", skip_serializing_if = "Option::is_none")]
+        start_index: Option<i32>,
+        #[serde(rename = "repeatMode", skip_serializing_if = "Option::is_none")]
+        repeat_mode: Option<RepeatMode>,
+        #[serde(rename = "currentTime", skip_serializing_if = "Option::is_none")]
+        current_time: Option<f32>,
+    },
+    #[serde(rename = "QUEUE_INSERT")]
+    QueueInsert {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        items: Vec<QueueItem>,
+        /// Item ID to insert before; appends when absent.
+        #[serde(rename = "insertBefore", skip_serializing_if = "Option::is_none")]
+        insert_before: Option<i32>,
+    },
+    /// Jumps within the queue, changes its repeat mode or shuffles it.
+    #[serde(rename = "QUEUE_UPDATE")]
+    QueueUpdate {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "currentItemId", skip_serializing_if = "Option::is_none")]
+        current_item_id: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        jump: Option<i32>,
+        #[serde(rename = "repeatMode", skip_serializing_if = "Option::is_none")]
+        repeat_mode: Option<RepeatMode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        shuffle: Option<bool>,
+        #[serde(rename = "currentTime", skip_serializing_if = "Option::is_none")]
+        current_time: Option<f32>,
+    },
+    #[serde(rename = "QUEUE_REMOVE")]
+    QueueRemove {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "itemIds")]
+        item_ids: Vec<i32>,
+    },
+    #[serde(rename = "QUEUE_REORDER")]
+    QueueReorder {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "itemIds")]
+        item_ids: Vec<i32>,
+        #[serde(rename = "insertBefore", skip_serializing_if = "Option::is_none")]
+        insert_before: Option<i32>,
+    },
+    #[serde(rename = "QUEUE_NEXT")]
+    QueueNext {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+    },
+    #[serde(rename = "QUEUE_PREV")]
+    QueuePrev {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+    },
+    /// Answered with `QUEUE_ITEM_IDS`.
+    #[serde(rename = "QUEUE_GET_ITEM_IDS")]
+    QueueGetItemIds {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+    },
+    /// Answered with `QUEUE_ITEMS`.
+    #[serde(rename = "QUEUE_GET_ITEMS")]
+    QueueGetItems {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "mediaSessionId")]
+        media_session_id: i32,
+        #[serde(rename = "itemIds")]
+        item_ids: Vec<i32>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl super::Request for MediaRequest {
+    fn request_id(&self) -> i32 {
+        match self {
+            MediaRequest::GetStatus { request_id, .. }
+            | MediaRequest::Seek { request_id, .. }
+            | MediaRequest::Load { request_id, .. }
+            | MediaRequest::Play { request_id, .. }
+            | MediaRequest::Pause { request_id, .. }
+            | MediaRequest::Stop { request_id, .. }
+            | MediaRequest::Volume { request_id, .. }
+            | MediaRequest::SetPlaybackRate { request_id, .. }
+            | MediaRequest::EditTracksInfo { request_id, .. }
+            | MediaRequest::QueueLoad { request_id, .. }
+            | MediaRequest::QueueInsert { request_id, .. }
+            | MediaRequest::QueueUpdate { request_id, .. }
+            | MediaRequest::QueueRemove { request_id, .. }
+            | MediaRequest::QueueReorder { request_id, .. }
+            | MediaRequest::QueueNext { request_id, .. }
+            | MediaRequest::QueuePrev { request_id, .. }
+            | MediaRequest::QueueGetItemIds { request_id, .. }
+            | MediaRequest::QueueGetItems { request_id, .. } => *request_id,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MediaInformation {
     #[serde(rename = "contentId")]
     pub content_id: String,
-    #[serde(rename = "streamType")]
+    // Receivers leave these out of some status updates, so don't fail the whole status.
+    #[serde(rename = "streamType", default)]
     pub stream_type: String, // BUFFERED, LIVE, NONE
-    #[serde(rename = "contentType")]
+    #[serde(rename = "contentType", default)]
     pub content_type: String,
+    /// Length in seconds, as reported by the receiver once it has loaded the media.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<MediaMetadata>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tracks: Option<Vec<MediaTrack>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl MediaInformation {
+    /// Starts describing a buffered stream at `content_id`; chain `with_*` calls for the rest.
+    pub fn new(content_id: impl Into<String>, content_type: impl Into<String>) -> Self {
+        Self {
+            content_id: content_id.into(),
+            stream_type: "BUFFERED".to_string(),
+            content_type: content_type.into(),
+            duration: None,
+            metadata: None,
+            tracks: None,
+        }
+    }
+
+    /// BUFFERED, LIVE or NONE.
+    pub fn with_stream_type(mut self, stream_type: impl Into<String>) -> Self {
+        self.stream_type = stream_type.into();
+        self
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: MediaMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_tracks(mut self, tracks: Vec<MediaTrack>) -> Self {
+        self.tracks = Some(tracks);
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MediaTrack {
     #[serde(rename = "trackId")]
     pub track_id: i32, // Unique ID
@@ -95,23 +283,279 @@ pub struct MediaTrack {
     pub subtype: Option<String>, // SUBTITLES, CAPTIONS, DESCRIPTIONS, CHAPTERS, METADATA
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MediaMetadata {
-    #[serde(rename = "metadataType")]
-    pub metadata_type: i32, // 0: Generic, 1: Movie, 2: TV Show, 3: Music Track, 4: Photo
+/// Descriptive metadata shown by the receiver, keyed on the wire by `metadataType`.
+///
+/// Types this crate does not model are read as [`MediaMetadata::Generic`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaMetadata {
+    Generic(GenericMediaMetadata),
+    Movie(MovieMediaMetadata),
+    TvShow(TvShowMediaMetadata),
+    MusicTrack(MusicTrackMediaMetadata),
+    Photo(PhotoMediaMetadata),
+}
+
+impl MediaMetadata {
+    pub fn metadata_type(&self) -> i32 {
+        match self {
+            MediaMetadata::Generic(_) => 0,
+            MediaMetadata::Movie(_) => 1,
+            MediaMetadata::TvShow(_) => 2,
+            MediaMetadata::MusicTrack(_) => 3,
+            MediaMetadata::Photo(_) => 4,
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            MediaMetadata::Generic(m) => m.title.as_deref(),
+            MediaMetadata::Movie(m) => m.title.as_deref(),
+            MediaMetadata::TvShow(m) => m.title.as_deref(),
+            MediaMetadata::MusicTrack(m) => m.title.as_deref(),
+            MediaMetadata::Photo(m) => m.title.as_deref(),
+        }
+    }
+
+    /// Replaces the artwork; photos have none, so this does nothing for them.
+    pub fn with_images(mut self, new_images: Vec<Image>) -> Self {
+        match &mut self {
+            MediaMetadata::Generic(m) => m.images = Some(new_images),
+            MediaMetadata::Movie(m) => m.images = Some(new_images),
+            MediaMetadata::TvShow(m) => m.images = Some(new_images),
+            MediaMetadata::MusicTrack(m) => m.images = Some(new_images),
+            MediaMetadata::Photo(_) => {}
+        }
+        self
+    }
+
+    /// Artwork for the media; photos have none.
+    pub fn images(&self) -> &[Image] {
+        let images = match self {
+            MediaMetadata::Generic(m) => &m.images,
+            MediaMetadata::Movie(m) => &m.images,
+            MediaMetadata::TvShow(m) => &m.images,
+            MediaMetadata::MusicTrack(m) => &m.images,
+            MediaMetadata::Photo(_) => return &[],
+        };
+        images.as_deref().unwrap_or_default()
+    }
+}
+
+impl Serialize for MediaMetadata {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fields = match self {
+            MediaMetadata::Generic(m) => serde_json::to_value(m),
+            MediaMetadata::Movie(m) => serde_json::to_value(m),
+            MediaMetadata::TvShow(m) => serde_json::to_value(m),
+            MediaMetadata::MusicTrack(m) => serde_json::to_value(m),
+            MediaMetadata::Photo(m) => serde_json::to_value(m),
+        };
+        let mut value = fields.map_err(serde::ser::Error::custom)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("metadataType".to_string(), self.metadata_type().into());
+        }
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MediaMetadata {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let metadata = match value.get("metadataType").and_then(|t| t.as_i64()) {
+            Some(1) => serde_json::from_value(value).map(MediaMetadata::Movie),
+            Some(2) => serde_json::from_value(value).map(MediaMetadata::TvShow),
+            Some(3) => serde_json::from_value(value).map(MediaMetadata::MusicTrack),
+            Some(4) => serde_json::from_value(value).map(MediaMetadata::Photo),
+            _ => serde_json::from_value(value).map(MediaMetadata::Generic),
+        };
+        metadata.map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenericMediaMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtitle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<Image>>,
+    /// ISO 8601 date, e.g. `2014-02-10` or just `2014`.
+    #[serde(rename = "releaseDate", skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MovieMediaMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub subtitle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub studio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<Image>>,
+    #[serde(rename = "releaseDate", skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TvShowMediaMetadata {
+    #[serde(rename = "seriesTitle", skip_serializing_if = "Option::is_none")]
+    pub series_title: Option<String>,
+    /// The episode title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<Image>>,
+    #[serde(rename = "originalAirdate", skip_serializing_if = "Option::is_none")]
+    pub original_airdate: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MusicTrackMediaMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(rename = "albumName", skip_serializing_if = "Option::is_none")]
+    pub album_name: Option<String>,
+    #[serde(rename = "albumArtist", skip_serializing_if = "Option::is_none")]
+    pub album_artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub composer: Option<String>,
+    #[serde(rename = "trackNumber", skip_serializing_if = "Option::is_none")]
+    pub track_number: Option<i32>,
+    #[serde(rename = "discNumber", skip_serializing_if = "Option::is_none")]
+    pub disc_number: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<Image>>,
+    #[serde(rename = "releaseDate", skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PhotoMediaMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    #[serde(rename = "creationDateTime", skip_serializing_if = "Option::is_none")]
+    pub creation_date_time: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Image {
     pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub width: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Image {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            height: None,
+            width: None,
+        }
+    }
+}
+
+/// An entry of a media queue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueueItem {
+    /// Assigned by the receiver; leave empty when loading or inserting.
+    #[serde(rename = "itemId", skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<i32>,
+    pub media: MediaInformation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoplay: Option<bool>,
+    #[serde(rename = "startTime", skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<f32>,
+    #[serde(rename = "preloadTime", skip_serializing_if = "Option::is_none")]
+    pub preload_time: Option<f32>,
+    #[serde(rename = "activeTrackIds", skip_serializing_if = "Option::is_none")]
+    pub active_track_ids: Option<Vec<i32>>,
+}
+
+impl QueueItem {
+    pub fn new(media: MediaInformation) -> Self {
+        Self {
+            item_id: None,
+            media,
+            autoplay: Some(true),
+            start_time: None,
+            preload_time: None,
+            active_track_ids: None,
+        }
+    }
+}
+
+impl From<MediaInformation> for QueueItem {
+    fn from(media: MediaInformation) -> Self {
+        Self::new(media)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RepeatMode {
+    RepeatOff,
+    RepeatAll,
+    RepeatSingle,
+    RepeatAllAndShuffle,
+}
+
+/// Appearance of text tracks; colors are `#RRGGBBAA` strings.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TextTrackStyle {
+    #[serde(rename = "foregroundColor", skip_serializing_if = "Option::is_none")]
+    pub foreground_color: Option<String>,
+    #[serde(rename = "backgroundColor", skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+    /// NONE, OUTLINE, DROP_SHADOW, RAISED or DEPRESSED.
+    #[serde(rename = "edgeType", skip_serializing_if = "Option::is_none")]
+    pub edge_type: Option<String>,
+    #[serde(rename = "edgeColor", skip_serializing_if = "Option::is_none")]
+    pub edge_color: Option<String>,
+    #[serde(rename = "fontScale", skip_serializing_if = "Option::is_none")]
+    pub font_scale: Option<f32>,
+    #[serde(rename = "fontFamily", skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    /// SANS_SERIF, MONOSPACED_SANS_SERIF, SERIF, MONOSPACED_SERIF, CASUAL, CURSIVE or SMALL_CAPITALS.
+    #[serde(rename = "fontGenericFamily", skip_serializing_if = "Option::is_none")]
+    pub font_generic_family: Option<String>,
+    /// NORMAL, BOLD, BOLD_ITALIC or ITALIC.
+    #[serde(rename = "fontStyle", skip_serializing_if = "Option::is_none")]
+    pub font_style: Option<String>,
+    #[serde(rename = "windowColor", skip_serializing_if = "Option::is_none")]
+    pub window_color: Option<String>,
+    /// NONE, NORMAL or ROUNDED_CORNERS.
+    #[serde(rename = "windowType", skip_serializing_if = "Option::is_none")]
+    pub window_type: Option<String>,
+    #[serde(rename = "windowRoundedCornerRadius", skip_serializing_if = "Option::is_none")]
+    pub window_rounded_corner_radius: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum MediaResponse {
     #[serde(rename = "MEDIA_STATUS")]
@@ -120,9 +564,58 @@ pub enum MediaResponse {
         request_id: i32,
         status: Vec<MediaStatus>,
     },
+    #[serde(rename = "LOAD_FAILED")]
+    LoadFailed {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "detailedErrorCode", skip_serializing_if = "Option::is_none")]
+        detailed_error_code: Option<i32>,
+    },
+    #[serde(rename = "LOAD_CANCELLED")]
+    LoadCancelled {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "itemId", skip_serializing_if = "Option::is_none")]
+        item_id: Option<i32>,
+    },
+    #[serde(rename = "INVALID_REQUEST")]
+    InvalidRequest {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Reply to `QUEUE_GET_ITEMS`.
+    #[serde(rename = "QUEUE_ITEMS")]
+    QueueItems {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        items: Vec<QueueItem>,
+    },
+    /// Reply to `QUEUE_GET_ITEM_IDS`.
+    #[serde(rename = "QUEUE_ITEM_IDS")]
+    QueueItemIds {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "itemIds")]
+        item_ids: Vec<i32>,
+    },
+    /// Unsolicited notice that items were inserted, removed, updated or reordered.
+    #[serde(rename = "QUEUE_CHANGE")]
+    QueueChange {
+        #[serde(rename = "requestId", default)]
+        request_id: i32,
+        /// INSERT, REMOVE, ITEMS_CHANGE, UPDATE or NO_CHANGE.
+        #[serde(rename = "changeType")]
+        change_type: String,
+        #[serde(rename = "itemIds", default)]
+        item_ids: Vec<i32>,
+        #[serde(rename = "insertBefore", skip_serializing_if = "Option::is_none")]
+        insert_before: Option<i32>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MediaStatus {
     #[serde(rename = "mediaSessionId")]
     pub media_session_id: i32,
@@ -137,12 +630,459 @@ pub struct MediaStatus {
     pub volume: Option<Volume>,
     #[serde(rename = "idleReason")]
     pub idle_reason: Option<String>,
+    /// Only sent when the media changes, so it is absent from most updates.
+    pub media: Option<MediaInformation>,
+    #[serde(rename = "activeTrackIds")]
+    pub active_track_ids: Option<Vec<i32>>,
+    #[serde(rename = "repeatMode")]
+    pub repeat_mode: Option<RepeatMode>,
+    #[serde(rename = "currentItemId")]
+    pub current_item_id: Option<i32>,
+    #[serde(rename = "loadingItemId")]
+    pub loading_item_id: Option<i32>,
+    #[serde(rename = "preloadedItemId")]
+    pub preloaded_item_id: Option<i32>,
+    /// The queue items around the current one; not necessarily the whole queue.
+    pub items: Option<Vec<QueueItem>>,
+    #[serde(rename = "extendedStatus")]
+    pub extended_status: Option<ExtendedMediaStatus>,
+    #[serde(rename = "liveSeekableRange")]
+    pub live_seekable_range: Option<LiveSeekableRange>,
+}
+
+impl MediaStatus {
+    /// Length of the current media in seconds, if the receiver reported it.
+    pub fn duration(&self) -> Option<f32> {
+        self.media.as_ref().and_then(|m| m.duration)
+    }
+
+    /// The tracks of the current media that are enabled.
+    pub fn active_tracks(&self) -> Vec<&MediaTrack> {
+        let (Some(media), Some(ids)) = (&self.media, &self.active_track_ids) else {
+            return Vec::new();
+        };
+        media
+            .tracks
+            .iter()
+            .flatten()
+            .filter(|t| ids.contains(&t.track_id))
+            .collect()
+    }
+
+    /// The enabled subtitle or caption track, if any.
+    pub fn active_text_track(&self) -> Option<&MediaTrack> {
+        self.active_tracks()
+            .into_iter()
+            .find(|t| t.track_type == "TEXT")
+    }
+}
+
+/// Status of media that is being loaded while `playerState` still describes the old one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExtendedMediaStatus {
+    #[serde(rename = "playerState")]
+    pub player_state: String, // LOADING
+    #[serde(rename = "mediaSessionId")]
+    pub media_session_id: Option<i32>,
+    pub media: Option<MediaInformation>,
+}
+
+/// Seekable window of a live stream, in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LiveSeekableRange {
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+    #[serde(rename = "isMovingWindow")]
+    pub is_moving_window: Option<bool>,
+    #[serde(rename = "isLiveDone")]
+    pub is_live_done: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Volume {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub muted: Option<bool>,
 }
 
 pub const NAMESPACE: &str = "urn:x-cast:com.google.cast.media";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Request;
+    use serde_json::{json, Value};
+
+    fn media() -> MediaInformation {
+        MediaInformation {
+            content_id: "http://192.168.1.2:8080/movie.mp4".to_string(),
+            stream_type: "BUFFERED".to_string(),
+            content_type: "video/mp4".to_string(),
+            duration: None,
+            metadata: None,
+            tracks: None,
+        }
+    }
+
+    /// Serializes `request`, checks its wire type, and parses it back.
+    fn round_trip(request: MediaRequest, wire_type: &str) -> Value {
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["type"], wire_type);
+        let parsed: MediaRequest = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(parsed, request);
+        value
+    }
+
+    #[test]
+    fn test_volume_and_playback_requests_round_trip() {
+        let value = round_trip(
+            MediaRequest::Volume {
+                request_id: 1,
+                media_session_id: 7,
+                volume: Volume {
+                    level: None,
+                    muted: Some(true),
+                },
+            },
+            "VOLUME",
+        );
+        assert_eq!(value["volume"]["muted"], true);
+        assert_eq!(value["volume"].get("level"), None);
+
+        let value = round_trip(
+            MediaRequest::SetPlaybackRate {
+                request_id: 2,
+                media_session_id: 7,
+                playback_rate: 1.5,
+            },
+            "SET_PLAYBACK_RATE",
+        );
+        assert_eq!(value["playbackRate"], 1.5);
+
+        let value = round_trip(
+            MediaRequest::EditTracksInfo {
+                request_id: 3,
+                media_session_id: 7,
+                active_track_ids: Some(vec![1]),
+                text_track_style: Some(TextTrackStyle {
+                    font_scale: Some(1.25),
+                    edge_type: Some("OUTLINE".to_string()),
+                    ..Default::default()
+                }),
+            },
+            "EDIT_TRACKS_INFO",
+        );
+        assert_eq!(value["activeTrackIds"], json!([1]));
+        assert_eq!(
+            value["textTrackStyle"],
+            json!({"fontScale": 1.25, "edgeType": "OUTLINE"})
+        );
+    }
+
+    #[test]
+    fn test_queue_requests_round_trip() {
+        let mut item = QueueItem::new(media());
+        item.preload_time = Some(10.0);
+        let value = round_trip(
+            MediaRequest::QueueLoad {
+                request_id: 1,
+                items: vec![item.clone(), QueueItem::new(media())],
+                start_index: Some(1),
+                repeat_mode: Some(RepeatMode::RepeatAll),
+                current_time: None,
+            },
+            "QUEUE_LOAD",
+        );
+        assert_eq!(value["repeatMode"], "REPEAT_ALL");
+        assert_eq!(value["startIndex"], 1);
+        assert_eq!(value["items"][0]["preloadTime"], 10.0);
+        assert!(value["items"][0].get("itemId").is_none());
+
+        let value = round_trip(
+            MediaRequest::QueueInsert {
+                request_id: 2,
+                media_session_id: 7,
+                items: vec![item],
+                insert_before: Some(3),
+            },
+            "QUEUE_INSERT",
+        );
+        assert_eq!(value["insertBefore"], 3);
+
+        let value = round_trip(
+            MediaRequest::QueueUpdate {
+                request_id: 3,
+                media_session_id: 7,
+                current_item_id: None,
+                jump: Some(-1),
+                repeat_mode: Some(RepeatMode::RepeatAllAndShuffle),
+                shuffle: None,
+                current_time: None,
+            },
+            "QUEUE_UPDATE",
+        );
+        assert_eq!(value["jump"], -1);
+        assert_eq!(value["repeatMode"], "REPEAT_ALL_AND_SHUFFLE");
+        assert!(value.get("shuffle").is_none());
+
+        let value = round_trip(
+            MediaRequest::QueueRemove {
+                request_id: 4,
+                media_session_id: 7,
+                item_ids: vec![2, 3],
+            },
+            "QUEUE_REMOVE",
+        );
+        assert_eq!(value["itemIds"], json!([2, 3]));
+
+        round_trip(
+            MediaRequest::QueueReorder {
+                request_id: 5,
+                media_session_id: 7,
+                item_ids: vec![3, 2],
+                insert_before: None,
+            },
+            "QUEUE_REORDER",
+        );
+        round_trip(
+            MediaRequest::QueueNext {
+                request_id: 6,
+                media_session_id: 7,
+            },
+            "QUEUE_NEXT",
+        );
+        round_trip(
+            MediaRequest::QueuePrev {
+                request_id: 7,
+                media_session_id: 7,
+            },
+            "QUEUE_PREV",
+        );
+        round_trip(
+            MediaRequest::QueueGetItemIds {
+                request_id: 8,
+                media_session_id: 7,
+            },
+            "QUEUE_GET_ITEM_IDS",
+        );
+        let request = MediaRequest::QueueGetItems {
+            request_id: 9,
+            media_session_id: 7,
+            item_ids: vec![1],
+        };
+        assert_eq!(request.request_id(), 9);
+        round_trip(request, "QUEUE_GET_ITEMS");
+    }
+
+    #[test]
+    fn test_parse_error_responses() {
+        let failed: MediaResponse =
+            serde_json::from_str(r#"{"type":"LOAD_FAILED","requestId":4,"detailedErrorCode":104}"#)
+                .unwrap();
+        assert_eq!(
+            failed,
+            MediaResponse::LoadFailed {
+                request_id: 4,
+                detailed_error_code: Some(104),
+            }
+        );
+
+        let cancelled: MediaResponse =
+            serde_json::from_str(r#"{"type":"LOAD_CANCELLED","requestId":5,"itemId":2}"#).unwrap();
+        assert_eq!(
+            cancelled,
+            MediaResponse::LoadCancelled {
+                request_id: 5,
+                item_id: Some(2),
+            }
+        );
+
+        let invalid: MediaResponse = serde_json::from_str(
+            r#"{"type":"INVALID_REQUEST","requestId":6,"reason":"INVALID_MEDIA_SESSION_ID"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            invalid,
+            MediaResponse::InvalidRequest {
+                request_id: 6,
+                reason: Some("INVALID_MEDIA_SESSION_ID".to_string()),
+            }
+        );
+
+        for response in [failed, cancelled, invalid] {
+            let json = serde_json::to_string(&response).unwrap();
+            assert_eq!(serde_json::from_str::<MediaResponse>(&json).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn test_parse_queue_responses() {
+        let ids: MediaResponse =
+            serde_json::from_str(r#"{"type":"QUEUE_ITEM_IDS","requestId":8,"itemIds":[1,2,3]}"#)
+                .unwrap();
+        assert_eq!(
+            ids,
+            MediaResponse::QueueItemIds {
+                request_id: 8,
+                item_ids: vec![1, 2, 3],
+            }
+        );
+
+        let items: MediaResponse = serde_json::from_value(json!({
+            "type": "QUEUE_ITEMS",
+            "requestId": 9,
+            "items": [{
+                "itemId": 1,
+                "media": {
+                    "contentId": "http://192.168.1.2:8080/movie.mp4",
+                    "streamType": "BUFFERED",
+                    "contentType": "video/mp4"
+                },
+                "autoplay": true
+            }]
+        }))
+        .unwrap();
+        let mut expected = QueueItem::new(media());
+        expected.item_id = Some(1);
+        assert_eq!(
+            items,
+            MediaResponse::QueueItems {
+                request_id: 9,
+                items: vec![expected],
+            }
+        );
+
+        // Queue changes are broadcast without a request ID.
+        let change: MediaResponse = serde_json::from_str(
+            r#"{"type":"QUEUE_CHANGE","changeType":"INSERT","itemIds":[4],"insertBefore":2}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            change,
+            MediaResponse::QueueChange {
+                request_id: 0,
+                change_type: "INSERT".to_string(),
+                item_ids: vec![4],
+                insert_before: Some(2),
+            }
+        );
+
+        for response in [ids, items, change] {
+            let json = serde_json::to_string(&response).unwrap();
+            assert_eq!(serde_json::from_str::<MediaResponse>(&json).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn test_parse_full_media_status() {
+        let response: MediaResponse = serde_json::from_value(json!({
+            "type": "MEDIA_STATUS",
+            "requestId": 0,
+            "status": [{
+                "mediaSessionId": 1,
+                "playbackRate": 1,
+                "playerState": "PLAYING",
+                "currentTime": 12.5,
+                "supportedMediaCommands": 274447,
+                "volume": {"level": 1, "muted": false},
+                "activeTrackIds": [2],
+                "repeatMode": "REPEAT_OFF",
+                "currentItemId": 5,
+                "media": {
+                    "contentId": "http://192.168.1.2:8080/movie.mp4",
+                    "streamType": "BUFFERED",
+                    "contentType": "video/mp4",
+                    "duration": 5400.25,
+                    "metadata": {"metadataType": 1, "title": "Movie"},
+                    "tracks": [
+                        {"trackId": 1, "type": "AUDIO", "language": "en"},
+                        {"trackId": 2, "type": "TEXT", "subtype": "SUBTITLES", "name": "English"}
+                    ]
+                },
+                "items": [{
+                    "itemId": 5,
+                    "media": {"contentId": "http://192.168.1.2:8080/movie.mp4"},
+                    "autoplay": true
+                }],
+                "extendedStatus": {"playerState": "LOADING", "mediaSessionId": 2},
+                "liveSeekableRange": {"start": 0, "end": 120.5, "isMovingWindow": true}
+            }]
+        }))
+        .unwrap();
+        let MediaResponse::MediaStatus { status, .. } = response else {
+            panic!("expected MEDIA_STATUS");
+        };
+        let s = &status[0];
+
+        assert_eq!(s.duration(), Some(5400.25));
+        assert_eq!(s.repeat_mode, Some(RepeatMode::RepeatOff));
+        assert_eq!(s.current_item_id, Some(5));
+        assert_eq!(s.active_tracks().len(), 1);
+        assert_eq!(s.active_text_track().unwrap().name.as_deref(), Some("English"));
+        assert_eq!(s.items.as_ref().unwrap()[0].item_id, Some(5));
+        assert_eq!(s.items.as_ref().unwrap()[0].media.content_type, "");
+        assert_eq!(s.extended_status.as_ref().unwrap().player_state, "LOADING");
+        assert_eq!(s.live_seekable_range.as_ref().unwrap().end, Some(120.5));
+    }
+
+    #[test]
+    fn test_minimal_media_status_has_no_media() {
+        let status: MediaStatus = serde_json::from_str(
+            r#"{"mediaSessionId":1,"playbackRate":1,"playerState":"PAUSED","currentTime":3,"supportedMediaCommands":15}"#,
+        )
+        .unwrap();
+        assert_eq!(status.duration(), None);
+        assert!(status.active_tracks().is_empty());
+        assert!(status.active_text_track().is_none());
+    }
+
+    #[test]
+    fn test_metadata_round_trip_by_type() {
+        let media = MediaInformation::new("http://192.168.1.2:8080/", "video/mp4")
+            .with_duration(1320.0)
+            .with_metadata(
+                MediaMetadata::TvShow(TvShowMediaMetadata {
+                    series_title: Some("The Office".to_string()),
+                    title: Some("Halloween".to_string()),
+                    season: Some(2),
+                    episode: Some(5),
+                    ..Default::default()
+                })
+                .with_images(vec![Image::new("http://192.168.1.2:8080/artwork")]),
+            );
+        let value = serde_json::to_value(&media).unwrap();
+        assert_eq!(value["streamType"], "BUFFERED");
+        assert_eq!(
+            value["metadata"],
+            json!({
+                "metadataType": 2,
+                "seriesTitle": "The Office",
+                "title": "Halloween",
+                "season": 2,
+                "episode": 5,
+                "images": [{"url": "http://192.168.1.2:8080/artwork"}]
+            })
+        );
+        assert_eq!(serde_json::from_value::<MediaInformation>(value).unwrap(), media);
+
+        let track: MediaMetadata = serde_json::from_value(json!({
+            "metadataType": 3,
+            "title": "So What",
+            "albumName": "Kind of Blue",
+            "trackNumber": 1
+        }))
+        .unwrap();
+        let MediaMetadata::MusicTrack(track) = track else {
+            panic!("expected a music track");
+        };
+        assert_eq!(track.album_name.as_deref(), Some("Kind of Blue"));
+        assert_eq!(track.track_number, Some(1));
+
+        // Types we don't model (e.g. audiobook chapters) still parse.
+        let other: MediaMetadata =
+            serde_json::from_value(json!({"metadataType": 5, "title": "Chapter 1"})).unwrap();
+        assert_eq!(other.metadata_type(), 0);
+        assert_eq!(other.title(), Some("Chapter 1"));
+    }
+}
@@ -1,4 +1,10 @@
 pub mod connection;
+pub mod deviceauth;
 pub mod heartbeat;
 pub mod media;
 pub mod receiver;
+
+/// A JSON request that carries its own `requestId`, so its reply can be matched to it.
+pub trait Request: serde::Serialize {
+    fn request_id(&self) -> i32;
+}
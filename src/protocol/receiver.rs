@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
@@ -28,6 +29,27 @@ pub enum ReceiverRequest {
         #[serde(rename = "sessionId")]
         session_id: String,
     },
+    /// Asks whether the device can run each of `app_ids`; answered with an
+    /// [`AppAvailabilityResponse`].
+    #[serde(rename = "GET_APP_AVAILABILITY")]
+    GetAppAvailability {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(rename = "appId")]
+        app_ids: Vec<String>,
+    },
+}
+
+impl super::Request for ReceiverRequest {
+    fn request_id(&self) -> i32 {
+        match self {
+            ReceiverRequest::Launch { request_id, .. }
+            | ReceiverRequest::GetStatus { request_id }
+            | ReceiverRequest::SetVolume { request_id, .. }
+            | ReceiverRequest::Stop { request_id, .. }
+            | ReceiverRequest::GetAppAvailability { request_id, .. } => *request_id,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,16 +61,67 @@ pub enum ReceiverResponse {
         request_id: i32,
         status: ReceiverStatusData,
     },
+    /// The device could not launch the requested application.
+    #[serde(rename = "LAUNCH_ERROR")]
+    LaunchError {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        /// e.g. NOT_FOUND, NOT_ALLOWED, CANCELLED or RECEIVER_UNAVAILABLE.
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    #[serde(rename = "INVALID_REQUEST")]
+    InvalidRequest {
+        #[serde(rename = "requestId")]
+        request_id: i32,
+        #[serde(default)]
+        reason: Option<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Reply to `GET_APP_AVAILABILITY`, which is tagged `responseType` instead of `type`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppAvailabilityResponse {
+    #[serde(rename = "requestId")]
+    pub request_id: i32,
+    #[serde(rename = "responseType")]
+    pub response_type: String,
+    pub availability: HashMap<String, AppAvailability>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AppAvailability {
+    AppAvailable,
+    AppUnavailable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ReceiverStatusData {
     #[serde(default)]
     pub applications: Vec<Application>,
     pub volume: Option<Volume>,
+    /// Whether the TV's current input is the Cast device; only sent by HDMI devices that can tell.
+    #[serde(rename = "isActiveInput", skip_serializing_if = "Option::is_none")]
+    pub is_active_input: Option<bool>,
+    /// Whether the TV is in standby; only sent by HDMI devices that can tell.
+    #[serde(rename = "isStandBy", skip_serializing_if = "Option::is_none")]
+    pub is_stand_by: Option<bool>,
+}
+
+impl ReceiverStatusData {
+    /// The running application with `app_id`, if any.
+    pub fn application(&self, app_id: &str) -> Option<&Application> {
+        self.applications.iter().find(|a| a.app_id == app_id)
+    }
+
+    /// The application in the foreground, unless the idle screen is showing.
+    pub fn foreground_application(&self) -> Option<&Application> {
+        self.applications.iter().find(|a| !a.is_idle_screen)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Application {
     #[serde(rename = "appId")]
     pub app_id: String,
@@ -62,14 +135,34 @@ pub struct Application {
     pub status_text: String,
     #[serde(rename = "isIdleScreen")]
     pub is_idle_screen: bool,
+    /// Namespaces the application listens on.
+    #[serde(default)]
+    pub namespaces: Vec<AppNamespace>,
+}
+
+impl Application {
+    pub fn supports_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.iter().any(|n| n.name == namespace)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppNamespace {
+    pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Volume {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub level: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub muted: Option<bool>,
+    /// `attenuation`, `fixed` or `master`; only reported by the device.
+    #[serde(rename = "controlType", skip_serializing_if = "Option::is_none")]
+    pub control_type: Option<String>,
+    /// The level change of one volume step on the device.
+    #[serde(rename = "stepInterval", skip_serializing_if = "Option::is_none")]
+    pub step_interval: Option<f32>,
 }
 
 pub const NAMESPACE: &str = "urn:x-cast:com.google.cast.receiver";
@@ -5,17 +5,37 @@ use crate::torrent::stream::GrowingFile;
 use bytes::Bytes;
 use librqbit::ManagedTorrent;
 use std::path::{Path, PathBuf};
+use crate::transcode::{self, HlsPipeline, TranscodingPipeline, HLS_PLAYLIST};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::fs::File;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader,
+    SeekFrom,
+};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, Notify};
 use std::time::Duration;
 
+mod http;
+
+use http::{ByteRange, Method, Request, ResponseHead, Validators};
+
 // Constants for buffering
 const DEFAULT_CHUNK_SIZE: usize = 8 * 1024 * 1024; // 8M
 const DEFAULT_BUFFER_CAPACITY: usize = 64; // 64 chunks
 
+/// Methods every resource supports.
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// Files are served under per-item URLs, so receivers may reuse them for a while; the
+/// validators let them check back cheaply after that.
+const STATIC_CACHE_CONTROL: &str = "max-age=3600";
+
+/// How long an idle keep-alive connection waits for its next request.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Clone, Copy)]
 pub struct StreamConfig {
     pub chunk_size: usize,
@@ -89,14 +109,328 @@ impl StreamSource {
 pub trait AsyncReadSeek: AsyncRead + AsyncSeek {}
 impl<T: AsyncRead + AsyncSeek> AsyncReadSeek for T {}
 
-/// Simple HTTP Server to stream a specific file.
+/// Something [`StreamServer`] serves under its own URL.
+#[derive(Clone)]
+pub enum Resource {
+    /// A file or torrent stream, served with range support.
+    Media {
+        source: StreamSource,
+        content_type: String,
+    },
+    /// A subtitle file, converted from SRT to WebVTT when needed.
+    Subtitle(PathBuf),
+    /// Transcoder output, streamed while it is being written.
+    Transcode(Arc<TranscodeOutput>),
+    /// An HLS playlist and its segments, served from the segmenter's directory.
+    Hls(Arc<HlsOutput>),
+    /// Bytes held in memory, such as a generated playlist.
+    Data {
+        bytes: Bytes,
+        content_type: String,
+    },
+}
+
+impl Resource {
+    /// A file or torrent stream, typed by its extension.
+    pub fn media(source: StreamSource) -> Self {
+        let content_type = get_mime_type(&source.get_path()).to_string();
+        Resource::Media {
+            source,
+            content_type,
+        }
+    }
+
+    /// A local file, typed by its extension.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::media(StreamSource::Static(path.into()))
+    }
+
+    /// The URL path for the resource under `token`.
+    fn url_path(&self, token: &str) -> String {
+        if let Resource::Hls(_) = self {
+            // Segment URIs in the playlist are relative to it.
+            return format!("{}/{}", token, HLS_PLAYLIST);
+        }
+        match self.extension() {
+            Some(ext) => format!("{}.{}", token, ext),
+            None => token.to_string(),
+        }
+    }
+
+    /// The extension for the resource's URL; some receivers look at it before the
+    /// `Content-Type`.
+    fn extension(&self) -> Option<String> {
+        let ext = match self {
+            Resource::Media { source, .. } => source
+                .get_path()
+                .extension()?
+                .to_str()?
+                .to_ascii_lowercase(),
+            Resource::Subtitle(_) => "vtt".to_string(),
+            Resource::Transcode(_) => "mp4".to_string(),
+            Resource::Hls(_) | Resource::Data { .. } => return None,
+        };
+        ext.bytes().all(|b| b.is_ascii_alphanumeric()).then_some(ext)
+    }
+}
+
+/// A resource registered with [`StreamServer::add`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedResource {
+    /// Identifies the resource to [`StreamServer::remove`].
+    pub token: String,
+    pub url: String,
+}
+
+/// Runs a transcoder process until it exits or is stopped.
+struct Supervisor {
+    stop: Arc<Notify>,
+    /// `None` while running, then whether the process exited successfully.
+    exit: watch::Receiver<Option<bool>>,
+}
+
+impl Supervisor {
+    fn spawn(mut child: tokio::process::Child) -> Self {
+        let stop = Arc::new(Notify::new());
+        let (exit_tx, exit) = watch::channel(None);
+        let stopped = stop.clone();
+        tokio::spawn(async move {
+            let success = tokio::select! {
+                status = child.wait() => status.is_ok_and(|s| s.success()),
+                _ = stopped.notified() => {
+                    let _ = child.kill().await;
+                    false
+                }
+            };
+            let _ = exit_tx.send(Some(success));
+        });
+        Self { stop, exit }
+    }
+
+    fn stop(&self) {
+        self.stop.notify_one();
+    }
+
+    fn exited(&self) -> Option<bool> {
+        *self.exit.borrow()
+    }
+
+    /// Waits for the process to exit; returns whether it succeeded.
+    async fn wait(&self) -> bool {
+        let mut exit = self.exit.clone();
+        let result = exit.wait_for(Option::is_some).await;
+        result.is_ok_and(|exit| *exit == Some(true))
+    }
+}
+
+/// A transcoder's output, written to a file that is served as it grows.
+///
+/// Removing it from the server stops the transcoder; the file is deleted once the last
+/// transfer of it ends.
+pub struct TranscodeOutput {
+    path: PathBuf,
+    key: Option<String>,
+    start_time: f64,
+    /// Set once copying to `path` has ended, however it ended.
+    done: Arc<AtomicBool>,
+    /// Whether the whole output made it to `path`, once known.
+    complete: watch::Receiver<Option<bool>>,
+    process: Supervisor,
+}
+
+impl TranscodeOutput {
+    /// Starts copying the pipeline's output to `path`. `key` identifies the transcode, so
+    /// an identical one can reuse it through [`StreamServer::find_transcode`].
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn start(
+        pipeline: TranscodingPipeline,
+        path: PathBuf,
+        key: Option<String>,
+    ) -> std::io::Result<Arc<Self>> {
+        // Created up front so a request arriving straight away finds it.
+        let mut file = File::from_std(std::fs::File::create(&path)?);
+        let start_time = pipeline.start_time;
+        let process = Supervisor::spawn(pipeline.process);
+        let exit = process.exit.clone();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let done_flag = done.clone();
+        let (complete_tx, complete) = watch::channel(None);
+        let mut stdout = pipeline.stdout;
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 1024 * 1024];
+            let mut copied = false;
+            while !done_flag.load(Ordering::SeqCst) {
+                match stdout.read(&mut buf).await {
+                    Ok(0) => {
+                        copied = true;
+                        break;
+                    }
+                    Err(_) => break,
+                    Ok(n) => {
+                        if file.write_all(&buf[..n]).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            copied &= file.flush().await.is_ok();
+            done_flag.store(true, Ordering::SeqCst);
+            let mut exit = exit;
+            let succeeded = exit
+                .wait_for(Option::is_some)
+                .await
+                .is_ok_and(|exit| *exit == Some(true));
+            let _ = complete_tx.send(Some(copied && succeeded));
+        });
+
+        Ok(Arc::new(Self {
+            path,
+            key,
+            start_time,
+            done,
+            complete,
+            process,
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// Where the output starts in the input, in seconds; its timestamps count from there.
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    /// Whether the transcoder has finished writing.
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::SeqCst)
+    }
+
+    /// Waits for the transcoder to finish; returns whether it wrote the whole output.
+    pub async fn wait_complete(&self) -> bool {
+        let mut complete = self.complete.clone();
+        let result = complete.wait_for(Option::is_some).await;
+        result.is_ok_and(|complete| *complete == Some(true))
+    }
+
+    /// Kills the transcoder; whatever it has written so far is still served.
+    pub fn stop(&self) {
+        self.done.store(true, Ordering::SeqCst);
+        self.process.stop();
+    }
+
+    /// Whether it is still being written or was written completely, i.e. worth reusing.
+    fn is_usable(&self) -> bool {
+        *self.complete.borrow() != Some(false)
+    }
+}
+
+impl Drop for TranscodeOutput {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// An HLS segmenter's output directory, served while it fills up.
+///
+/// Removing it from the server stops the segmenter; the directory is deleted once nothing
+/// refers to it any more.
+pub struct HlsOutput {
+    dir: PathBuf,
+    start_time: f64,
+    process: Supervisor,
+}
+
+impl HlsOutput {
+    pub fn new(pipeline: HlsPipeline) -> Arc<Self> {
+        Arc::new(Self {
+            dir: pipeline.dir,
+            start_time: pipeline.start_time,
+            process: Supervisor::spawn(pipeline.process),
+        })
+    }
+
+    pub fn playlist_path(&self) -> PathBuf {
+        self.dir.join(HLS_PLAYLIST)
+    }
+
+    /// Where the output starts in the input, in seconds; its timestamps count from there.
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    /// The playlist as it stands, if the segmenter has written one yet.
+    pub fn playlist(&self) -> Option<String> {
+        std::fs::read_to_string(self.playlist_path()).ok()
+    }
+
+    /// Seconds of media produced so far; the receiver can seek freely within them.
+    pub fn produced_duration(&self) -> f64 {
+        self.playlist()
+            .map_or(0.0, |p| transcode::hls_playlist_duration(&p))
+    }
+
+    /// Waits until the playlist lists its first segment, so the receiver has something to
+    /// play. Fails if the segmenter exits without producing one or `timeout` passes.
+    pub async fn wait_ready(&self, timeout: Duration) -> Result<(), CastError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if self
+                .playlist()
+                .is_some_and(|p| transcode::hls_playlist_has_segments(&p))
+            {
+                return Ok(());
+            }
+            if self.process.exited().is_some() {
+                return Err(CastError::Transcoding(
+                    "ffmpeg exited before producing a segment".into(),
+                ));
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(CastError::Transcoding(
+                    "Timeout waiting for the first HLS segment".into(),
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Waits for the segmenter to finish; returns whether it segmented the whole input.
+    pub async fn wait_complete(&self) -> bool {
+        self.process.wait().await
+    }
+
+    /// Kills the segmenter; the segments written so far are still served.
+    pub fn stop(&self) {
+        self.process.stop();
+    }
+}
+
+impl Drop for HlsOutput {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+type Registry = Arc<Mutex<HashMap<String, Resource>>>;
+
+/// HTTP server for the media, subtitles and artwork we cast.
+///
+/// Every resource gets its own unguessable URL and is served until it is removed, so
+/// several can be offered at once (e.g. a whole queue) and a stale URL never serves
+/// another file's bytes.
 pub struct StreamServer {
-    source: Arc<Mutex<Option<StreamSource>>>,
-    transcode_rx: Arc<tokio::sync::Mutex<Option<tokio::process::ChildStdout>>>,
-    transcode_process: Arc<tokio::sync::Mutex<Option<tokio::process::Child>>>,
-    transcode_path: Arc<Mutex<Option<PathBuf>>>,
-    transcode_done: Arc<std::sync::atomic::AtomicBool>,
-    subtitle_path: Arc<Mutex<Option<PathBuf>>>,
+    resources: Registry,
+    base_url: String,
     port: u16,
 }
 
@@ -109,12 +443,8 @@ impl Default for StreamServer {
 impl StreamServer {
     pub fn new() -> Self {
         Self {
-            source: Arc::new(Mutex::new(None)),
-            transcode_rx: Arc::new(tokio::sync::Mutex::new(None)),
-            transcode_process: Arc::new(tokio::sync::Mutex::new(None)),
-            transcode_path: Arc::new(Mutex::new(None)),
-            transcode_done: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-            subtitle_path: Arc::new(Mutex::new(None)),
+            resources: Arc::new(Mutex::new(HashMap::new())),
+            base_url: String::new(),
             port: 0,
         }
     }
@@ -129,22 +459,17 @@ impl StreamServer {
 
         let addr = listener.local_addr().map_err(CastError::Io)?;
         self.port = addr.port();
-        let source_clone = self.source.clone();
-        let transcode_path_clone = self.transcode_path.clone();
-        let transcode_done_clone = self.transcode_done.clone();
-        let subtitle_path_clone = self.subtitle_path.clone();
+        self.base_url = format!("http://{}:{}", local_ip, self.port);
+        let resources = self.resources.clone();
 
         println!("Streaming server listening on {}", addr);
 
         tokio::spawn(async move {
             loop {
                 if let Ok((socket, _)) = listener.accept().await {
-                    let src = source_clone.clone();
-                    let t_path = transcode_path_clone.clone();
-                    let t_done = transcode_done_clone.clone();
-                    let sub_path = subtitle_path_clone.clone();
+                    let resources = resources.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = handle_connection(socket, src, t_path, t_done, sub_path).await {
+                        if let Err(e) = handle_connection(socket, resources).await {
                             log::error!("Connection handling error: {}", e);
                         }
                     });
@@ -152,111 +477,95 @@ impl StreamServer {
             }
         });
 
-        Ok(format!("http://{}:{}", local_ip, self.port))
+        Ok(self.base_url.clone())
     }
 
-    /// Sets the source to be streamed.
-    pub async fn set_source(&self, source: StreamSource) {
-        {
-            let mut src = self.source.lock().unwrap();
-            *src = Some(source);
+    /// Serves `resource` under a new URL until it is removed.
+    pub fn add(&self, resource: Resource) -> ServedResource {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let path = resource.url_path(&token);
+        self.resources
+            .lock()
+            .unwrap()
+            .insert(token.clone(), resource);
+        ServedResource {
+            url: format!("{}/{}", self.base_url, path),
+            token,
         }
-        // Clear transcode
-        self.clear_transcode().await;
-        // Clear subtitle
-        {
-            let mut sub = self.subtitle_path.lock().unwrap();
-            *sub = None;
-        }
-    }
-
-    /// Sets the file to be streamed (Legacy helper).
-    pub async fn set_file(&self, path: PathBuf) {
-        self.set_source(StreamSource::Static(path)).await;
-    }
-
-    pub async fn set_subtitle(&self, path: PathBuf) {
-        let mut sub = self.subtitle_path.lock().unwrap();
-        *sub = Some(path);
     }
 
-    async fn clear_transcode(&self) {
-        {
-            let mut trx = self.transcode_rx.lock().await;
-            *trx = None;
-        }
-        {
-            let mut proc = self.transcode_process.lock().await;
-            if let Some(mut child) = proc.take() {
-                let _ = child.kill().await;
-            }
-        }
-        self.transcode_done.store(true, std::sync::atomic::Ordering::SeqCst);
-        let old_path = {
-            let mut p = self.transcode_path.lock().unwrap();
-            p.take()
+    /// Stops serving a resource; transfers already under way run to completion.
+    ///
+    /// Returns `false` if there was no such resource.
+    pub fn remove(&self, token: &str) -> bool {
+        let mut resources = self.resources.lock().unwrap();
+        let Some(removed) = resources.remove(token) else {
+            return false;
         };
-        if let Some(path) = old_path {
-            let _ = tokio::fs::remove_file(path).await;
+        // A transcoder may be shared by several tokens; stop it with the last one.
+        match removed {
+            Resource::Transcode(output) => {
+                let shared = resources
+                    .values()
+                    .any(|r| matches!(r, Resource::Transcode(o) if Arc::ptr_eq(o, &output)));
+                if !shared {
+                    output.stop();
+                }
+            }
+            Resource::Hls(output) => {
+                let shared = resources
+                    .values()
+                    .any(|r| matches!(r, Resource::Hls(o) if Arc::ptr_eq(o, &output)));
+                if !shared {
+                    output.stop();
+                }
+            }
+            _ => {}
         }
+        true
     }
 
-    pub async fn set_transcode_output(&self, pipeline: crate::transcode::TranscodingPipeline) {
-        // Cleanup old
-        self.clear_transcode().await;
-
-        let temp_dir = std::env::temp_dir().join("castru_transcode");
-        let _ = tokio::fs::create_dir_all(&temp_dir).await;
-        let file_name = format!("transcode_{}.mp4", uuid::Uuid::new_v4());
-        let path = temp_dir.join(file_name);
-
-        {
-            let mut p = self.transcode_path.lock().unwrap();
-            *p = Some(path.clone());
-        }
-
-        self.transcode_done.store(false, std::sync::atomic::Ordering::SeqCst);
+    /// A transcode being served under `key` that is still running or finished cleanly.
+    pub fn find_transcode(&self, key: &str) -> Option<Arc<TranscodeOutput>> {
+        self.resources
+            .lock()
+            .unwrap()
+            .values()
+            .find_map(|resource| match resource {
+                Resource::Transcode(output) if output.key() == Some(key) && output.is_usable() => {
+                    Some(output.clone())
+                }
+                _ => None,
+            })
+    }
 
-        {
-            let mut proc = self.transcode_process.lock().await;
-            *proc = Some(pipeline.process);
-        }
+    pub fn contains(&self, token: &str) -> bool {
+        self.resources.lock().unwrap().contains_key(token)
+    }
 
-        let mut stdout = pipeline.stdout;
-        let path_clone = path.clone();
-        let done_flag = self.transcode_done.clone();
+    /// How many resources are being served.
+    pub fn len(&self) -> usize {
+        self.resources.lock().unwrap().len()
+    }
 
-        tokio::spawn(async move {
-            if let Ok(mut file) = tokio::fs::File::create(&path_clone).await {
-                let mut buf = vec![0u8; 1024 * 1024];
-                loop {
-                    if done_flag.load(std::sync::atomic::Ordering::SeqCst) {
-                        break;
-                    }
-                    match stdout.read(&mut buf).await {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            if (file.write_all(&buf[..n]).await).is_err() {
-                                break;
-                            }
-                        }
-                        Err(_) => break,
-                    }
-                }
-            }
-            done_flag.store(true, std::sync::atomic::Ordering::SeqCst);
-        });
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
-async fn stream_file_buffered<R>(
-    socket: &mut TcpStream,
+/// Copies `remaining` bytes from `reader` to `writer` through a read-ahead buffer.
+///
+/// Returns how many bytes were written, which is less than `remaining` if the source ended
+/// early.
+async fn stream_file_buffered<R, W>(
+    writer: &mut W,
     reader: R,
     config: StreamConfig,
     remaining: u64,
-) -> std::io::Result<()> 
+) -> std::io::Result<u64>
 where
     R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin,
 {
     let (tx, mut rx) = mpsc::channel(config.buffer_capacity);
 
@@ -264,184 +573,339 @@ where
     tokio::spawn(producer_task(reader, tx, config.chunk_size, remaining));
 
     // Consumer loop
+    let mut written = 0;
     while let Some(res) = rx.recv().await {
         match res {
             Ok(chunk) => {
-                socket.write_all(&chunk).await?;
+                writer.write_all(&chunk).await?;
+                written += chunk.len() as u64;
             }
             Err(e) => return Err(e),
         }
     }
-    Ok(())
+    Ok(written)
 }
 
-async fn handle_connection(
-    mut socket: TcpStream,
-    source_arc: Arc<Mutex<Option<StreamSource>>>,
-    transcode_path_arc: Arc<Mutex<Option<PathBuf>>>,
-    transcode_done: Arc<std::sync::atomic::AtomicBool>,
-    subtitle_path_arc: Arc<Mutex<Option<PathBuf>>>,
+/// Tails the transcoder's output file until the transcoder is done, using chunked
+/// encoding unless the client only speaks HTTP/1.0.
+async fn stream_transcode<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    path: &Path,
+    done: &AtomicBool,
+    chunked: bool,
 ) -> std::io::Result<()> {
-    let mut buf = [0; 1024];
-    let n = socket.read(&mut buf).await?;
-    if n == 0 {
-        return Ok(());
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut pipe_buf = vec![0u8; 1024 * 1024];
+    loop {
+        match file.read(&mut pipe_buf).await? {
+            0 => {
+                if done.load(Ordering::SeqCst) {
+                    if chunked {
+                        writer.write_all(b"0\r\n\r\n").await?;
+                    }
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            n if chunked => {
+                writer.write_all(format!("{:X}\r\n", n).as_bytes()).await?;
+                writer.write_all(&pipe_buf[..n]).await?;
+                writer.write_all(b"\r\n").await?;
+            }
+            n => writer.write_all(&pipe_buf[..n]).await?,
+        }
     }
+}
 
-    let request = String::from_utf8_lossy(&buf[..n]);
+/// What follows a response head.
+enum Body {
+    Empty,
+    Bytes(Bytes),
+    /// `length` bytes from a reader already positioned at the first one.
+    Stream {
+        reader: Box<dyn AsyncReadSeek + Unpin + Send>,
+        length: u64,
+    },
+    /// The transcoder's output, whose length is not known up front.
+    Transcode(Arc<TranscodeOutput>),
+}
 
-    // Check for subtitle request
-    if request.starts_with("GET /subtitle") {
-         let sub_path = {
-            let p = subtitle_path_arc.lock().unwrap();
-            p.clone()
-        };
-        if let Some(path) = sub_path {
-            let status_line = "HTTP/1.1 200 OK";
-            let is_vtt = path.extension().is_some_and(|e| e == "vtt");
-            let is_srt = path.extension().is_some_and(|e| e == "srt");
-
-            if is_srt {
-                 // Convert SRT to VTT
-                 let content = tokio::fs::read_to_string(&path).await?;
-                 use crate::utils::subtitles::srt_to_vtt;
-                 let vtt_content = srt_to_vtt(&content);
-                 let content_len = vtt_content.len();
-
-                 let header = format!(
-                    "{} \r\n\
-                    Content-Type: text/vtt\r\n\
-                    Content-Length: {}\r\n\
-                    Connection: close\r\n\
-                    Access-Control-Allow-Origin: *\r\n\
-                    \r\n",
-                    status_line, content_len
-                );
-                socket.write_all(header.as_bytes()).await?;
-                socket.write_all(vtt_content.as_bytes()).await?;
-                return Ok(());
-            }
+struct Response {
+    head: ResponseHead,
+    body: Body,
+}
 
-            // Normal file serving (VTT or other)
-            let content_type = if is_vtt { "text/vtt" } else { "application/octet-stream" };
-            
-            let file_size = tokio::fs::metadata(&path).await?.len();
-
-            let header = format!(
-                "{} \r\n\
-                Content-Type: {}\r\n\
-                Content-Length: {}\r\n\
-                Connection: close\r\n\
-                Access-Control-Allow-Origin: *\r\n\
-                \r\n",
-                status_line, content_type, file_size
-            );
-            socket.write_all(header.as_bytes()).await?;
-            
-            let mut file = tokio::fs::File::open(&path).await?;
-            tokio::io::copy(&mut file, &mut socket).await?;
-            return Ok(());
-        }
+impl Response {
+    fn new(head: ResponseHead, body: Body) -> Self {
+        Self { head, body }
     }
 
-    // Check transcode first
-    let t_path = {
-        let p = transcode_path_arc.lock().unwrap();
-        p.clone()
-    };
+    /// A response with no body, e.g. an error.
+    fn status(status: u16) -> Self {
+        let head = ResponseHead::new(status)
+            .header("Content-Length", 0)
+            .header("Cache-Control", "no-store");
+        Self::new(head, Body::Empty)
+    }
 
-    if let Some(path) = t_path {
-        let status_line = "HTTP/1.1 200 OK";
-        let header = format!(
-            "{} \r\n\
-            Content-Type: video/mp4\r\n\
-            Connection: keep-alive\r\n\
-            Transfer-Encoding: chunked\r\n\
-            \r\n",
-            status_line
-        );
-        socket.write_all(header.as_bytes()).await?;
+    /// 304 for a conditional request whose copy is still current.
+    fn not_modified(validators: &Validators) -> Self {
+        let head = ResponseHead::new(304).header("Cache-Control", STATIC_CACHE_CONTROL);
+        Self::new(with_validators(head, validators), Body::Empty)
+    }
 
-        let mut file = tokio::fs::File::open(&path).await?;
-        let mut pipe_buf = vec![0u8; 1024 * 1024];
-        loop {
-            match file.read(&mut pipe_buf).await {
-                Ok(0) => {
-                    if transcode_done.load(std::sync::atomic::Ordering::SeqCst) {
-                        socket.write_all(b"0\r\n\r\n").await?;
-                        break;
-                    }
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                    continue;
-                }
-                Ok(n) => {
-                    let size_str = format!("{:X}\r\n", n);
-                    socket.write_all(size_str.as_bytes()).await?;
-                    socket.write_all(&pipe_buf[..n]).await?;
-                    socket.write_all(b"\r\n").await?;
-                }
-                Err(e) => return Err(e),
+    fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.head = self.head.header(name, value);
+        self
+    }
+}
+
+/// Serves requests on `socket` until the client closes it, asks us to, or goes idle.
+async fn handle_connection(socket: TcpStream, resources: Registry) -> std::io::Result<()> {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+    loop {
+        let next = tokio::time::timeout(KEEP_ALIVE_TIMEOUT, http::read_request(&mut reader)).await;
+        let request = match next {
+            Err(_) | Ok(Ok(Ok(None))) => return Ok(()),
+            Ok(Err(e)) => return Err(e),
+            Ok(Ok(Err(e))) => {
+                log::debug!("Rejecting malformed request: {:?}", e);
+                let head = cors(Response::status(e.status()).head);
+                return writer.write_all(&head.to_bytes(false)).await;
             }
+            Ok(Ok(Ok(Some(request)))) => request,
+        };
+
+        let response = match route(&request, &resources).await {
+            Ok(response) => response,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Response::status(404),
+            Err(e) => {
+                log::error!("Failed to serve {}: {}", request.path, e);
+                Response::status(500)
+            }
+        };
+        // We never read request bodies, so a request with one ends the connection.
+        let keep_alive = request.keep_alive() && !request.has_body();
+        if !send(&mut writer, &request, response, keep_alive).await? {
+            return Ok(());
         }
-        return Ok(());
     }
+}
 
-    // Extract Range header
-    let range_header = request
-        .lines()
-        .find(|line| line.starts_with("Range: bytes="))
-        .map(|line| line.trim_start_matches("Range: bytes=").trim());
-
-    // Get source
-    let source = {
-        let s = source_arc.lock().unwrap();
-        match s.as_ref() {
-            Some(src) => src.clone(),
-            None => return Ok(()), // 404
+/// Finds the resource named by the first path segment, ignoring any extension. The rest of
+/// the path names a file within an HLS resource.
+async fn route(request: &Request, resources: &Registry) -> std::io::Result<Response> {
+    match request.method {
+        Method::Get | Method::Head => {}
+        Method::Options => return Ok(preflight(request)),
+        Method::Other(_) => return Ok(Response::status(405).header("Allow", ALLOWED_METHODS)),
+    }
+    let path = request.path.trim_start_matches('/');
+    let (first, file) = path.split_once('/').unwrap_or((path, ""));
+    let token = first.split('.').next().unwrap_or_default();
+    let resource = resources.lock().unwrap().get(token).cloned();
+    let response = match resource {
+        None => return Ok(Response::status(404)),
+        Some(Resource::Media {
+            source,
+            content_type,
+        }) => serve_source(&source, &content_type, request).await?,
+        Some(Resource::Subtitle(path)) => serve_subtitle(&path, request).await?,
+        Some(Resource::Hls(output)) => serve_hls(&output, file, request).await?,
+        Some(Resource::Transcode(output)) => {
+            let head = ResponseHead::new(200)
+                .header("Content-Type", "video/mp4")
+                .header("Cache-Control", "no-store");
+            Response::new(head, Body::Transcode(output))
+        }
+        Some(Resource::Data {
+            bytes,
+            content_type,
+        }) => {
+            let head = ResponseHead::new(200)
+                .header("Content-Type", content_type)
+                .header("Content-Length", bytes.len())
+                .header("Cache-Control", "no-cache");
+            Response::new(head, Body::Bytes(bytes))
         }
     };
+    Ok(response)
+}
 
-    let path = source.get_path();
-    let mime_type = get_mime_type(&path);
-
-    // Open stream
-    let mut stream = source.open().await?;
+/// Answers a CORS preflight. Receiver apps send one before fetching with a `Range`.
+fn preflight(request: &Request) -> Response {
+    let allow_headers = request
+        .header("Access-Control-Request-Headers")
+        .unwrap_or("Range");
+    let head = ResponseHead::new(204)
+        .header("Allow", ALLOWED_METHODS)
+        .header("Access-Control-Allow-Methods", ALLOWED_METHODS)
+        .header("Access-Control-Allow-Headers", allow_headers)
+        .header("Access-Control-Max-Age", 86_400);
+    Response::new(head, Body::Empty)
+}
 
-    // Determine size
-    let file_size = if let Some(s) = source.total_size() {
-        s
-    } else {
-        tokio::fs::metadata(&path).await?.len()
-    };
+/// Lets receiver apps, which fetch text tracks and manifests with XHR, read any response.
+fn cors(head: ResponseHead) -> ResponseHead {
+    head.header("Access-Control-Allow-Origin", "*").header(
+        "Access-Control-Expose-Headers",
+        "Accept-Ranges, Content-Length, Content-Range, ETag, Last-Modified",
+    )
+}
 
-    let (start, end) = parse_range(range_header, file_size);
-    let length = end - start + 1;
+fn with_validators(head: ResponseHead, validators: &Validators) -> ResponseHead {
+    head.header("ETag", &validators.etag)
+        .header("Last-Modified", http::http_date(validators.last_modified))
+}
 
-    stream.seek(SeekFrom::Start(start)).await?;
+/// Serves a file or torrent stream, honouring `Range`.
+async fn serve_source(
+    source: &StreamSource,
+    content_type: &str,
+    request: &Request,
+) -> std::io::Result<Response> {
+    let path = source.get_path();
+    // A torrent still downloading has no stable version to validate against.
+    let (size, validators) = match source.total_size() {
+        Some(size) => (size, None),
+        None => {
+            let metadata = tokio::fs::metadata(&path).await?;
+            (metadata.len(), Validators::from_metadata(&metadata))
+        }
+    };
+    if let Some(validators) = validators.as_ref().filter(|v| request.is_not_modified(v)) {
+        return Ok(Response::not_modified(validators));
+    }
+    let cache_control = match validators {
+        Some(_) => STATIC_CACHE_CONTROL,
+        None => "no-cache",
+    };
+    let head = |status| {
+        let head = ResponseHead::new(status)
+            .header("Accept-Ranges", "bytes")
+            .header("Cache-Control", cache_control);
+        match &validators {
+            Some(validators) => with_validators(head, validators),
+            None => head,
+        }
+    };
 
-    let status_line = if range_header.is_some() {
-        "HTTP/1.1 206 Partial Content"
-    } else {
-        "HTTP/1.1 200 OK"
+    let range = match request.range_is_current(validators.as_ref()) {
+        true => request.header("Range"),
+        false => None,
     };
+    let (head, start, length) = match http::resolve_range(range, size) {
+        ByteRange::Full => (head(200), 0, size),
+        ByteRange::Partial { start, end } => {
+            let range = format!("bytes {}-{}/{}", start, end, size);
+            (head(206).header("Content-Range", range), start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            let head = head(416)
+                .header("Content-Range", format!("bytes */{}", size))
+                .header("Content-Length", 0);
+            return Ok(Response::new(head, Body::Empty));
+        }
+    };
+    let head = head
+        .header("Content-Type", content_type)
+        .header("Content-Length", length);
+    if request.method == Method::Head {
+        return Ok(Response::new(head, Body::Empty));
+    }
 
-    let header = format!(
-        "{} \r\n\
-        Content-Type: {}\r\n\
-        Content-Length: {}\r\n\
-        Content-Range: bytes {}-{}/{}\r\n\
-        Connection: keep-alive\r\n\
-        Accept-Ranges: bytes\r\n\
-        \r\n",
-        status_line, mime_type, length, start, end, file_size
-    );
+    let mut reader = source.open().await?;
+    reader.seek(SeekFrom::Start(start)).await?;
+    Ok(Response::new(head, Body::Stream { reader, length }))
+}
 
-    socket.write_all(header.as_bytes()).await?;
+/// Serves the subtitle track, converting SRT to the WebVTT that Cast receivers expect.
+async fn serve_subtitle(path: &Path, request: &Request) -> std::io::Result<Response> {
+    if path.extension().is_some_and(|e| e == "srt") {
+        use crate::utils::subtitles::srt_to_vtt;
+        let metadata = tokio::fs::metadata(path).await?;
+        let mut head = ResponseHead::new(200).header("Cache-Control", STATIC_CACHE_CONTROL);
+        if let Some(validators) = Validators::from_metadata(&metadata) {
+            if request.is_not_modified(&validators) {
+                return Ok(Response::not_modified(&validators));
+            }
+            head = with_validators(head, &validators);
+        }
+        let vtt = srt_to_vtt(&tokio::fs::read_to_string(path).await?);
+        let head = head
+            .header("Content-Type", "text/vtt")
+            .header("Content-Length", vtt.len());
+        return Ok(Response::new(head, Body::Bytes(vtt.into())));
+    }
+    let content_type = get_mime_type(path);
+    serve_source(&StreamSource::Static(path.to_path_buf()), content_type, request).await
+}
 
-    let config = StreamConfig::default();
-    stream_file_buffered(&mut socket, stream, config, length).await?;
+/// Serves the playlist or a segment from an HLS directory.
+async fn serve_hls(output: &HlsOutput, file: &str, request: &Request) -> std::io::Result<Response> {
+    // Only plain file names, so a request cannot leave the directory.
+    let plain = file
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'-');
+    if file.is_empty() || file.starts_with('.') || !plain {
+        return Ok(Response::status(404));
+    }
+    let path = output.dir.join(file);
+    if file != HLS_PLAYLIST {
+        return serve_source(&StreamSource::Static(path.clone()), get_mime_type(&path), request)
+            .await;
+    }
+    // The playlist grows until the segmenter finishes, so it must not be cached.
+    let playlist = Bytes::from(tokio::fs::read(&path).await?);
+    let head = ResponseHead::new(200)
+        .header("Content-Type", get_mime_type(&path))
+        .header("Content-Length", playlist.len())
+        .header("Cache-Control", "no-cache");
+    Ok(Response::new(head, Body::Bytes(playlist)))
+}
 
-    Ok(())
+/// Writes `response`, omitting the body for HEAD. Returns whether the connection can carry
+/// another request.
+async fn send<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    request: &Request,
+    response: Response,
+    keep_alive: bool,
+) -> std::io::Result<bool> {
+    let Response { head, body } = response;
+    let mut head = cors(head).header("Date", http::http_date(std::time::SystemTime::now()));
+    let mut keep_alive = keep_alive;
+    let chunked = request.minor_version > 0;
+    if let Body::Transcode(_) = body {
+        if chunked {
+            head = head.header("Transfer-Encoding", "chunked");
+        } else {
+            // Without chunked encoding only closing the connection marks the end.
+            keep_alive = false;
+        }
+    }
+    writer.write_all(&head.to_bytes(keep_alive)).await?;
+    if request.method == Method::Head {
+        return Ok(keep_alive);
+    }
+
+    match body {
+        Body::Empty => {}
+        Body::Bytes(bytes) => writer.write_all(&bytes).await?,
+        Body::Stream { reader, length } => {
+            let sent = stream_file_buffered(writer, reader, StreamConfig::default(), length).await?;
+            // A short body leaves the client unable to find the next response.
+            if sent < length {
+                log::warn!("Source ended {} bytes early", length - sent);
+                return Ok(false);
+            }
+        }
+        Body::Transcode(output) => {
+            stream_transcode(writer, &output.path, &output.done, chunked).await?
+        }
+    }
+    Ok(keep_alive)
 }
 
 async fn producer_task<R>(
@@ -477,27 +941,6 @@ where
     Ok(())
 }
 
-fn parse_range(range: Option<&str>, file_size: u64) -> (u64, u64) {
-    if let Some(r) = range {
-        if let Some((start_str, end_str)) = r.split_once('-') {
-            if start_str.is_empty() {
-                if let Ok(suffix) = end_str.parse::<u64>() {
-                    return (file_size.saturating_sub(suffix), file_size - 1);
-                }
-            }
-
-            let start = start_str.parse::<u64>().unwrap_or(0);
-            let end = if end_str.is_empty() {
-                file_size - 1
-            } else {
-                end_str.parse::<u64>().unwrap_or(file_size - 1)
-            };
-            return (start, std::cmp::min(end, file_size - 1));
-        }
-    }
-    (0, file_size - 1)
-}
-
 pub fn get_mime_type(path: &Path) -> &'static str {
     match path.extension().and_then(|ext| ext.to_str()) {
         Some("mp4") | Some("m4v") => "video/mp4",
@@ -513,6 +956,9 @@ pub fn get_mime_type(path: &Path) -> &'static str {
         Some("png") => "image/png",
         Some("gif") => "image/gif",
         Some("webp") => "image/webp",
+        Some("vtt") => "text/vtt",
+        Some("ts") => "video/mp2t",
+        Some("m3u8") => "application/vnd.apple.mpegurl",
         _ => "application/octet-stream",
     }
 }
@@ -520,7 +966,6 @@ pub fn get_mime_type(path: &Path) -> &'static str {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
 
     #[test]
     fn test_mime_type_detection() {
@@ -533,15 +978,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_range_parsing() {
-        let size = 1000;
-        assert_eq!(parse_range(Some("0-499"), size), (0, 499));
-        assert_eq!(parse_range(Some("500-"), size), (500, 999));
-        assert_eq!(parse_range(Some("-500"), size), (500, 999));
-        assert_eq!(parse_range(None, size), (0, 999));
-    }
-
     #[tokio::test]
     async fn test_producer_task_basic() {
         let data = vec![1u8, 2, 3, 4, 5, 6, 7, 8];
@@ -585,7 +1021,8 @@ mod tests {
 
         assert_eq!(received_data, data);
     }
-}    #[tokio::test]
+
+    #[tokio::test]
     async fn test_server_start_binding() {
         let mut server = StreamServer::new();
         let local_ip = "127.0.0.1";
@@ -595,7 +1032,7 @@ mod tests {
         assert!(url_res.is_ok());
         let url = url_res.unwrap();
         assert!(url.starts_with(&format!("http://{}:", local_ip)));
-        let port_part = url.split(':').last().unwrap();
+        let port_part = url.split(':').next_back().unwrap();
         let port: u16 = port_part.parse().expect("Port should be a number");
         assert!(port > 0);
         assert_eq!(server.port, port);
@@ -612,34 +1049,294 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_server_state_management() {
+    async fn test_resource_registry() {
+        let mut server = StreamServer::new();
+        let base = server.start("127.0.0.1", None).await.unwrap();
+
+        let movie = server.add(Resource::file("Movie.MKV"));
+        let subtitle = server.add(Resource::Subtitle(PathBuf::from("movie.srt")));
+        let playlist = server.add(Resource::Data {
+            bytes: Bytes::from_static(b"#EXTM3U\n"),
+            content_type: "application/vnd.apple.mpegurl".to_string(),
+        });
+        assert_eq!(server.len(), 3);
+        assert_ne!(movie.token, subtitle.token);
+        assert_eq!(movie.token.len(), 32);
+        assert_eq!(movie.url, format!("{}/{}.mkv", base, movie.token));
+        assert_eq!(subtitle.url, format!("{}/{}.vtt", base, subtitle.token));
+        assert_eq!(playlist.url, format!("{}/{}", base, playlist.token));
+
+        assert!(server.remove(&movie.token));
+        assert!(!server.contains(&movie.token));
+        assert!(!server.remove(&movie.token));
+        assert!(server.contains(&subtitle.token));
+    }
+
+    #[tokio::test]
+    async fn test_shared_transcode_output() {
+        let mut process = tokio::process::Command::new("echo")
+            .arg("transcoded")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = process.stdout.take().unwrap();
+        let path = std::env::temp_dir().join(format!("castru_shared_{}.part", uuid::Uuid::new_v4().simple()));
+        let output = TranscodeOutput::start(
+            TranscodingPipeline {
+                process,
+                stdout,
+                start_time: 0.0,
+            },
+            path.clone(),
+            Some("0123456789abcdef".to_string()),
+        )
+        .unwrap();
+        assert!(output.wait_complete().await);
+        assert_eq!(std::fs::read(&path).unwrap(), b"transcoded\n");
+
         let server = StreamServer::new();
+        let first = server.add(Resource::Transcode(output.clone()));
+        let reused = server.find_transcode("0123456789abcdef").unwrap();
+        assert!(Arc::ptr_eq(&reused, &output));
+        assert!(server.find_transcode("fedcba9876543210").is_none());
+        let second = server.add(Resource::Transcode(reused));
+
+        // The output outlives the token it was first served under.
+        assert!(server.remove(&first.token));
+        assert!(server.find_transcode("0123456789abcdef").is_some());
+        assert!(server.remove(&second.token));
+        drop(output);
+        assert!(!path.exists());
+    }
 
-        // Test set_file
-        let path = PathBuf::from("test_video.mp4");
-        server.set_file(path.clone()).await;
-        
-        {
-            let source_guard = server.source.lock().unwrap();
-            match source_guard.as_ref() {
-                Some(StreamSource::Static(p)) => assert_eq!(*p, path),
-                _ => panic!("Expected Static source"),
+    /// Reads one response from `reader`, returning its head and body (none for HEAD).
+    async fn read_response<R: tokio::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+        head_request: bool,
+    ) -> (String, Vec<u8>) {
+        use tokio::io::AsyncBufReadExt;
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            if line == "\r\n" {
+                break;
             }
+            head.push_str(&line);
         }
+        let length = head
+            .lines()
+            .find_map(|l| l.strip_prefix("Content-Length: "))
+            .map_or(0, |l| l.parse().unwrap());
+        let length = if head_request { 0 } else { length };
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.unwrap();
+        (head, body)
+    }
 
-        // Test set_subtitle
-        let sub_path = PathBuf::from("test.vtt");
-        server.set_subtitle(sub_path.clone()).await;
-        
-        {
-            let sub_guard = server.subtitle_path.lock().unwrap();
-            assert_eq!(*sub_guard, Some(sub_path));
-        }
+    #[tokio::test]
+    async fn test_keep_alive_head_and_ranges() {
+        let path = std::env::temp_dir().join(format!("castru_http_{}.mp4", uuid::Uuid::new_v4()));
+        let data: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let mut server = StreamServer::new();
+        let base = server.start("127.0.0.1", None).await.unwrap();
+        let movie = server.add(Resource::file(&path));
+        let movie_path = movie.url.strip_prefix(&base).unwrap();
+        let other = server.add(Resource::Data {
+            bytes: Bytes::from_static(b"other"),
+            content_type: "text/plain".to_string(),
+        });
+
+        let socket = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        // Several requests on one connection, the way Cast receivers probe and then play.
+        let requests = format!(
+            "HEAD {movie} HTTP/1.1\r\nHost: cast\r\n\r\n\
+             GET {movie} HTTP/1.1\r\nhost: cast\r\nrange: bytes=100-199\r\n\r\n\
+             GET {movie} HTTP/1.1\r\nHost: cast\r\nRange: bytes=2000-\r\n\r\n\
+             GET / HTTP/1.1\r\nHost: cast\r\n\r\n\
+             POST {movie} HTTP/1.1\r\nHost: cast\r\n\r\n\
+             GET /{other} HTTP/1.1\r\nHost: cast\r\n\r\n\
+             GET {movie} HTTP/1.1\r\nHost: cast\r\nConnection: close\r\n\r\n",
+            movie = movie_path,
+            other = other.token,
+        );
+        writer.write_all(requests.as_bytes()).await.unwrap();
+
+        let (head, body) = read_response(&mut reader, true).await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Length: 1000\r\n"));
+        assert!(head.contains("Content-Type: video/mp4\r\n"));
+        assert!(head.contains("Connection: keep-alive\r\n"));
+        assert!(body.is_empty());
+
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(head.contains("Content-Range: bytes 100-199/1000\r\n"));
+        assert_eq!(body, &data[100..200]);
+
+        let (head, _) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 416 "));
+        assert!(head.contains("Content-Range: bytes */1000\r\n"));
+
+        let (head, _) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 404 "));
+
+        let (head, _) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 405 "));
+        assert!(head.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.contains("Content-Type: text/plain\r\n"));
+        assert_eq!(body, b"other");
+
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.contains("Connection: close\r\n"));
+        assert_eq!(body, data);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    /// Sends `text` with its line endings turned into CRLF.
+    async fn write_request<W: AsyncWrite + Unpin>(writer: &mut W, text: String) {
+        writer
+            .write_all(text.replace('\n', "\r\n").as_bytes())
+            .await
+            .unwrap();
+    }
 
-        // Test clearing logic (set_source should clear subtitle)
-        server.set_source(StreamSource::Static(PathBuf::from("new.mp4"))).await;
-        {
-             let sub_guard = server.subtitle_path.lock().unwrap();
-             assert!(sub_guard.is_none());
+    #[tokio::test]
+    async fn test_cors_and_conditional_requests() {
+        let path = std::env::temp_dir().join(format!("castru_http_{}.srt", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, "1\n00:00:01,000 --> 00:00:02,000\nHello\n")
+            .await
+            .unwrap();
+        let mut server = StreamServer::new();
+        let base = server.start("127.0.0.1", None).await.unwrap();
+        let subtitle = server.add(Resource::Subtitle(path.clone()));
+        let subtitle_path = subtitle.url.strip_prefix(&base).unwrap();
+
+        let socket = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+        write_request(&mut writer, format!(
+            "OPTIONS {} HTTP/1.1\nHost: cast\nOrigin: https://receiver\n\
+             Access-Control-Request-Method: GET\nAccess-Control-Request-Headers: range\n\n",
+            subtitle_path
+        ))
+        .await;
+        let (head, _) = read_response(&mut reader, true).await;
+        assert!(head.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(head.contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(head.contains("Access-Control-Allow-Methods: GET, HEAD, OPTIONS\r\n"));
+        assert!(head.contains("Access-Control-Allow-Headers: range\r\n"));
+
+        write_request(&mut writer, format!("GET {} HTTP/1.1\nHost: cast\n\n", subtitle_path)).await;
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(head.contains("Content-Type: text/vtt\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(head.contains("Cache-Control: max-age=3600\r\n"));
+        assert!(head.contains("Last-Modified: "));
+        assert!(String::from_utf8(body).unwrap().starts_with("WEBVTT"));
+        let etag = head
+            .lines()
+            .find_map(|l| l.strip_prefix("ETag: "))
+            .unwrap()
+            .to_string();
+
+        write_request(&mut writer, format!(
+            "GET {} HTTP/1.1\nHost: cast\nIf-None-Match: {}\n\n",
+            subtitle_path, etag
+        ))
+        .await;
+        let (head, _) = read_response(&mut reader, true).await;
+        assert!(head.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+        assert!(head.contains(&format!("ETag: {}\r\n", etag)));
+
+        // Errors carry CORS headers too, so the receiver can see what went wrong.
+        write_request(&mut writer, "GET /missing HTTP/1.1\nHost: cast\n\n".to_string()).await;
+        let (head, _) = read_response(&mut reader, false).await;
+        assert!(head.starts_with("HTTP/1.1 404 "));
+        assert!(head.contains("Access-Control-Allow-Origin: *\r\n"));
+        assert!(head.contains("Cache-Control: no-store\r\n"));
+
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn test_hls_directory() {
+        let dir = std::env::temp_dir().join(format!("castru_hls_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(HLS_PLAYLIST),
+            "#EXTM3U\n#EXT-X-PLAYLIST-TYPE:EVENT\n#EXTINF:4.0,\nsegment00000.ts\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("segment00000.ts"), [0x47u8; 188]).unwrap();
+        let output = HlsOutput::new(HlsPipeline {
+            process: tokio::process::Command::new("true").spawn().unwrap(),
+            dir: dir.clone(),
+            start_time: 0.0,
+        });
+        output.wait_ready(Duration::from_secs(1)).await.unwrap();
+        assert_eq!(output.produced_duration(), 4.0);
+
+        let mut server = StreamServer::new();
+        let base = server.start("127.0.0.1", None).await.unwrap();
+        let hls = server.add(Resource::Hls(output));
+        assert_eq!(hls.url, format!("{}/{}/index.m3u8", base, hls.token));
+        let playlist_path = hls.url.strip_prefix(&base).unwrap();
+        let segment_path = playlist_path.replace(HLS_PLAYLIST, "segment00000.ts");
+
+        let socket = TcpStream::connect(base.trim_start_matches("http://")).await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader);
+
+        write_request(&mut writer, format!("GET {} HTTP/1.1\nHost: cast\n\n", playlist_path)).await;
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.contains("Content-Type: application/vnd.apple.mpegurl\r\n"));
+        assert!(head.contains("Cache-Control: no-cache\r\n"));
+        assert!(String::from_utf8(body).unwrap().contains("segment00000.ts"));
+
+        write_request(&mut writer, format!("GET {} HTTP/1.1\nHost: cast\n\n", segment_path)).await;
+        let (head, body) = read_response(&mut reader, false).await;
+        assert!(head.contains("Content-Type: video/mp2t\r\n"));
+        assert_eq!(body.len(), 188);
+
+        // Nothing outside the directory is reachable, even a file right next to it.
+        let outside = dir.with_extension("ts");
+        std::fs::write(&outside, [0x47u8; 188]).unwrap();
+        let name = outside.file_name().unwrap().to_str().unwrap();
+        for escape in [format!("/{}/../{}", hls.token, name), format!("/{}/..", hls.token)] {
+            write_request(&mut writer, format!("GET {} HTTP/1.1\nHost: cast\n\n", escape)).await;
+            let (head, _) = read_response(&mut reader, false).await;
+            assert!(head.starts_with("HTTP/1.1 404 "), "{} was served", escape);
         }
+        let _ = std::fs::remove_file(&outside);
+
+        // Removing the resource deletes its directory once nothing refers to it.
+        assert!(server.remove(&hls.token));
+        assert!(!dir.exists());
     }
+
+    #[tokio::test]
+    async fn test_malformed_request_is_rejected() {
+        let mut server = StreamServer::new();
+        let url = server.start("127.0.0.1", None).await.unwrap();
+        let mut socket = TcpStream::connect(url.trim_start_matches("http://")).await.unwrap();
+        socket.write_all(b"NONSENSE\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+    }
+}
@@ -0,0 +1,590 @@
+//! Minimal HTTP/1.1 request parsing and response heads for [`StreamServer`](super::StreamServer).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Largest request head (request line plus headers) we accept.
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Options,
+    Other(String),
+}
+
+impl Method {
+    fn parse(method: &str) -> Self {
+        match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "OPTIONS" => Method::Options,
+            other => Method::Other(other.to_string()),
+        }
+    }
+}
+
+/// A parsed request head. Request bodies are not supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    /// Path without the query, e.g. `/subtitle`.
+    pub path: String,
+    /// Everything after `?`, if present.
+    pub query: Option<String>,
+    /// Minor version: 0 for HTTP/1.0, 1 for HTTP/1.1.
+    pub minor_version: u8,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// The value of header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the client wants the connection kept open after this request.
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.header("Connection").unwrap_or_default();
+        let has = |token: &str| {
+            connection
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        };
+        if self.minor_version == 0 {
+            has("keep-alive")
+        } else {
+            !has("close")
+        }
+    }
+
+    /// Whether a conditional GET or HEAD can be answered with 304 Not Modified
+    /// (RFC 9110 section 13.2.2).
+    pub fn is_not_modified(&self, validators: &Validators) -> bool {
+        if !matches!(self.method, Method::Get | Method::Head) {
+            return false;
+        }
+        if let Some(tags) = self.header("If-None-Match") {
+            return tags.trim() == "*"
+                || tags
+                    .split(',')
+                    .any(|tag| opaque_tag(tag) == opaque_tag(&validators.etag));
+        }
+        self.header("If-Modified-Since")
+            .and_then(parse_http_date)
+            .is_some_and(|since| validators.last_modified <= since)
+    }
+
+    /// Whether a `Range` should be honoured given `If-Range`: only if the client's copy is
+    /// still current. Without validators we cannot tell, so only an unconditional range is.
+    pub fn range_is_current(&self, validators: Option<&Validators>) -> bool {
+        let Some(if_range) = self.header("If-Range") else {
+            return true;
+        };
+        let Some(validators) = validators else {
+            return false;
+        };
+        if if_range.starts_with('"') {
+            // Entity tags must match strongly.
+            if_range == validators.etag
+        } else {
+            parse_http_date(if_range) == Some(validators.last_modified)
+        }
+    }
+
+    /// Whether a body follows the head; we cannot skip it, so the connection must close.
+    pub fn has_body(&self) -> bool {
+        self.header("Transfer-Encoding").is_some()
+            || self
+                .header("Content-Length")
+                .is_some_and(|l| l.trim() != "0")
+    }
+}
+
+/// Why a request head could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Not a valid HTTP/1.x request (400).
+    Malformed,
+    /// The head exceeded [`MAX_HEAD_SIZE`] (431).
+    TooLarge,
+    /// A protocol version other than HTTP/1.0 or 1.1 (505).
+    UnsupportedVersion,
+}
+
+impl ParseError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ParseError::Malformed => 400,
+            ParseError::TooLarge => 431,
+            ParseError::UnsupportedVersion => 505,
+        }
+    }
+}
+
+/// Reads the next request head from `reader`.
+///
+/// Returns `Ok(None)` if the client closed the connection between requests.
+pub async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Result<Option<Request>, ParseError>> {
+    let mut head = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = (&mut *reader)
+            .take((MAX_HEAD_SIZE + 1 - head.len()) as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if n == 0 {
+            return Ok(if head.is_empty() {
+                Ok(None)
+            } else {
+                Err(ParseError::Malformed)
+            });
+        }
+        if !line.ends_with(b"\n") {
+            return Ok(Err(ParseError::TooLarge));
+        }
+        let blank = line == b"\r\n" || line == b"\n";
+        // Empty lines before the request line are allowed (RFC 9112 section 2.2).
+        if blank && head.is_empty() {
+            continue;
+        }
+        if blank {
+            break;
+        }
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEAD_SIZE {
+            return Ok(Err(ParseError::TooLarge));
+        }
+    }
+    let Ok(head) = std::str::from_utf8(&head) else {
+        return Ok(Err(ParseError::Malformed));
+    };
+    Ok(parse_head(head).map(Some))
+}
+
+/// Parses a request line and headers, without the terminating blank line.
+pub fn parse_head(head: &str) -> Result<Request, ParseError> {
+    let mut lines = head.lines();
+    let request_line = lines.next().ok_or(ParseError::Malformed)?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ParseError::Malformed);
+    };
+    if method.is_empty() || !method.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return Err(ParseError::Malformed);
+    }
+    let minor_version = match version {
+        "HTTP/1.1" => 1,
+        "HTTP/1.0" => 0,
+        v if v.starts_with("HTTP/") => return Err(ParseError::UnsupportedVersion),
+        _ => return Err(ParseError::Malformed),
+    };
+
+    let mut headers = Vec::new();
+    for line in lines {
+        // Obsolete line folding is rejected (RFC 9112 section 5.2).
+        if line.starts_with([' ', '\t']) {
+            return Err(ParseError::Malformed);
+        }
+        let (name, value) = line.split_once(':').ok_or(ParseError::Malformed)?;
+        if name.is_empty() || name.contains([' ', '\t']) {
+            return Err(ParseError::Malformed);
+        }
+        headers.push((name.to_string(), value.trim().to_string()));
+    }
+
+    let (path, query) = split_target(target)?;
+    let request = Request {
+        method: Method::parse(method),
+        path,
+        query,
+        minor_version,
+        headers,
+    };
+    // HTTP/1.1 requests must name the host (RFC 9112 section 3.2).
+    if minor_version == 1 && request.header("Host").is_none() {
+        return Err(ParseError::Malformed);
+    }
+    Ok(request)
+}
+
+/// Splits an origin-form (`/a?b`), absolute-form (`http://host/a?b`) or `*` target.
+fn split_target(target: &str) -> Result<(String, Option<String>), ParseError> {
+    let target = match target.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => target,
+    };
+    if target != "*" && !target.starts_with('/') {
+        return Err(ParseError::Malformed);
+    }
+    let target = target.split('#').next().unwrap_or_default();
+    Ok(match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    })
+}
+
+/// The outcome of applying a `Range` header to a resource of known size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable `Range` header: send everything with 200.
+    Full,
+    /// Send bytes `start..=end` with 206.
+    Partial { start: u64, end: u64 },
+    /// None of the ranges overlap the resource: 416.
+    Unsatisfiable,
+}
+
+/// Resolves a `Range` header against a resource of `size` bytes.
+///
+/// Headers that do not parse, or use a unit other than bytes, are ignored as RFC 9110
+/// allows. Several ranges are coalesced into one span covering them all, which spares us
+/// `multipart/byteranges`; Cast receivers only ever ask for one.
+pub fn resolve_range(header: Option<&str>, size: u64) -> ByteRange {
+    let Some(header) = header else {
+        return ByteRange::Full;
+    };
+    let Some((unit, specs)) = header.split_once('=') else {
+        return ByteRange::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return ByteRange::Full;
+    }
+
+    let mut span: Option<(u64, u64)> = None;
+    let mut parsed_any = false;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        parsed_any = true;
+        let Some((first, last)) = spec.split_once('-') else {
+            return ByteRange::Full;
+        };
+        let number = |s: &str| -> Option<u64> {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            s.parse().ok()
+        };
+        let range = match (first.trim(), last.trim()) {
+            ("", suffix) => {
+                let Some(suffix) = number(suffix) else {
+                    return ByteRange::Full;
+                };
+                (suffix > 0 && size > 0).then(|| (size.saturating_sub(suffix), size - 1))
+            }
+            (first, "") => {
+                let Some(first) = number(first) else {
+                    return ByteRange::Full;
+                };
+                (first < size).then(|| (first, size - 1))
+            }
+            (first, last) => {
+                let (Some(first), Some(last)) = (number(first), number(last)) else {
+                    return ByteRange::Full;
+                };
+                if first > last {
+                    return ByteRange::Full;
+                }
+                (first < size).then(|| (first, last.min(size - 1)))
+            }
+        };
+        if let Some((start, end)) = range {
+            span = Some(match span {
+                Some((s, e)) => (s.min(start), e.max(end)),
+                None => (start, end),
+            });
+        }
+    }
+    match span {
+        Some((start, end)) => ByteRange::Partial { start, end },
+        // `bytes=` with no ranges at all is invalid rather than unsatisfiable.
+        None if !parsed_any => ByteRange::Full,
+        None => ByteRange::Unsatisfiable,
+    }
+}
+
+/// What identifies a version of a file, for conditional requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    /// A strong entity tag, quoted.
+    pub etag: String,
+    /// Truncated to whole seconds, as HTTP dates are.
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    /// Derives validators from a file's size and modification time.
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            etag: format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos()),
+            last_modified: UNIX_EPOCH + Duration::from_secs(modified.as_secs()),
+        })
+    }
+}
+
+/// An entity tag without its weakness marker, for weak comparison.
+fn opaque_tag(tag: &str) -> &str {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats `time` as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = (secs / 86_400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let rem = secs % 86_400;
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Parses an IMF-fixdate. The obsolete RFC 850 and asctime forms are not accepted, which
+/// only means the condition they carry is ignored.
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: u32 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|m| m == month)? as u32 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut hms = time.split(':').map(|n| n.parse::<u64>().ok());
+    let (Some(Some(h)), Some(Some(m)), Some(Some(s)), None) =
+        (hms.next(), hms.next(), hms.next(), hms.next())
+    else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + h * 3600 + m * 60 + s))
+}
+
+/// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// A response status line and headers.
+#[derive(Debug, Clone)]
+pub struct ResponseHead {
+    pub status: u16,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl ResponseHead {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// Serializes the head, announcing whether the connection stays open.
+    pub fn to_bytes(&self, keep_alive: bool) -> Vec<u8> {
+        let mut out = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        out.push_str(&format!("Connection: {}\r\n\r\n", connection));
+        out.into_bytes()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        206 => "Partial Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        416 => "Range Not Satisfiable",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_head() {
+        let request = parse_head(
+            "GET /subtitle?lang=en HTTP/1.1\r\nhost: 10.0.0.2:8000\r\nRANGE:  bytes=0-99 \r\n",
+        )
+        .unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.path, "/subtitle");
+        assert_eq!(request.query.as_deref(), Some("lang=en"));
+        assert_eq!(request.header("Range"), Some("bytes=0-99"));
+        assert_eq!(request.header("Host"), Some("10.0.0.2:8000"));
+        assert!(request.keep_alive());
+
+        let request = parse_head("HEAD http://10.0.0.2:8000/?t=30 HTTP/1.0\r\n").unwrap();
+        assert_eq!(request.method, Method::Head);
+        assert_eq!(request.path, "/");
+        assert_eq!(request.query.as_deref(), Some("t=30"));
+        assert!(!request.keep_alive());
+
+        let request = parse_head("GET / HTTP/1.1\r\nHost: x\r\nConnection: Close\r\n").unwrap();
+        assert!(!request.keep_alive());
+    }
+
+    #[test]
+    fn test_parse_head_errors() {
+        assert_eq!(parse_head("GET /"), Err(ParseError::Malformed));
+        assert_eq!(parse_head("GET / HTTP/1.1\r\n"), Err(ParseError::Malformed)); // no Host
+        assert_eq!(parse_head("GET / HTTP/2.0\r\nHost: x\r\n"), Err(ParseError::UnsupportedVersion));
+        assert_eq!(parse_head("GET / HTTP/1.1\r\nHost x\r\n"), Err(ParseError::Malformed));
+        assert_eq!(
+            parse_head("GET / HTTP/1.1\r\nHost: x\r\n folded\r\n"),
+            Err(ParseError::Malformed)
+        );
+        assert_eq!(parse_head("GET relative HTTP/1.1\r\nHost: x\r\n"), Err(ParseError::Malformed));
+    }
+
+    #[tokio::test]
+    async fn test_read_requests_back_to_back() {
+        let input: &[u8] = b"\r\nGET /a HTTP/1.1\r\nHost: x\r\n\r\nHEAD /b HTTP/1.1\nHost: x\n\n";
+        let mut reader = tokio::io::BufReader::new(input);
+        let first = read_request(&mut reader).await.unwrap().unwrap().unwrap();
+        assert_eq!(first.path, "/a");
+        let second = read_request(&mut reader).await.unwrap().unwrap().unwrap();
+        assert_eq!((second.method, second.path.as_str()), (Method::Head, "/b"));
+        assert_eq!(read_request(&mut reader).await.unwrap(), Ok(None));
+
+        let huge = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        let mut reader = tokio::io::BufReader::new(huge.as_bytes());
+        assert_eq!(read_request(&mut reader).await.unwrap(), Err(ParseError::TooLarge));
+
+        // Lines that end exactly one byte past the limit.
+        let request_line = "GET / HTTP/1.1\r\n";
+        let padding = "a".repeat(MAX_HEAD_SIZE + 1 - request_line.len() - "X: \r\n".len());
+        let over = format!("{}X: {}\r\n", request_line, padding);
+        assert_eq!(over.len(), MAX_HEAD_SIZE + 1);
+        let over = over + "\r\n";
+        let mut reader = tokio::io::BufReader::new(over.as_bytes());
+        assert_eq!(read_request(&mut reader).await.unwrap(), Err(ParseError::TooLarge));
+    }
+
+    #[test]
+    fn test_http_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(http_date(leap), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(parse_http_date(&http_date(leap)), Some(leap));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_conditional_requests() {
+        let validators = Validators {
+            etag: "\"3e8-1\"".to_string(),
+            last_modified: UNIX_EPOCH + Duration::from_secs(784_111_777),
+        };
+        let request = |headers: &str| {
+            parse_head(&format!("GET / HTTP/1.1\r\nHost: x\r\n{}", headers)).unwrap()
+        };
+
+        assert!(!request("").is_not_modified(&validators));
+        assert!(request("If-None-Match: \"a\", W/\"3e8-1\"\r\n").is_not_modified(&validators));
+        assert!(request("If-None-Match: *\r\n").is_not_modified(&validators));
+        // If-None-Match takes precedence over If-Modified-Since.
+        assert!(!request(
+            "If-None-Match: \"old\"\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n"
+        )
+        .is_not_modified(&validators));
+        assert!(request("If-Modified-Since: Mon, 07 Nov 1994 00:00:00 GMT\r\n")
+            .is_not_modified(&validators));
+        assert!(!request("If-Modified-Since: Sat, 05 Nov 1994 00:00:00 GMT\r\n")
+            .is_not_modified(&validators));
+
+        assert!(request("").range_is_current(None));
+        assert!(request("If-Range: \"3e8-1\"\r\n").range_is_current(Some(&validators)));
+        assert!(!request("If-Range: W/\"3e8-1\"\r\n").range_is_current(Some(&validators)));
+        assert!(request("If-Range: Sun, 06 Nov 1994 08:49:37 GMT\r\n")
+            .range_is_current(Some(&validators)));
+        assert!(!request("If-Range: \"3e8-1\"\r\n").range_is_current(None));
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        let size = 1000;
+        let partial = |start, end| ByteRange::Partial { start, end };
+        assert_eq!(resolve_range(None, size), ByteRange::Full);
+        assert_eq!(resolve_range(Some("bytes=0-499"), size), partial(0, 499));
+        assert_eq!(resolve_range(Some("bytes=500-"), size), partial(500, 999));
+        assert_eq!(resolve_range(Some("bytes=-500"), size), partial(500, 999));
+        assert_eq!(resolve_range(Some("bytes=900-5000"), size), partial(900, 999));
+        // Several ranges are coalesced.
+        assert_eq!(resolve_range(Some("bytes=0-9, 500-599"), size), partial(0, 599));
+        assert_eq!(resolve_range(Some("bytes=5000-6000, 10-19"), size), partial(10, 19));
+        // Nothing satisfiable.
+        assert_eq!(resolve_range(Some("bytes=1000-"), size), ByteRange::Unsatisfiable);
+        assert_eq!(resolve_range(Some("bytes=-0"), size), ByteRange::Unsatisfiable);
+        assert_eq!(resolve_range(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        // Invalid headers are ignored.
+        assert_eq!(resolve_range(Some("bytes=9-1"), size), ByteRange::Full);
+        assert_eq!(resolve_range(Some("bytes=abc"), size), ByteRange::Full);
+        assert_eq!(resolve_range(Some("items=0-1"), size), ByteRange::Full);
+        assert_eq!(resolve_range(Some("bytes="), size), ByteRange::Full);
+        assert_eq!(resolve_range(Some("bytes= ,"), size), ByteRange::Full);
+    }
+}
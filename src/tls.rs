@@ -1,6 +1,11 @@
+use crate::error::CastError;
 use rustls::client::{ServerCertVerified, ServerCertVerifier};
-use rustls::{Certificate, ClientConfig, ServerName};
-use std::sync::Arc;
+use rustls::{Certificate, CertificateError, ClientConfig, ServerName};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 use tokio_rustls::TlsConnector;
 
@@ -20,10 +25,220 @@ impl ServerCertVerifier for NoCertificateVerification {
     }
 }
 
+/// How the device's TLS certificate is checked.
+///
+/// Cast devices use self-signed certificates, so there is no CA to verify against.
+#[derive(Clone, Default)]
+pub enum CertificatePolicy {
+    /// Accept any certificate.
+    #[default]
+    AcceptAny,
+    /// Remember the certificate fingerprint the first time we see `device_id` (usually
+    /// [`crate::CastDevice::uuid`]) and reject any other certificate afterwards.
+    TrustOnFirstUse {
+        device_id: String,
+        store: Arc<FingerprintStore>,
+    },
+}
+
+impl fmt::Debug for CertificatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificatePolicy::AcceptAny => write!(f, "AcceptAny"),
+            CertificatePolicy::TrustOnFirstUse { device_id, .. } => f
+                .debug_struct("TrustOnFirstUse")
+                .field("device_id", device_id)
+                .finish(),
+        }
+    }
+}
+
+/// Returns the SHA-256 fingerprint of a DER certificate as lowercase hex.
+pub fn fingerprint(cert_der: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert_der)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The certificate presented by a device differs from the one pinned for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintMismatch {
+    pub device_id: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for FingerprintMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "certificate for {} changed (pinned {}, got {})",
+            self.device_id, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for FingerprintMismatch {}
+
+impl From<FingerprintMismatch> for CastError {
+    fn from(m: FingerprintMismatch) -> Self {
+        CastError::CertificateMismatch {
+            device_id: m.device_id,
+            expected: m.expected,
+            actual: m.actual,
+        }
+    }
+}
+
+/// Certificate fingerprints pinned per device, persisted as a JSON file.
+///
+/// New pins are only held in memory until [`FingerprintStore::flush`], since they are made
+/// during the TLS handshake where blocking on the file system would stall it.
+#[derive(Debug, Default)]
+pub struct FingerprintStore {
+    path: Option<PathBuf>,
+    fingerprints: Mutex<BTreeMap<String, String>>,
+    /// Pins were added since the file was last written.
+    unsaved: AtomicBool,
+}
+
+impl FingerprintStore {
+    /// Loads the store at `path`, starting empty if the file does not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, CastError> {
+        let path = path.into();
+        let fingerprints = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            fingerprints: Mutex::new(fingerprints),
+            unsaved: AtomicBool::new(false),
+        })
+    }
+
+    /// A store that is never written to disk.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// `$XDG_CONFIG_HOME/castru/known_devices.json`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("castru").join("known_devices.json"))
+    }
+
+    /// Returns the fingerprint pinned for `device_id`.
+    pub fn get(&self, device_id: &str) -> Option<String> {
+        self.fingerprints.lock().unwrap().get(device_id).cloned()
+    }
+
+    /// Unpins `device_id`, e.g. after its certificate was legitimately replaced.
+    pub fn forget(&self, device_id: &str) -> Result<(), CastError> {
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        fingerprints.remove(device_id);
+        self.save(&fingerprints)
+    }
+
+    /// Pins `fingerprint` if `device_id` is new, otherwise checks it matches.
+    pub fn check(&self, device_id: &str, fingerprint: &str) -> Result<(), FingerprintMismatch> {
+        let mut fingerprints = self.fingerprints.lock().unwrap();
+        match fingerprints.get(device_id) {
+            Some(expected) if expected == fingerprint => Ok(()),
+            Some(expected) => Err(FingerprintMismatch {
+                device_id: device_id.to_string(),
+                expected: expected.clone(),
+                actual: fingerprint.to_string(),
+            }),
+            None => {
+                log::info!("Pinning certificate {} for {}", fingerprint, device_id);
+                fingerprints.insert(device_id.to_string(), fingerprint.to_string());
+                self.unsaved.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes pins added by [`FingerprintStore::check`] to disk, if there are any.
+    pub fn flush(&self) -> Result<(), CastError> {
+        let fingerprints = self.fingerprints.lock().unwrap();
+        if self.unsaved.swap(false, Ordering::SeqCst) {
+            if let Err(e) = self.save(&fingerprints) {
+                self.unsaved.store(true, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, fingerprints: &BTreeMap<String, String>) -> Result<(), CastError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(fingerprints)?)?;
+        Ok(())
+    }
+}
+
+/// Verifier for [`CertificatePolicy::TrustOnFirstUse`].
+pub struct PinnedCertificateVerification {
+    device_id: String,
+    store: Arc<FingerprintStore>,
+}
+
+impl ServerCertVerifier for PinnedCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.store
+            .check(&self.device_id, &fingerprint(&end_entity.0))
+            .map_err(|m| rustls::Error::InvalidCertificate(CertificateError::Other(Arc::new(m))))?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Extracts a [`FingerprintMismatch`] from a failed TLS handshake, if that was the cause.
+pub fn fingerprint_mismatch(error: &std::io::Error) -> Option<FingerprintMismatch> {
+    let rustls::Error::InvalidCertificate(CertificateError::Other(inner)) =
+        error.get_ref()?.downcast_ref::<rustls::Error>()?
+    else {
+        return None;
+    };
+    inner.downcast_ref::<FingerprintMismatch>().cloned()
+}
+
 pub fn create_tls_connector() -> TlsConnector {
+    create_tls_connector_with_policy(&CertificatePolicy::AcceptAny)
+}
+
+/// Creates a connector that checks the device certificate according to `policy`.
+pub fn create_tls_connector_with_policy(policy: &CertificatePolicy) -> TlsConnector {
+    let verifier: Arc<dyn ServerCertVerifier> = match policy {
+        CertificatePolicy::AcceptAny => Arc::new(NoCertificateVerification),
+        CertificatePolicy::TrustOnFirstUse { device_id, store } => {
+            Arc::new(PinnedCertificateVerification {
+                device_id: device_id.clone(),
+                store: store.clone(),
+            })
+        }
+    };
     let config = ClientConfig::builder()
         .with_safe_defaults()
-        .with_custom_certificate_verifier(Arc::new(NoCertificateVerification))
+        .with_custom_certificate_verifier(verifier)
         .with_no_client_auth();
 
     // Cast protocol requires ALPN? Usually not strictly enforced by generic clients but good to check.
@@ -31,3 +246,46 @@ pub fn create_tls_connector() -> TlsConnector {
 
     TlsConnector::from(Arc::new(config))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_store_pins_and_persists() {
+        let path = std::env::temp_dir().join(format!(
+            "castru-known-devices-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        let store = FingerprintStore::open(&path).unwrap();
+        assert_eq!(store.get("device-1"), None);
+        assert!(store.check("device-1", "aa").is_ok());
+        assert!(store.check("device-1", "aa").is_ok());
+        // Nothing is written until the pins are flushed.
+        assert!(!path.exists());
+        store.flush().unwrap();
+
+        let reopened = FingerprintStore::open(&path).unwrap();
+        assert_eq!(reopened.get("device-1").as_deref(), Some("aa"));
+        assert_eq!(
+            reopened.check("device-1", "bb"),
+            Err(FingerprintMismatch {
+                device_id: "device-1".to_string(),
+                expected: "aa".to_string(),
+                actual: "bb".to_string(),
+            })
+        );
+
+        reopened.forget("device-1").unwrap();
+        assert!(FingerprintStore::open(&path).unwrap().get("device-1").is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_fingerprint_is_sha256_hex() {
+        assert_eq!(
+            fingerprint(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
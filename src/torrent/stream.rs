@@ -9,11 +9,15 @@ use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
 
 pub struct GrowingFile {
     file: Option<File>,
-    path: PathBuf,
     position: u64,
     total_size: u64,
+    // Where the file's pieces are, for reads that wait on the torrent; reads only poll
+    // the file for now.
+    #[allow(dead_code)]
     handle: Arc<ManagedTorrent>,
+    #[allow(dead_code)]
     file_offset: u64,
+    #[allow(dead_code)]
     piece_length: u64,
 }
 
@@ -28,7 +32,6 @@ impl GrowingFile {
         let file = File::open(&path).await?;
         Ok(Self {
             file: Some(file),
-            path,
             position: 0,
             total_size,
             handle,
@@ -1,5 +1,8 @@
+pub mod cache;
+
 use crate::error::CastError;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::{Child, ChildStdout, Command};
@@ -11,6 +14,8 @@ pub struct MediaProbeResult {
     pub pix_fmt: Option<String>,
     pub audio_codec: Option<String>,
     pub duration: Option<f64>,
+    /// Container tags (title, artist, show, ...) with lowercased keys.
+    pub tags: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,11 +45,30 @@ struct FFProbeStream {
 #[derive(Deserialize)]
 struct FFProbeFormat {
     duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
 }
 
 pub struct TranscodingPipeline {
     pub process: Child,
     pub stdout: ChildStdout,
+    /// Where the output starts in the input, in seconds.
+    pub start_time: f64,
+}
+
+/// Name of the playlist an HLS pipeline writes into its directory.
+pub const HLS_PLAYLIST: &str = "index.m3u8";
+
+/// Target length of each HLS segment, in seconds.
+pub const HLS_SEGMENT_SECONDS: u32 = 4;
+
+/// An ffmpeg process segmenting its output as HLS into `dir`.
+pub struct HlsPipeline {
+    pub process: Child,
+    /// Holds [`HLS_PLAYLIST`] and the MPEG-TS segments it lists.
+    pub dir: PathBuf,
+    /// Where the output starts in the input, in seconds.
+    pub start_time: f64,
 }
 
 pub async fn probe_media(path: &Path) -> Result<MediaProbeResult, CastError> {
@@ -146,7 +170,84 @@ pub fn spawn_ffmpeg(config: &TranscodeConfig) -> Result<TranscodingPipeline, Cas
         .take()
         .ok_or_else(|| CastError::Transcoding("Failed to capture ffmpeg stdout".to_string()))?;
 
-    Ok(TranscodingPipeline { process, stdout })
+    Ok(TranscodingPipeline {
+        process,
+        stdout,
+        start_time: config.start_time,
+    })
+}
+
+/// Spawns ffmpeg writing HLS into a new temporary directory.
+///
+/// The playlist is an EVENT playlist: it grows as segments are written and gets
+/// `#EXT-X-ENDLIST` once the whole input is done, so a receiver can seek anywhere within
+/// what has been produced so far.
+pub fn spawn_hls(config: &TranscodeConfig) -> Result<HlsPipeline, CastError> {
+    let dir = std::env::temp_dir()
+        .join("castru_transcode")
+        .join(format!("hls_{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| CastError::Transcoding(format!("Failed to create HLS directory: {}", e)))?;
+
+    let mut cmd = Command::new("ffmpeg");
+    if config.start_time > 0.0 {
+        cmd.arg("-ss").arg(config.start_time.to_string());
+    }
+    cmd.arg("-i")
+        .arg(&config.input_path)
+        .arg("-c:v")
+        .arg(&config.target_video_codec)
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-preset")
+        .arg("ultrafast")
+        // Keyframes on segment boundaries so every segment can start playback.
+        .arg("-force_key_frames")
+        .arg(format!("expr:gte(t,n_forced*{})", HLS_SEGMENT_SECONDS))
+        .arg("-c:a")
+        .arg(&config.target_audio_codec)
+        .arg("-ac")
+        .arg("2")
+        .arg("-f")
+        .arg("hls")
+        .arg("-hls_time")
+        .arg(HLS_SEGMENT_SECONDS.to_string())
+        .arg("-hls_playlist_type")
+        .arg("event")
+        // Segments only appear under their final name once complete.
+        .arg("-hls_flags")
+        .arg("temp_file")
+        .arg("-hls_segment_filename")
+        .arg(dir.join("segment%05d.ts"))
+        .arg(dir.join(HLS_PLAYLIST))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    match cmd.spawn() {
+        Ok(process) => Ok(HlsPipeline {
+            process,
+            dir,
+            start_time: config.start_time,
+        }),
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&dir);
+            Err(CastError::Transcoding(format!("Failed to spawn ffmpeg: {}", e)))
+        }
+    }
+}
+
+/// The media time covered by the segments listed in an HLS playlist, in seconds.
+pub fn hls_playlist_duration(playlist: &str) -> f64 {
+    playlist
+        .lines()
+        .filter_map(|line| line.strip_prefix("#EXTINF:"))
+        .filter_map(|info| info.split(',').next()?.trim().parse::<f64>().ok())
+        .sum()
+}
+
+/// Whether an HLS playlist lists at least one segment.
+pub fn hls_playlist_has_segments(playlist: &str) -> bool {
+    playlist.lines().any(|line| line.starts_with("#EXTINF:"))
 }
 
 fn parse_ffprobe_output(output: &[u8]) -> Result<MediaProbeResult, CastError> {
@@ -168,10 +269,18 @@ fn parse_ffprobe_output(output: &[u8]) -> Result<MediaProbeResult, CastError> {
         }
     }
 
-    let duration = parsed
-        .format
-        .and_then(|f| f.duration)
-        .and_then(|d| d.parse::<f64>().ok());
+    let (duration, tags) = match parsed.format {
+        Some(format) => (
+            format.duration.and_then(|d| d.parse::<f64>().ok()),
+            // Matroska writes TITLE, MP4 writes title.
+            format
+                .tags
+                .into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v))
+                .collect(),
+        ),
+        None => (None, HashMap::new()),
+    };
 
     Ok(MediaProbeResult {
         video_codec,
@@ -179,6 +288,7 @@ fn parse_ffprobe_output(output: &[u8]) -> Result<MediaProbeResult, CastError> {
         pix_fmt,
         audio_codec,
         duration,
+        tags,
     })
 }
 
@@ -202,7 +312,10 @@ mod tests {
                 }
             ],
             "format": {
-                "duration": "123.456"
+                "duration": "123.456",
+                "tags": {
+                    "TITLE": "Pilot"
+                }
             }
         }"#;
 
@@ -211,5 +324,18 @@ mod tests {
         assert_eq!(result.video_codec, Some("h264".to_string()));
         assert_eq!(result.audio_codec, Some("aac".to_string()));
         assert_eq!(result.duration, Some(123.456));
+        assert_eq!(result.tags.get("title").map(String::as_str), Some("Pilot"));
+    }
+
+    #[test]
+    fn test_hls_playlist_duration() {
+        let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:4\n\
+                        #EXT-X-PLAYLIST-TYPE:EVENT\n\
+                        #EXTINF:4.004000,\nsegment00000.ts\n\
+                        #EXTINF:3.503000,\nsegment00001.ts\n";
+        assert!((hls_playlist_duration(playlist) - 7.507).abs() < 1e-9);
+        assert!(hls_playlist_has_segments(playlist));
+        assert_eq!(hls_playlist_duration("#EXTM3U\n"), 0.0);
+        assert!(!hls_playlist_has_segments("#EXTM3U\n#EXT-X-TARGETDURATION:4\n"));
     }
 }
@@ -0,0 +1,323 @@
+//! On-disk cache of finished transcodes, so replaying or seeking back in a file that needed
+//! transcoding serves a plain, seekable MP4 instead of running ffmpeg again.
+
+use super::TranscodeConfig;
+use crate::error::CastError;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+/// Bumped whenever the transcode settings change in a way the key does not capture.
+const FORMAT_VERSION: &str = "1";
+
+/// Unfinished outputs older than this are left over from an earlier run.
+const STALE_PARTIAL_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub dir: PathBuf,
+    /// Finished transcodes are evicted, least recently used first, above this size.
+    /// 0 disables caching.
+    pub max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: std::env::temp_dir().join("castru_transcode").join("cache"),
+            max_bytes: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Finished transcodes, keyed by input file, its modification time and the transcode
+/// settings.
+///
+/// Transcodes in progress are written to [`TranscodeCache::partial_path`]s in the same
+/// directory; once one that covers the whole input finishes, [`TranscodeCache::store`]
+/// remuxes it into an MP4 that [`TranscodeCache::lookup`] then returns.
+pub struct TranscodeCache {
+    config: CacheConfig,
+}
+
+impl TranscodeCache {
+    /// Opens the cache directory, clearing out what earlier runs left unfinished.
+    pub fn new(config: CacheConfig) -> Self {
+        let cache = Self { config };
+        if let Err(e) = std::fs::create_dir_all(&cache.config.dir) {
+            log::warn!("Cannot create transcode cache {:?}: {}", cache.config.dir, e);
+        }
+        for (path, _, modified) in cache.entries() {
+            let stale = modified.elapsed().is_ok_and(|age| age > STALE_PARTIAL_AGE);
+            if !is_complete(&path) && stale {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        cache.evict();
+        cache
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_bytes > 0
+    }
+
+    /// Identifies the output of `config`, or `None` if the input cannot be read.
+    pub fn key(config: &TranscodeConfig) -> Option<String> {
+        let path = std::fs::canonicalize(&config.input_path).ok()?;
+        let metadata = std::fs::metadata(&path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+        let mut hash = Fnv1a::new();
+        hash.write(path.to_string_lossy().as_bytes());
+        hash.write(&modified.as_nanos().to_le_bytes());
+        hash.write(&metadata.len().to_le_bytes());
+        hash.write(&config.start_time.to_bits().to_le_bytes());
+        hash.write(config.target_video_codec.as_bytes());
+        hash.write(config.target_audio_codec.as_bytes());
+        hash.write(FORMAT_VERSION.as_bytes());
+        Some(format!("{:016x}", hash.finish()))
+    }
+
+    /// The finished transcode of `config`'s whole input, whatever its start time.
+    pub fn lookup(&self, config: &TranscodeConfig) -> Option<PathBuf> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let whole = TranscodeConfig {
+            start_time: 0.0,
+            ..config.clone()
+        };
+        let path = self.complete_path(&Self::key(&whole)?);
+        // Mark it as recently used for eviction.
+        let file = std::fs::File::options().append(true).open(&path).ok()?;
+        let _ = file.set_modified(SystemTime::now());
+        Some(path)
+    }
+
+    /// A new, unique path in the cache directory for a transcode in progress.
+    pub fn partial_path(&self) -> PathBuf {
+        self.config
+            .dir
+            .join(format!("{}.part", uuid::Uuid::new_v4().simple()))
+    }
+
+    /// Remuxes a finished transcode (an MP4 file or an HLS playlist) into a seekable MP4
+    /// under `key`, then evicts old entries if the cache has grown too large.
+    pub async fn store(&self, key: &str, finished: &Path) -> Result<PathBuf, CastError> {
+        if !self.is_enabled() {
+            return Err(CastError::Transcoding("Transcode cache is disabled".into()));
+        }
+        let target = self.complete_path(key);
+        let remux = self
+            .config
+            .dir
+            .join(format!("{}.remux.part", uuid::Uuid::new_v4().simple()));
+        let status = Command::new("ffmpeg")
+            .arg("-v")
+            .arg("error")
+            .arg("-y")
+            .arg("-i")
+            .arg(finished)
+            .arg("-c")
+            .arg("copy")
+            // HLS segments carry ADTS audio, which MP4 cannot hold as is.
+            .arg("-bsf:a")
+            .arg("aac_adtstoasc")
+            // Index up front so receivers can seek without reading the whole file.
+            .arg("-movflags")
+            .arg("+faststart")
+            .arg("-f")
+            .arg("mp4")
+            .arg(&remux)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .map_err(|e| CastError::Transcoding(format!("Failed to spawn ffmpeg: {}", e)))?;
+        if !status.success() {
+            let _ = tokio::fs::remove_file(&remux).await;
+            return Err(CastError::Transcoding(format!("Remuxing failed ({})", status)));
+        }
+        tokio::fs::rename(&remux, &target).await?;
+        log::info!("Cached transcode as {:?}", target);
+        self.evict_except(Some(&target));
+        Ok(target)
+    }
+
+    /// Deletes the least recently used finished transcodes until they fit in `max_bytes`.
+    /// Transcodes in progress are neither counted nor deleted.
+    pub fn evict(&self) {
+        self.evict_except(None);
+    }
+
+    /// Like [`TranscodeCache::evict`], but never deletes `keep`, even if it alone is too
+    /// large.
+    fn evict_except(&self, keep: Option<&Path>) {
+        let mut entries = self.entries();
+        entries.retain(|(path, _, _)| is_complete(path));
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.retain(|(path, _, _)| Some(path.as_path()) != keep);
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.config.max_bytes {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                log::debug!("Evicted cached transcode {:?}", path);
+                total -= len;
+            }
+        }
+    }
+
+    fn complete_path(&self, key: &str) -> PathBuf {
+        self.config.dir.join(format!("{}.mp4", key))
+    }
+
+    /// Every file in the cache with its size and modification time.
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(dir) = std::fs::read_dir(&self.config.dir) else {
+            return Vec::new();
+        };
+        dir.flatten()
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+            })
+            .collect()
+    }
+}
+
+fn is_complete(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "mp4")
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Separate fields so ("ab", "c") and ("a", "bc") differ.
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("castru_cache_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn transcode_config(input: &Path, start_time: f64) -> TranscodeConfig {
+        TranscodeConfig {
+            input_path: input.to_path_buf(),
+            start_time,
+            target_video_codec: "libx264".to_string(),
+            target_audio_codec: "aac".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_follows_input_and_settings() {
+        let dir = scratch_dir();
+        let input = dir.join("movie.mkv");
+        std::fs::write(&input, b"original").unwrap();
+
+        let key = TranscodeCache::key(&transcode_config(&input, 0.0)).unwrap();
+        assert_eq!(key.len(), 16);
+        assert_eq!(TranscodeCache::key(&transcode_config(&input, 0.0)).unwrap(), key);
+        assert_ne!(TranscodeCache::key(&transcode_config(&input, 30.0)).unwrap(), key);
+        let mut hevc = transcode_config(&input, 0.0);
+        hevc.target_video_codec = "libx265".to_string();
+        assert_ne!(TranscodeCache::key(&hevc).unwrap(), key);
+
+        // Editing the file invalidates its entry.
+        std::fs::write(&input, b"edited, and longer").unwrap();
+        assert_ne!(TranscodeCache::key(&transcode_config(&input, 0.0)).unwrap(), key);
+        assert_eq!(TranscodeCache::key(&transcode_config(&dir.join("missing.mkv"), 0.0)), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lookup_and_eviction() {
+        let dir = scratch_dir();
+        let input = dir.join("movie.mkv");
+        std::fs::write(&input, b"source").unwrap();
+        let cache = TranscodeCache::new(CacheConfig {
+            dir: dir.join("cache"),
+            max_bytes: 150,
+        });
+
+        let whole = transcode_config(&input, 0.0);
+        assert_eq!(cache.lookup(&whole), None);
+        let key = TranscodeCache::key(&whole).unwrap();
+        std::fs::write(cache.complete_path(&key), [0u8; 100]).unwrap();
+        // A seek into the file is served from the whole transcode.
+        assert_eq!(cache.lookup(&transcode_config(&input, 42.0)), Some(cache.complete_path(&key)));
+
+        let old = cache.complete_path("0000000000000001");
+        std::fs::write(&old, [0u8; 100]).unwrap();
+        let last_week = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+        std::fs::File::options()
+            .append(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(last_week)
+            .unwrap();
+        let partial = cache.partial_path();
+        std::fs::write(&partial, [0u8; 100]).unwrap();
+
+        cache.evict();
+        assert!(!old.exists(), "least recently used entry goes first");
+        assert!(cache.complete_path(&key).exists());
+        assert!(partial.exists(), "transcodes in progress are kept");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_eviction_keeps_the_stored_entry() {
+        let dir = scratch_dir();
+        let cache = TranscodeCache::new(CacheConfig {
+            dir: dir.clone(),
+            max_bytes: 150,
+        });
+        // A large transcode still in progress does not push out finished ones.
+        let partial = cache.partial_path();
+        std::fs::write(&partial, [0u8; 1000]).unwrap();
+        let older = cache.complete_path("0000000000000001");
+        std::fs::write(&older, [0u8; 100]).unwrap();
+        cache.evict();
+        assert!(older.exists());
+        assert!(partial.exists());
+
+        // The entry just stored stays, even when it alone is over the limit.
+        let stored = cache.complete_path("0000000000000002");
+        std::fs::write(&stored, [0u8; 200]).unwrap();
+        cache.evict_except(Some(&stored));
+        assert!(stored.exists());
+        assert!(!older.exists());
+        assert!(partial.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,293 @@
+//! Guesses Cast metadata for local media from container tags and file names.
+
+use crate::protocol::media::{
+    GenericMediaMetadata, MediaMetadata, MovieMediaMetadata, MusicTrackMediaMetadata,
+    PhotoMediaMetadata, TvShowMediaMetadata,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "m4a", "aac", "ogg", "oga", "opus", "wav"];
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+const ARTWORK_NAMES: &[&str] = &["poster", "cover", "folder"];
+
+/// Words in release file names after which nothing belongs to the title anymore.
+const RELEASE_WORDS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "2160p", "4k", "uhd", "hdtv", "web", "webrip", "web-dl",
+    "webdl", "bluray", "brrip", "bdrip", "dvdrip", "x264", "x265", "h264", "h265", "hevc",
+    "xvid", "hdr", "remux", "proper", "repack",
+];
+
+/// Builds metadata for the file at `path` from its ffprobe `tags` (lowercased keys),
+/// falling back to what the file name says.
+pub fn guess_metadata(path: &Path, tags: &HashMap<String, String>) -> MediaMetadata {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let words: Vec<&str> = stem
+        .split(['.', '_', ' '])
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return MediaMetadata::Photo(PhotoMediaMetadata {
+            title: tag(tags, &["title"]).or_else(|| title_from(&words)),
+            artist: tag(tags, &["artist"]),
+            creation_date_time: tag(tags, &["creation_time", "date"]),
+            ..Default::default()
+        });
+    }
+
+    if AUDIO_EXTENSIONS.contains(&extension.as_str()) || tags.contains_key("album") {
+        return MediaMetadata::MusicTrack(music_metadata(stem, &words, tags));
+    }
+
+    if let Some(episode) = find_episode(&words).or_else(|| {
+        tags.contains_key("show").then_some(EpisodeMarker {
+            index: words.len(),
+            season: None,
+            episode: None,
+        })
+    }) {
+        return MediaMetadata::TvShow(TvShowMediaMetadata {
+            series_title: tag(tags, &["show"]).or_else(|| title_from(&words[..episode.index])),
+            title: tag(tags, &["title"])
+                .or_else(|| title_from(words.get(episode.index + 1..).unwrap_or_default())),
+            season: tag(tags, &["season_number"])
+                .and_then(|s| number(&s))
+                .or(episode.season),
+            episode: tag(tags, &["episode_sort", "episode_id"])
+                .and_then(|e| number(&e))
+                .or(episode.episode),
+            original_airdate: tag(tags, &["date"]),
+            images: None,
+        });
+    }
+
+    if let Some(index) = words.iter().skip(1).position(|w| year(w).is_some()) {
+        let year_word = words[index + 1];
+        return MediaMetadata::Movie(MovieMediaMetadata {
+            title: tag(tags, &["title"]).or_else(|| title_from(&words[..index + 1])),
+            release_date: tag(tags, &["date", "date_released"]).or_else(|| year(year_word)),
+            studio: tag(tags, &["studio", "publisher"]),
+            ..Default::default()
+        });
+    }
+
+    MediaMetadata::Generic(GenericMediaMetadata {
+        title: tag(tags, &["title"]).or_else(|| title_from(&words)),
+        artist: tag(tags, &["artist"]),
+        release_date: tag(tags, &["date"]),
+        ..Default::default()
+    })
+}
+
+/// Finds cover art next to `path`: `<name>.jpg`, `<name>-poster.jpg`, `poster.jpg`,
+/// `cover.jpg` or `folder.jpg` (or the PNG equivalents).
+pub fn find_artwork(path: &Path) -> Option<PathBuf> {
+    let dir = path.parent()?;
+    let stem = path.file_stem()?.to_str()?;
+    let names = [stem.to_string(), format!("{}-poster", stem)]
+        .into_iter()
+        .chain(ARTWORK_NAMES.iter().map(|n| n.to_string()));
+    names
+        .flat_map(|name| ["jpg", "jpeg", "png"].map(|ext| dir.join(format!("{}.{}", name, ext))))
+        .find(|candidate| candidate.is_file())
+}
+
+fn music_metadata(
+    stem: &str,
+    words: &[&str],
+    tags: &HashMap<String, String>,
+) -> MusicTrackMediaMetadata {
+    // "01 - Artist - Title" or "Artist - Title"
+    let mut parts: Vec<&str> = stem.split(" - ").map(str::trim).collect();
+    let track_number = parts
+        .first()
+        .filter(|p| p.len() <= 3)
+        .and_then(|p| p.parse::<i32>().ok());
+    if track_number.is_some() && parts.len() > 1 {
+        parts.remove(0);
+    }
+    let (name_artist, name_title) = match parts.as_slice() {
+        [artist, title] => (Some(artist.to_string()), Some(title.to_string())),
+        _ => (None, title_from(words)),
+    };
+
+    MusicTrackMediaMetadata {
+        title: tag(tags, &["title"]).or(name_title),
+        artist: tag(tags, &["artist"]).or(name_artist),
+        album_name: tag(tags, &["album"]),
+        album_artist: tag(tags, &["album_artist"]),
+        composer: tag(tags, &["composer"]),
+        track_number: tag(tags, &["track"])
+            .and_then(|t| number(&t))
+            .or(track_number),
+        disc_number: tag(tags, &["disc"]).and_then(|d| number(&d)),
+        release_date: tag(tags, &["date"]),
+        images: None,
+    }
+}
+
+struct EpisodeMarker {
+    index: usize,
+    season: Option<i32>,
+    episode: Option<i32>,
+}
+
+/// Finds an `S01E02` or `1x02` word.
+fn find_episode(words: &[&str]) -> Option<EpisodeMarker> {
+    words.iter().enumerate().find_map(|(index, word)| {
+        let lower = word.to_lowercase();
+        let (season, episode) = if let Some(rest) = lower.strip_prefix('s') {
+            rest.split_once('e')?
+        } else {
+            lower.split_once('x').filter(|(s, e)| s.len() <= 2 && e.len() >= 2)?
+        };
+        // S01E02E03 is a double episode; keep the first.
+        let episode = episode.split('e').next()?;
+        let all_digits = |s: &str| !s.is_empty() && s.len() <= 3 && s.bytes().all(|b| b.is_ascii_digit());
+        if !all_digits(season) || !all_digits(episode) {
+            return None;
+        }
+        Some(EpisodeMarker {
+            index,
+            season: season.parse().ok(),
+            episode: episode.parse().ok(),
+        })
+    })
+}
+
+/// Joins file-name words into a title, stopping at release details.
+fn title_from(words: &[&str]) -> Option<String> {
+    let title: Vec<&str> = words
+        .iter()
+        .copied()
+        .take_while(|w| !RELEASE_WORDS.contains(&w.to_lowercase().as_str()) && year(w).is_none())
+        .collect();
+    let title = title.join(" ");
+    let title = title.trim_matches(|c: char| c == '-' || c.is_whitespace());
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// A release year such as `2010` or `(2010)`.
+fn year(word: &str) -> Option<String> {
+    let digits = word.trim_matches(['(', ')', '[', ']']);
+    let is_year = digits.len() == 4
+        && (digits.starts_with("19") || digits.starts_with("20"))
+        && digits.bytes().all(|b| b.is_ascii_digit());
+    is_year.then(|| digits.to_string())
+}
+
+fn tag(tags: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|k| tags.get(*k))
+        .map(|v| v.trim())
+        .find(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// Parses `3` or `3/12`.
+fn number(value: &str) -> Option<i32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guess(name: &str) -> MediaMetadata {
+        guess_metadata(Path::new(name), &HashMap::new())
+    }
+
+    #[test]
+    fn test_guess_tv_episode_from_file_name() {
+        let MediaMetadata::TvShow(tv) = guess("/media/The.Office.S02E05.Halloween.720p.HDTV.x264.mkv")
+        else {
+            panic!("expected a TV episode");
+        };
+        assert_eq!(tv.series_title.as_deref(), Some("The Office"));
+        assert_eq!(tv.title.as_deref(), Some("Halloween"));
+        assert_eq!((tv.season, tv.episode), (Some(2), Some(5)));
+
+        let MediaMetadata::TvShow(tv) = guess("Doctor Who - 3x10 - Blink.mp4") else {
+            panic!("expected a TV episode");
+        };
+        assert_eq!(tv.series_title.as_deref(), Some("Doctor Who"));
+        assert_eq!(tv.title.as_deref(), Some("Blink"));
+        assert_eq!((tv.season, tv.episode), (Some(3), Some(10)));
+    }
+
+    #[test]
+    fn test_guess_movie_and_generic() {
+        let MediaMetadata::Movie(movie) = guess("Inception (2010) 1080p BluRay.mp4") else {
+            panic!("expected a movie");
+        };
+        assert_eq!(movie.title.as_deref(), Some("Inception"));
+        assert_eq!(movie.release_date.as_deref(), Some("2010"));
+
+        // 1920x1080 is not an episode marker.
+        let generic = guess("screen_capture_1920x1080.mp4");
+        assert!(matches!(generic, MediaMetadata::Generic(_)));
+        assert_eq!(generic.title(), Some("screen capture 1920x1080"));
+    }
+
+    #[test]
+    fn test_tags_override_file_name() {
+        let tags = HashMap::from([
+            ("title".to_string(), "So What".to_string()),
+            ("artist".to_string(), "Miles Davis".to_string()),
+            ("album".to_string(), "Kind of Blue".to_string()),
+            ("track".to_string(), "1/5".to_string()),
+        ]);
+        let MediaMetadata::MusicTrack(track) = guess_metadata(Path::new("01 track.flac"), &tags)
+        else {
+            panic!("expected a music track");
+        };
+        assert_eq!(track.title.as_deref(), Some("So What"));
+        assert_eq!(track.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(track.album_name.as_deref(), Some("Kind of Blue"));
+        assert_eq!(track.track_number, Some(1));
+
+        let MediaMetadata::MusicTrack(track) = guess("03 - Daft Punk - Get Lucky.mp3") else {
+            panic!("expected a music track");
+        };
+        assert_eq!(track.artist.as_deref(), Some("Daft Punk"));
+        assert_eq!(track.title.as_deref(), Some("Get Lucky"));
+        assert_eq!(track.track_number, Some(3));
+
+        let tags = HashMap::from([
+            ("show".to_string(), "Planet Earth".to_string()),
+            ("season_number".to_string(), "1".to_string()),
+            ("episode_sort".to_string(), "4".to_string()),
+            ("title".to_string(), "Caves".to_string()),
+        ]);
+        let MediaMetadata::TvShow(tv) = guess_metadata(Path::new("pe104.m4v"), &tags) else {
+            panic!("expected a TV episode");
+        };
+        assert_eq!(tv.series_title.as_deref(), Some("Planet Earth"));
+        assert_eq!(tv.title.as_deref(), Some("Caves"));
+        assert_eq!((tv.season, tv.episode), (Some(1), Some(4)));
+    }
+
+    #[test]
+    fn test_find_artwork() {
+        let dir = std::env::temp_dir().join(format!("castru-artwork-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let video = dir.join("movie.mp4");
+        assert_eq!(find_artwork(&video), None);
+
+        std::fs::write(dir.join("folder.jpg"), b"").unwrap();
+        assert_eq!(find_artwork(&video), Some(dir.join("folder.jpg")));
+        std::fs::write(dir.join("movie.png"), b"").unwrap();
+        assert_eq!(find_artwork(&video), Some(dir.join("movie.png")));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,12 +1,29 @@
-use std::sync::Arc;
+use bytes::BytesMut;
+use castru::codec::CastCodec;
+use tokio_util::codec::{Decoder, Encoder};
+use castru::proto::CastMessage;
+use rcgen::generate_simple_self_signed;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tokio::sync::broadcast;
 use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
 use tokio_rustls::TlsAcceptor;
-use rcgen::generate_simple_self_signed;
-use tokio::io::AsyncReadExt;
 
+#[derive(Clone, Debug)]
+enum MockCommand {
+    Send(CastMessage),
+    SendRaw(Vec<u8>),
+    Disconnect,
+}
+
+/// A TLS server speaking CastV2 framing that records everything it receives.
 pub struct MockDevice {
     port: u16,
+    cert_der: Vec<u8>,
+    received: Arc<Mutex<Vec<CastMessage>>>,
+    commands: broadcast::Sender<MockCommand>,
     _join_handle: tokio::task::JoinHandle<()>,
 }
 
@@ -15,8 +32,8 @@ impl MockDevice {
         let certified_key = generate_simple_self_signed(vec!["localhost".into()]).unwrap();
         let cert_der = certified_key.cert.der().to_vec();
         let key_der = certified_key.signing_key.serialize_der();
-        
-        let certs = vec![Certificate(cert_der)];
+
+        let certs = vec![Certificate(cert_der.clone())];
         let key = PrivateKey(key_der);
 
         let config = ServerConfig::builder()
@@ -29,16 +46,55 @@ impl MockDevice {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let port = listener.local_addr().unwrap().port();
 
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (commands, _) = broadcast::channel(64);
+
+        let received_clone = received.clone();
+        let commands_clone = commands.clone();
+
         let handle = tokio::spawn(async move {
             loop {
                 if let Ok((stream, _)) = listener.accept().await {
                     let acceptor = acceptor.clone();
+                    let received = received_clone.clone();
+                    let mut command_rx = commands_clone.subscribe();
                     tokio::spawn(async move {
-                        if let Ok(mut stream) = acceptor.accept(stream).await {
-                             let mut buf = [0u8; 1024];
-                             while let Ok(n) = stream.read(&mut buf).await {
-                                 if n == 0 { break; }
-                             }
+                        let Ok(stream) = acceptor.accept(stream).await else {
+                            return;
+                        };
+                        let (mut reader, mut writer) = tokio::io::split(stream);
+                        let mut buf = BytesMut::with_capacity(1024);
+                        let mut codec = CastCodec::new();
+                        loop {
+                            tokio::select! {
+                                res = reader.read_buf(&mut buf) => {
+                                    match res {
+                                        Ok(0) | Err(_) => break,
+                                        Ok(_) => {
+                                            while let Ok(Some(msg)) = codec.decode(&mut buf) {
+                                                received.lock().unwrap().push(msg);
+                                            }
+                                        }
+                                    }
+                                }
+                                cmd = command_rx.recv() => {
+                                    match cmd {
+                                        Ok(MockCommand::Send(msg)) => {
+                                            let mut out = BytesMut::new();
+                                            codec.encode(msg, &mut out).unwrap();
+                                            if writer.write_all(&out).await.is_err() {
+                                                break;
+                                            }
+                                        }
+                                        Ok(MockCommand::SendRaw(bytes)) => {
+                                            if writer.write_all(&bytes).await.is_err() {
+                                                break;
+                                            }
+                                        }
+                                        Ok(MockCommand::Disconnect) | Err(_) => break,
+                                    }
+                                }
+                            }
                         }
                     });
                 }
@@ -47,6 +103,9 @@ impl MockDevice {
 
         Self {
             port,
+            cert_der,
+            received,
+            commands,
             _join_handle: handle,
         }
     }
@@ -54,4 +113,81 @@ impl MockDevice {
     pub fn port(&self) -> u16 {
         self.port
     }
+
+    /// DER certificate presented in the TLS handshake.
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert_der
+    }
+
+    /// Sends a message to every connected client.
+    pub fn send(&self, msg: CastMessage) {
+        let _ = self.commands.send(MockCommand::Send(msg));
+    }
+
+    /// Sends a string payload to every connected client.
+    pub fn send_json(&self, source_id: &str, destination_id: &str, namespace: &str, payload: &str) {
+        self.send(CastMessage {
+            protocol_version: 0,
+            source_id: source_id.to_string(),
+            destination_id: destination_id.to_string(),
+            namespace: namespace.to_string(),
+            payload_type: 0,
+            payload_utf8: Some(payload.to_string()),
+            payload_binary: None,
+        });
+    }
+
+    /// Writes raw bytes to every connected client, bypassing the codec.
+    pub fn send_raw(&self, bytes: &[u8]) {
+        let _ = self.commands.send(MockCommand::SendRaw(bytes.to_vec()));
+    }
+
+    /// Drops every open TLS session.
+    pub fn disconnect(&self) {
+        let _ = self.commands.send(MockCommand::Disconnect);
+    }
+
+    /// Messages received so far matching `pred`.
+    pub fn received<F: Fn(&CastMessage) -> bool>(&self, pred: F) -> Vec<CastMessage> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| pred(m))
+            .cloned()
+            .collect()
+    }
+
+    /// Waits until at least `count` received messages match `pred`.
+    pub async fn wait_for<F: Fn(&CastMessage) -> bool>(
+        &self,
+        count: usize,
+        pred: F,
+    ) -> Vec<CastMessage> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let matching = self.received(&pred);
+            if matching.len() >= count {
+                return matching;
+            }
+            if tokio::time::Instant::now() > deadline {
+                panic!(
+                    "Timed out waiting for {} messages, got {}",
+                    count,
+                    matching.len()
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// Returns true if `msg` is a CONNECT sent to `destination_id`.
+pub fn is_connect_to(msg: &CastMessage, destination_id: &str) -> bool {
+    msg.destination_id == destination_id
+        && msg.namespace == "urn:x-cast:com.google.cast.tp.connection"
+        && msg
+            .payload_utf8
+            .as_deref()
+            .is_some_and(|p| p.contains("\"CONNECT\""))
 }
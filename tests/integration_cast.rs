@@ -1,7 +1,10 @@
+use castru::client::{CastClientConfig, ConnectionEvent, ConnectionState, ReconnectPolicy};
 use castru::CastClient;
+use std::time::Duration;
+use tokio::time::timeout;
 
 mod common;
-use common::mock_device::MockDevice;
+use common::mock_device::{is_connect_to, MockDevice};
 
 #[tokio::test]
 async fn test_integration_connect() {
@@ -20,3 +23,599 @@ async fn test_integration_connect() {
     // Since MockDevice just reads and discards, this write should succeed.
     assert!(res.is_ok());
 }
+
+fn fast_reconnect(policy: ReconnectPolicy) -> CastClientConfig {
+    CastClientConfig {
+        reconnect: ReconnectPolicy {
+            initial_delay: Duration::from_millis(50),
+            ..policy
+        },
+        ..Default::default()
+    }
+}
+
+async fn wait_for_state(client: &CastClient, expected: ConnectionState) {
+    let mut state = client.connection_state();
+    timeout(Duration::from_secs(5), state.wait_for(|s| *s == expected))
+        .await
+        .expect("timed out waiting for connection state")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_reconnect_reopens_virtual_connections() {
+    let device = MockDevice::start().await;
+    let config = fast_reconnect(ReconnectPolicy::default());
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), config)
+        .await
+        .unwrap();
+    let mut events = client.connection_events();
+
+    wait_for_state(&client, ConnectionState::Connected).await;
+    client.connect_receiver().await.unwrap();
+    client.connect_transport("web-1").await.unwrap();
+    device.wait_for(1, |m| is_connect_to(m, "web-1")).await;
+
+    device.disconnect();
+
+    let reconnected = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ConnectionEvent::Reconnected) = events.recv().await {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(reconnected.is_ok(), "client did not report reconnection");
+    assert_eq!(client.state(), ConnectionState::Connected);
+
+    device.wait_for(2, |m| is_connect_to(m, "receiver-0")).await;
+    device.wait_for(2, |m| is_connect_to(m, "web-1")).await;
+}
+
+#[tokio::test]
+async fn test_reconnect_policy_gives_up() {
+    let device = MockDevice::start().await;
+    let config = fast_reconnect(ReconnectPolicy::never());
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), config)
+        .await
+        .unwrap();
+
+    wait_for_state(&client, ConnectionState::Connected).await;
+    device.disconnect();
+    wait_for_state(&client, ConnectionState::Closed).await;
+}
+
+#[tokio::test]
+async fn test_client_answers_device_ping() {
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    wait_for_state(&client, ConnectionState::Connected).await;
+
+    device.send_json(
+        "receiver-0",
+        "sender-0",
+        "urn:x-cast:com.google.cast.tp.heartbeat",
+        r#"{"type":"PING"}"#,
+    );
+
+    let pongs = device
+        .wait_for(1, |m| {
+            m.destination_id == "receiver-0"
+                && m.payload_utf8.as_deref() == Some(r#"{"type":"PONG"}"#)
+        })
+        .await;
+    assert_eq!(pongs[0].namespace, "urn:x-cast:com.google.cast.tp.heartbeat");
+}
+
+#[tokio::test]
+async fn test_silent_device_triggers_reconnect() {
+    // The mock never answers PINGs, so the session goes silent.
+    let device = MockDevice::start().await;
+    let config = CastClientConfig {
+        heartbeat_interval: Duration::from_millis(50),
+        heartbeat_timeout: Duration::from_millis(200),
+        ..fast_reconnect(ReconnectPolicy::default())
+    };
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), config)
+        .await
+        .unwrap();
+    let mut events = client.connection_events();
+
+    let reason = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ConnectionEvent::Disconnected { reason }) = events.recv().await {
+                return reason;
+            }
+        }
+    })
+    .await
+    .expect("silent connection was never dropped");
+    assert!(reason.contains("no message from device"));
+
+    let reconnected = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ConnectionEvent::Reconnected) = events.recv().await {
+                return;
+            }
+        }
+    })
+    .await;
+    assert!(reconnected.is_ok(), "client did not reconnect");
+}
+
+#[tokio::test]
+async fn test_close_sends_close_and_stops_task() {
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    wait_for_state(&client, ConnectionState::Connected).await;
+    client.connect_receiver().await.unwrap();
+    client.connect_transport("web-1").await.unwrap();
+
+    timeout(Duration::from_secs(5), client.close())
+        .await
+        .expect("close did not finish")
+        .unwrap();
+
+    assert_eq!(client.state(), ConnectionState::Closed);
+    let closes = device
+        .wait_for(2, |m| {
+            m.namespace == "urn:x-cast:com.google.cast.tp.connection"
+                && m.payload_utf8.as_deref() == Some(r#"{"type":"CLOSE"}"#)
+        })
+        .await;
+    assert_eq!(closes[0].destination_id, "web-1");
+    assert_eq!(closes[1].destination_id, "receiver-0");
+    assert!(client.connect_receiver().await.is_err());
+}
+
+#[tokio::test]
+async fn test_dropping_client_stops_task() {
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    let mut state = client.connection_state();
+    wait_for_state(&client, ConnectionState::Connected).await;
+
+    drop(client);
+    timeout(Duration::from_secs(5), state.wait_for(|s| *s == ConnectionState::Closed))
+        .await
+        .expect("task kept running after the client was dropped")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_device_close_marks_transport_disconnected() {
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    let mut events = client.connection_events();
+    wait_for_state(&client, ConnectionState::Connected).await;
+    client.connect_transport("web-1").await.unwrap();
+    device.wait_for(1, |m| is_connect_to(m, "web-1")).await;
+    assert!(client.is_transport_connected("web-1"));
+
+    device.send_json(
+        "web-1",
+        "sender-0",
+        "urn:x-cast:com.google.cast.tp.connection",
+        r#"{"type":"CLOSE"}"#,
+    );
+
+    let closed = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ConnectionEvent::TransportClosed { transport_id }) = events.recv().await {
+                return transport_id;
+            }
+        }
+    })
+    .await
+    .expect("no TransportClosed event");
+    assert_eq!(closed, "web-1");
+    assert!(!client.is_transport_connected("web-1"));
+}
+
+#[tokio::test]
+async fn test_custom_namespace_binary_round_trip() {
+    use castru::client::Payload;
+    use castru::proto::CastMessage;
+
+    const NAMESPACE: &str = "urn:x-cast:com.example.game";
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    wait_for_state(&client, ConnectionState::Connected).await;
+
+    let channel = client.channel(NAMESPACE, "web-3");
+    let mut incoming = channel.subscribe();
+    channel.send_binary(b"\x00\x01hello").await.unwrap();
+
+    let sent = device.wait_for(1, |m| m.namespace == NAMESPACE).await;
+    assert_eq!(sent[0].destination_id, "web-3");
+    assert_eq!(sent[0].payload_binary.as_deref(), Some(&b"\x00\x01hello"[..]));
+
+    device.send(CastMessage {
+        protocol_version: 0,
+        source_id: "web-3".to_string(),
+        destination_id: "sender-0".to_string(),
+        namespace: NAMESPACE.to_string(),
+        payload_type: 1,
+        payload_utf8: None,
+        payload_binary: Some(vec![9, 8, 7]),
+    });
+
+    let msg = timeout(Duration::from_secs(5), incoming.recv())
+        .await
+        .expect("no message on the custom namespace")
+        .unwrap();
+    assert_eq!(msg.source_id, "web-3");
+    assert_eq!(msg.payload, Payload::Binary(vec![9, 8, 7]));
+}
+
+#[tokio::test]
+async fn test_configured_sender_id() {
+    let device = MockDevice::start().await;
+    let config = CastClientConfig {
+        sender_id: Some("sender-castru".to_string()),
+        ..Default::default()
+    };
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), config)
+        .await
+        .unwrap();
+    assert_eq!(client.sender_id(), "sender-castru");
+
+    client.connect_receiver().await.unwrap();
+    let connects = device.wait_for(1, |m| is_connect_to(m, "receiver-0")).await;
+    assert_eq!(connects[0].source_id, "sender-castru");
+}
+
+#[tokio::test]
+async fn test_authenticate_device() {
+    use castru::auth::CredentialTrust;
+    use castru::proto::{
+        AuthResponse, DeviceAuthMessage, HashAlgorithm, SignatureAlgorithm,
+    };
+    use prost::Message;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair, SigningKey};
+
+    const NAMESPACE: &str = "urn:x-cast:com.google.cast.tp.deviceauth";
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+
+    let root_key = KeyPair::generate().unwrap();
+    let mut root_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    root_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let root = root_params.self_signed(&root_key).unwrap();
+    let root_issuer = Issuer::new(root_params, root_key);
+    let device_key = KeyPair::generate().unwrap();
+    let device_cert = CertificateParams::new(Vec::<String>::new())
+        .unwrap()
+        .signed_by(&device_key, &root_issuer)
+        .unwrap();
+
+    let anchors = vec![root.der().to_vec()];
+    let auth = tokio::spawn({
+        let client = client.clone();
+        async move { client.authenticate(&anchors).await }
+    });
+
+    // Answer the challenge like a genuine device would.
+    let challenge = device.wait_for(1, |m| m.namespace == NAMESPACE).await;
+    let challenge = DeviceAuthMessage::decode(challenge[0].payload_binary.as_deref().unwrap())
+        .unwrap()
+        .challenge
+        .expect("no challenge sent");
+    let nonce = challenge.sender_nonce.unwrap();
+    let mut signed = nonce.clone();
+    signed.extend_from_slice(device.cert_der());
+
+    let reply = DeviceAuthMessage {
+        challenge: None,
+        response: Some(AuthResponse {
+            signature: device_key.sign(&signed).unwrap(),
+            client_auth_certificate: device_cert.der().to_vec(),
+            client_ca: vec![],
+            signature_algorithm: Some(SignatureAlgorithm::RsassaPkcs1v15 as i32),
+            sender_nonce: Some(nonce),
+            hash_algorithm: Some(HashAlgorithm::Sha256 as i32),
+            crl: None,
+        }),
+        error: None,
+    };
+    device.send(castru::proto::CastMessage {
+        protocol_version: 0,
+        source_id: "receiver-0".to_string(),
+        destination_id: client.sender_id().to_string(),
+        namespace: NAMESPACE.to_string(),
+        payload_type: 1,
+        payload_utf8: None,
+        payload_binary: Some(reply.encode_to_vec()),
+    });
+
+    let result = timeout(Duration::from_secs(5), auth).await.unwrap().unwrap();
+    let result = result.unwrap();
+    assert_eq!(result.credentials, CredentialTrust::Trusted);
+    assert!(result.nonce_signed);
+}
+
+#[tokio::test]
+async fn test_pinned_certificate_change_is_rejected() {
+    use castru::tls::{CertificatePolicy, FingerprintStore};
+    use castru::CastError;
+    use std::sync::Arc;
+
+    let store = Arc::new(FingerprintStore::in_memory());
+    let pinned = || CastClientConfig {
+        certificate_policy: CertificatePolicy::TrustOnFirstUse {
+            device_id: "uuid-1234".to_string(),
+            store: store.clone(),
+        },
+        ..Default::default()
+    };
+
+    // First use pins the certificate; reconnecting to the same device is fine.
+    let device = MockDevice::start().await;
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), pinned())
+        .await
+        .unwrap();
+    client.close().await.unwrap();
+    assert_eq!(
+        store.get("uuid-1234").as_deref(),
+        Some(castru::tls::fingerprint(device.cert_der()).as_str())
+    );
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), pinned())
+        .await
+        .unwrap();
+    client.close().await.unwrap();
+
+    // A different certificate for the same device ID is refused.
+    let impostor = MockDevice::start().await;
+    let res = CastClient::connect_with_config("127.0.0.1", impostor.port(), pinned()).await;
+    match res {
+        Err(CastError::CertificateMismatch { device_id, .. }) => assert_eq!(device_id, "uuid-1234"),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("connected despite a changed certificate"),
+    }
+}
+
+#[tokio::test]
+async fn test_oversized_frame_drops_connection() {
+    let device = MockDevice::start().await;
+    let config = fast_reconnect(ReconnectPolicy::default());
+    let client = CastClient::connect_with_config("127.0.0.1", device.port(), config)
+        .await
+        .unwrap();
+    let mut events = client.connection_events();
+    wait_for_state(&client, ConnectionState::Connected).await;
+
+    // A peer announcing a 4 GiB frame must not make us buffer it.
+    device.send_raw(&u32::MAX.to_be_bytes());
+
+    let reason = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(ConnectionEvent::Disconnected { reason }) = events.recv().await {
+                return reason;
+            }
+        }
+    })
+    .await
+    .expect("oversized frame did not drop the connection");
+    assert!(reason.contains("exceeds"), "unexpected reason: {}", reason);
+}
+
+#[tokio::test]
+async fn test_join_running_media_session() {
+    use castru::controllers::receiver::ReceiverController;
+    use castru::protocol::{media, receiver};
+
+    let device = MockDevice::start().await;
+    let client = CastClient::connect("127.0.0.1", device.port()).await.unwrap();
+    client.connect_receiver().await.unwrap();
+
+    let controller = ReceiverController::new(&client);
+    let join = tokio::spawn(async move { controller.join_media_session().await });
+
+    // Answer the receiver status request with the app a phone launched.
+    let request = device.wait_for(1, |m| m.namespace == receiver::NAMESPACE).await;
+    let request: serde_json::Value =
+        serde_json::from_str(request[0].payload_utf8.as_deref().unwrap()).unwrap();
+    device.send_json(
+        "receiver-0",
+        client.sender_id(),
+        receiver::NAMESPACE,
+        &serde_json::json!({
+            "type": "RECEIVER_STATUS",
+            "requestId": request["requestId"],
+            "status": {"applications": [{
+                "appId": "CC1AD845",
+                "displayName": "Default Media Receiver",
+                "sessionId": "session-1",
+                "transportId": "web-9",
+                "statusText": "Casting from phone",
+                "isIdleScreen": false,
+                "namespaces": [{"name": media::NAMESPACE}]
+            }]}
+        })
+        .to_string(),
+    );
+
+    // Then the media status request on the app's transport.
+    let request = device.wait_for(1, |m| m.namespace == media::NAMESPACE).await;
+    assert_eq!(request[0].destination_id, "web-9");
+    let request: serde_json::Value =
+        serde_json::from_str(request[0].payload_utf8.as_deref().unwrap()).unwrap();
+    assert_eq!(request["type"], "GET_STATUS");
+    device.send_json(
+        "web-9",
+        client.sender_id(),
+        media::NAMESPACE,
+        &serde_json::json!({
+            "type": "MEDIA_STATUS",
+            "requestId": request["requestId"],
+            "status": [{
+                "mediaSessionId": 42,
+                "playbackRate": 1,
+                "playerState": "PLAYING",
+                "currentTime": 61.5,
+                "supportedMediaCommands": 15
+            }]
+        })
+        .to_string(),
+    );
+
+    let session = timeout(Duration::from_secs(5), join)
+        .await
+        .expect("join did not finish")
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.application.transport_id, "web-9");
+    assert_eq!(session.media_session_id(), Some(42));
+    assert!(client.is_transport_connected("web-9"));
+    device.wait_for(1, |m| is_connect_to(m, "web-9")).await;
+
+    // Joining must not disturb playback: no LOAD, STOP or LAUNCH was sent.
+    let disruptive = device.received(|m| {
+        m.payload_utf8.as_deref().is_some_and(|p| {
+            p.contains("\"LOAD\"") || p.contains("\"STOP\"") || p.contains("\"LAUNCH\"")
+        })
+    });
+    assert!(disruptive.is_empty());
+
+    // Commands go to the joined session without the caller naming it.
+    session.pause().await.unwrap();
+    let pause = device
+        .wait_for(1, |m| {
+            m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"PAUSE\""))
+        })
+        .await;
+    assert_eq!(pause[0].destination_id, "web-9");
+    assert!(pause[0].payload_utf8.as_deref().unwrap().contains("\"mediaSessionId\":42"));
+}
+
+/// Answers the LAUNCH a client sends to `device` with the app running on `transport_id`.
+async fn answer_launch(device: &MockDevice, client: &CastClient, transport_id: &str) {
+    use castru::protocol::receiver;
+
+    let launch = device
+        .wait_for(1, |m| {
+            m.namespace == receiver::NAMESPACE
+                && m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"LAUNCH\""))
+        })
+        .await;
+    let launch: serde_json::Value =
+        serde_json::from_str(launch[0].payload_utf8.as_deref().unwrap()).unwrap();
+    device.send_json(
+        "receiver-0",
+        client.sender_id(),
+        receiver::NAMESPACE,
+        &serde_json::json!({
+            "type": "RECEIVER_STATUS",
+            "requestId": launch["requestId"],
+            "status": {"applications": [{
+                "appId": "CC1AD845",
+                "displayName": "Default Media Receiver",
+                "sessionId": format!("session-{}", transport_id),
+                "transportId": transport_id,
+                "statusText": "",
+                "isIdleScreen": false
+            }]}
+        })
+        .to_string(),
+    );
+}
+
+fn send_media_status(device: &MockDevice, client: &CastClient, player_state: &str, time: f32) {
+    device.send_json(
+        "web-1",
+        client.sender_id(),
+        castru::protocol::media::NAMESPACE,
+        &serde_json::json!({
+            "type": "MEDIA_STATUS",
+            "requestId": 0,
+            "status": [{
+                "mediaSessionId": 1,
+                "playbackRate": 1,
+                "playerState": player_state,
+                "currentTime": time,
+                "supportedMediaCommands": 15
+            }]
+        })
+        .to_string(),
+    );
+}
+
+/// The `currentTime` of every SEEK `device` received.
+fn seek_times(device: &MockDevice) -> Vec<f64> {
+    device
+        .received(|m| m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"SEEK\"")))
+        .iter()
+        .map(|m| {
+            let payload: serde_json::Value =
+                serde_json::from_str(m.payload_utf8.as_deref().unwrap()).unwrap();
+            payload["currentTime"].as_f64().unwrap()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_group_keeps_members_in_step() {
+    use castru::controllers::group::CastGroup;
+    use castru::protocol::media::MediaInformation;
+
+    let kitchen = MockDevice::start().await;
+    let lounge = MockDevice::start().await;
+    let kitchen_client = CastClient::connect("127.0.0.1", kitchen.port()).await.unwrap();
+    let lounge_client = CastClient::connect("127.0.0.1", lounge.port()).await.unwrap();
+    kitchen_client.connect_receiver().await.unwrap();
+    lounge_client.connect_receiver().await.unwrap();
+
+    let mut group = CastGroup::new(vec![
+        ("Kitchen".to_string(), kitchen_client.clone()),
+        ("Lounge".to_string(), lounge_client.clone()),
+    ]);
+    let (launched, (), ()) = tokio::join!(
+        group.launch(),
+        answer_launch(&kitchen, &kitchen_client, "web-1"),
+        answer_launch(&lounge, &lounge_client, "web-1"),
+    );
+    launched.unwrap();
+
+    group
+        .load(MediaInformation::new("http://example.com/a.mp4", "video/mp4"), true, 0.0, None)
+        .await
+        .unwrap();
+    for device in [&kitchen, &lounge] {
+        device
+            .wait_for(1, |m| {
+                m.destination_id == "web-1"
+                    && m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"LOAD\""))
+            })
+            .await;
+    }
+
+    // The lounge fell 10s behind the kitchen, which leads.
+    let mut states = group.watch_states();
+    send_media_status(&kitchen, &kitchen_client, "PLAYING", 100.0);
+    send_media_status(&lounge, &lounge_client, "PLAYING", 90.0);
+    timeout(
+        Duration::from_secs(5),
+        states.wait_for(|s| s[0].current_time == 100.0 && s[1].current_time == 90.0),
+    )
+    .await
+    .expect("member states were not updated")
+    .unwrap();
+
+    assert_eq!(group.resync(1.0).await.unwrap(), 1);
+    lounge.wait_for(1, |m| m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"SEEK\""))).await;
+    let seek = seek_times(&lounge)[0];
+    assert!((100.0..101.0).contains(&seek), "lounge seeked to {}", seek);
+    assert!(seek_times(&kitchen).is_empty());
+
+    // Pausing lines both members up on the leader's position.
+    group.pause().await.unwrap();
+    for device in [&kitchen, &lounge] {
+        device
+            .wait_for(1, |m| m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"PAUSE\"")))
+            .await;
+    }
+    kitchen.wait_for(1, |m| m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"SEEK\""))).await;
+    lounge.wait_for(2, |m| m.payload_utf8.as_deref().is_some_and(|p| p.contains("\"SEEK\""))).await;
+    assert_eq!(seek_times(&kitchen)[0], seek_times(&lounge)[1]);
+}